@@ -2,11 +2,40 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+#[cfg(feature = "build-mode-pyoxidizer")]
 use pyoxidizerlib::app_packaging::repackage::run_from_build;
+#[cfg(feature = "build-mode-pyoxidizer")]
 use pyoxidizerlib::logging::logger_from_env;
 use std::env;
+#[cfg(feature = "build-mode-pyoxidizer")]
 use std::path::PathBuf;
+#[cfg(not(feature = "build-mode-pyoxidizer"))]
+use std::process::Command;
 
+/// Define the Python version cfgs otherwise emitted by PyOxidizer.
+#[cfg(not(feature = "build-mode-pyoxidizer"))]
+fn main() {
+    // python3-sys exports the path of the Python interpreter it was
+    // configured with.
+    let python = env::var("DEP_PYTHON3_PYTHON_INTERPRETER")
+        .expect("DEP_PYTHON3_PYTHON_INTERPRETER not defined");
+
+    let output = Command::new(&python)
+        .args(&["-c", "import sys; print(sys.version_info[1])"])
+        .output()
+        .expect("unable to run Python");
+
+    let minor_version = String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse::<u32>()
+        .expect("unable to parse Python version");
+
+    for minor in 4..=minor_version {
+        println!("cargo:rustc-cfg=Py_3_{}", minor);
+    }
+}
+
+#[cfg(feature = "build-mode-pyoxidizer")]
 fn main() {
     // We support using pre-built artifacts, in which case we emit the
     // cargo metadata lines from the "original" build to "register" the
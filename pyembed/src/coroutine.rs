@@ -0,0 +1 @@
+../../pyoxidizer/src/pyembed/coroutine.rs
\ No newline at end of file
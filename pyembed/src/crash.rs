@@ -0,0 +1 @@
+../../pyoxidizer/src/pyembed/crash.rs
\ No newline at end of file
@@ -0,0 +1 @@
+../../pyoxidizer/src/pyembed/extension.rs
\ No newline at end of file
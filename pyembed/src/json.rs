@@ -0,0 +1 @@
+../../pyoxidizer/src/pyembed/json.rs
\ No newline at end of file
@@ -0,0 +1 @@
+../../pyoxidizer/src/pyembed/overlay.rs
\ No newline at end of file
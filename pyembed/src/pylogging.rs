@@ -0,0 +1 @@
+../../pyoxidizer/src/pyembed/pylogging.rs
\ No newline at end of file
@@ -0,0 +1 @@
+../../pyoxidizer/src/pyembed/selftest.rs
\ No newline at end of file
@@ -0,0 +1 @@
+../../pyoxidizer/src/pyembed/zipimport.rs
\ No newline at end of file
@@ -7,6 +7,7 @@ use std::env;
 use std::path::{Path, PathBuf};
 
 use super::environment::EnvironmentContext;
+use crate::py_packaging::audit::SourceUsage;
 use crate::py_packaging::config::{EmbeddedPythonConfig, RawAllocator, RunMode};
 use crate::py_packaging::distribution::{ExtensionModuleFilter, PythonDistributionLocation};
 
@@ -59,11 +60,23 @@ pub struct PackagingFilterInclude {
     pub glob_files: Vec<String>,
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub struct PackagingFileDependentPackages {
+    pub usages: Vec<SourceUsage>,
+    pub excludes: Vec<String>,
+    pub install_location: InstallLocation,
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct PackagingWriteLicenseFiles {
     pub path: String,
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub struct PackagingTclLibraryFiles {
+    pub path: String,
+}
+
 #[derive(Clone, Debug)]
 pub enum PythonPackaging {
     StdlibExtensionsPolicy(PackagingStdlibExtensionsPolicy),
@@ -72,7 +85,9 @@ pub enum PythonPackaging {
     StdlibExtensionVariant(PackagingStdlibExtensionVariant),
     Stdlib(PackagingStdlib),
     FilterInclude(PackagingFilterInclude),
+    FileDependentPackages(PackagingFileDependentPackages),
     WriteLicenseFiles(PackagingWriteLicenseFiles),
+    TclLibraryFiles(PackagingTclLibraryFiles),
 }
 
 #[derive(Clone, Debug)]
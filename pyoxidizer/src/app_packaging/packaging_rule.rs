@@ -287,7 +287,10 @@ pub fn resolve_python_packaging(
 
         PythonPackaging::WriteLicenseFiles(_) => Vec::new(),
 
-        // This is a no-op because it can only be handled at a higher level.
+        PythonPackaging::TclLibraryFiles(_) => Vec::new(),
+
+        // These are no-ops because they can only be handled at a higher level.
         PythonPackaging::FilterInclude(_) => Vec::new(),
+        PythonPackaging::FileDependentPackages(_) => Vec::new(),
     }
 }
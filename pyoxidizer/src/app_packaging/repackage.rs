@@ -13,24 +13,33 @@ use std::io::{BufRead, BufReader, BufWriter, Error as IOError, Write};
 use std::path::{Path, PathBuf};
 
 use super::config::{
-    eval_starlark_config_file, find_pyoxidizer_config_file_env, Config, PythonPackaging,
+    eval_starlark_config_file, find_pyoxidizer_config_file_env, Config, InstallLocation,
+    PythonPackaging,
 };
 use super::packaging_rule::{
     packages_from_module_names, resolve_python_packaging, ResourceAction, ResourceLocation,
 };
 use super::state::{BuildContext, PackagingState};
+use crate::environment::canonicalize_path;
+use crate::py_packaging::audit::{
+    flagged_packages, top_level_package, SourceAuditor, SourceFinding,
+};
 use crate::py_packaging::bytecode::{python_source_encoding, BytecodeCompiler, CompileMode};
+use crate::py_packaging::config::SslCaBundle;
 use crate::py_packaging::distribution::{
     resolve_python_distribution_archive, ExtensionModule, ParsedPythonDistribution,
     PythonDistributionLocation,
 };
 use crate::py_packaging::embedded_resource::EmbeddedPythonResources;
 use crate::py_packaging::libpython::{derive_importlib, link_libpython};
-use crate::py_packaging::pyembed::{derive_python_config, write_data_rs};
+use crate::py_packaging::pyembed::{
+    derive_python_config, link_packed_data, supports_linked_data, write_data_rs,
+};
 use crate::py_packaging::resource::{
     packages_from_module_name, AppRelativeResources, PackagedModuleBytecode, PackagedModuleSource,
     PythonResource,
 };
+use crate::py_packaging::terminfo::{resolve_terminfo_entries, write_terminfo_entries};
 
 lazy_static! {
     /// Python extension modules that should never be included.
@@ -208,6 +217,9 @@ pub struct PythonResources {
 
     /// Path where to write license files.
     pub license_files_path: Option<String>,
+
+    /// Path where to install Tcl/Tk library files.
+    pub tcl_files_path: Option<String>,
 }
 
 fn read_resource_names_file(path: &Path) -> Result<BTreeSet<String>, IOError> {
@@ -239,19 +251,84 @@ fn filter_btreemap<V>(logger: &slog::Logger, m: &mut BTreeMap<String, V>, f: &BT
     }
 }
 
+/// Move entries belonging to a set of top-level packages between maps.
+fn move_packages<V>(
+    logger: &slog::Logger,
+    from: &mut BTreeMap<String, V>,
+    to: &mut BTreeMap<String, V>,
+    packages: &BTreeSet<String>,
+) {
+    let keys: Vec<String> = from
+        .keys()
+        .filter(|key| packages.contains(top_level_package(key)))
+        .cloned()
+        .collect();
+
+    for key in keys {
+        info!(logger, "moving {}", key);
+        let value = from.remove(&key).unwrap();
+        to.insert(key, value);
+    }
+}
+
 struct BytecodeRequest {
     source: Vec<u8>,
     optimize_level: i32,
     is_package: bool,
 }
 
+/// Audit the source of embedded modules for features that rely on the filesystem.
+///
+/// Findings are reported as they are encountered and returned keyed by module
+/// name.
+fn audit_embedded_sources(
+    logger: &slog::Logger,
+    dist: &ParsedPythonDistribution,
+    sources: &BTreeMap<String, PackagedModuleSource>,
+    bytecode_requests: &BTreeMap<String, BytecodeRequest>,
+) -> Result<BTreeMap<String, Vec<SourceFinding>>, String> {
+    let mut auditor = SourceAuditor::new(&dist.python_exe);
+    let mut res = BTreeMap::new();
+
+    let modules = bytecode_requests
+        .iter()
+        .map(|(name, request)| (name, &request.source))
+        .chain(sources.iter().map(|(name, module)| (name, &module.source)));
+
+    for (name, source) in modules {
+        // Standard library modules are never relocated. Auditing them would
+        // flag packages like `ctypes` that must remain embedded.
+        if res.contains_key(name) || dist.py_modules.contains_key(name) {
+            continue;
+        }
+
+        let findings = auditor
+            .audit(source, name)
+            .or_else(|e| Err(format!("error auditing {}: {}", name, e)))?;
+
+        for finding in &findings {
+            warn!(
+                logger,
+                "{} uses {} on line {}",
+                name,
+                finding.usage.as_str(),
+                finding.line
+            );
+        }
+
+        res.insert(name.clone(), findings);
+    }
+
+    Ok(res)
+}
+
 /// Resolves a series of packaging rules to a final set of resources to package.
 #[allow(clippy::cognitive_complexity)]
 pub fn resolve_python_resources(
     logger: &slog::Logger,
     context: &BuildContext,
     dist: &ParsedPythonDistribution,
-) -> PythonResources {
+) -> Result<PythonResources, String> {
     let packages = &context.config.python_packaging;
 
     // Since bytecode has a non-trivial cost to generate, our strategy is to accumulate
@@ -263,6 +340,7 @@ pub fn resolve_python_resources(
     let mut embedded_bytecode_requests: BTreeMap<String, BytecodeRequest> = BTreeMap::new();
     let mut embedded_resources: BTreeMap<String, BTreeMap<String, Vec<u8>>> = BTreeMap::new();
     let mut embedded_built_extension_modules = BTreeMap::new();
+    let mut embedded_zip_archives: BTreeMap<String, BTreeMap<String, Vec<u8>>> = BTreeMap::new();
 
     let mut app_relative: BTreeMap<String, AppRelativeResources> = BTreeMap::new();
     let mut app_relative_bytecode_requests: BTreeMap<String, BTreeMap<String, BytecodeRequest>> =
@@ -270,6 +348,7 @@ pub fn resolve_python_resources(
 
     let mut read_files: Vec<PathBuf> = Vec::new();
     let mut license_files_path = None;
+    let mut tcl_files_path = None;
 
     for packaging in packages {
         warn!(logger, "processing packaging rule: {:?}", packaging);
@@ -553,6 +632,29 @@ pub fn resolve_python_resources(
                     );
                     embedded_built_extension_modules.remove(&em.name);
                 }
+                (
+                    ResourceAction::Add,
+                    ResourceLocation::Embedded,
+                    PythonResource::ZipArchive(archive),
+                ) => {
+                    info!(logger, "adding embedded zip archive: {}", archive.name);
+                    embedded_zip_archives.insert(archive.name, archive.files);
+                }
+                (
+                    ResourceAction::Add,
+                    ResourceLocation::AppRelative { .. },
+                    PythonResource::ZipArchive(..),
+                ) => {
+                    panic!("adding app-relative zip archives not supported");
+                }
+                (
+                    ResourceAction::Remove,
+                    ResourceLocation::Embedded,
+                    PythonResource::ZipArchive(archive),
+                ) => {
+                    info!(logger, "removing embedded zip archive: {}", archive.name);
+                    embedded_zip_archives.remove(&archive.name);
+                }
             }
         }
 
@@ -560,6 +662,59 @@ pub fn resolve_python_resources(
             license_files_path = Some(rule.path.clone());
         }
 
+        if let PythonPackaging::TclLibraryFiles(rule) = packaging {
+            tcl_files_path = Some(rule.path.clone());
+        }
+
+        if let PythonPackaging::FileDependentPackages(rule) = packaging {
+            let path = match &rule.install_location {
+                InstallLocation::AppRelative { path } => path.clone(),
+                InstallLocation::Embedded => {
+                    panic!("FileDependentPackages install location must be app-relative")
+                }
+            };
+
+            info!(
+                logger,
+                "auditing embedded module sources from {:?}", packaging
+            );
+            let findings = audit_embedded_sources(
+                logger,
+                dist,
+                &embedded_sources,
+                &embedded_bytecode_requests,
+            )?;
+            let packages = flagged_packages(&findings, &rule.usages, &rule.excludes);
+
+            info!(
+                logger,
+                "installing {} packages relying on the filesystem to app-relative {}: {}",
+                packages.len(),
+                path,
+                packages.iter().cloned().collect::<Vec<String>>().join(", ")
+            );
+
+            let app_relative_entry = app_relative.entry(path.clone()).or_default();
+            move_packages(
+                logger,
+                &mut embedded_sources,
+                &mut app_relative_entry.module_sources,
+                &packages,
+            );
+            move_packages(
+                logger,
+                &mut embedded_resources,
+                &mut app_relative_entry.resources,
+                &packages,
+            );
+            move_packages(
+                logger,
+                &mut embedded_bytecode_requests,
+                app_relative_bytecode_requests.entry(path).or_default(),
+                &packages,
+            );
+        }
+
         if let PythonPackaging::FilterInclude(rule) = packaging {
             let mut include_names: BTreeSet<String> = BTreeSet::new();
 
@@ -686,6 +841,28 @@ pub fn resolve_python_resources(
         }
     }
 
+    // Add the distribution's sysconfig data, which may have been filtered or
+    // not added in the first place.
+    if context.config.embedded_python_config.sysconfig_data {
+        for (name, path) in &dist.py_modules {
+            if name.starts_with("_sysconfigdata") && !embedded_bytecode_requests.contains_key(name)
+            {
+                info!(logger, "adding embedded sysconfig data module {}", name);
+                let source = fs::read(path)
+                    .or_else(|e| Err(format!("unable to read {}: {}", path.display(), e)))?;
+
+                embedded_bytecode_requests.insert(
+                    name.clone(),
+                    BytecodeRequest {
+                        source,
+                        optimize_level: 0,
+                        is_package: false,
+                    },
+                );
+            }
+        }
+    }
+
     // Remove extension modules that have problems.
     for e in OS_IGNORE_EXTENSIONS.as_slice() {
         warn!(
@@ -695,6 +872,23 @@ pub fn resolve_python_resources(
         embedded_extension_modules.remove(&String::from(*e));
     }
 
+    // Tcl/Tk library files are only needed by _tkinter.
+    if tcl_files_path.is_some() {
+        if !embedded_extension_modules.contains_key("_tkinter") {
+            warn!(
+                logger,
+                "not installing Tcl/Tk library files because _tkinter is not embedded"
+            );
+            tcl_files_path = None;
+        } else if dist.tcl_library_path.is_none() {
+            warn!(
+                logger,
+                "not installing Tcl/Tk library files because the distribution does not provide them"
+            );
+            tcl_files_path = None;
+        }
+    }
+
     // Audit Python source for __file__, which could be problematic.
     let mut file_seen = false;
 
@@ -718,7 +912,7 @@ pub fn resolve_python_resources(
     }
 
     if file_seen {
-        warn!(logger, "__file__ was encountered in some modules; PyOxidizer does not set __file__ and this may create problems at run-time; see https://github.com/indygreg/PyOxidizer/issues/69 for more");
+        warn!(logger, "__file__ was encountered in some modules; PyOxidizer does not set __file__ and this may create problems at run-time; see https://github.com/indygreg/PyOxidizer/issues/69 for more; the FileDependentPackages packaging rule can install affected packages on the filesystem");
     }
 
     let mut embedded_bytecodes: BTreeMap<String, PackagedModuleBytecode> = BTreeMap::new();
@@ -841,7 +1035,7 @@ pub fn resolve_python_resources(
         })
         .collect();
 
-    PythonResources {
+    Ok(PythonResources {
         embedded: EmbeddedPythonResources {
             module_sources: embedded_sources,
             module_bytecodes: embedded_bytecodes,
@@ -850,11 +1044,13 @@ pub fn resolve_python_resources(
             resources: embedded_resources,
             extension_modules: embedded_extension_modules,
             built_extension_modules: embedded_built_extension_modules,
+            zip_archives: embedded_zip_archives,
         },
         app_relative,
         read_files,
         license_files_path,
-    }
+        tcl_files_path,
+    })
 }
 
 /// Install all app-relative files next to the generated binary.
@@ -863,6 +1059,7 @@ fn install_app_relative(
     context: &BuildContext,
     path: &str,
     app_relative: &AppRelativeResources,
+    cache_tag: &str,
 ) -> Result<(), String> {
     let dest_path = context.app_exe_path.parent().unwrap().join(path);
 
@@ -939,9 +1136,9 @@ fn install_app_relative(
         }
 
         module_path.set_file_name(format!(
-            // TODO determine string from Python distribution in use.
-            "{}.cpython-37.pyc",
-            module_path.file_name().unwrap().to_string_lossy()
+            "{}.{}.pyc",
+            module_path.file_name().unwrap().to_string_lossy(),
+            cache_tag
         ));
 
         info!(
@@ -1056,6 +1253,35 @@ pub fn package_project(logger: &slog::Logger, context: &mut BuildContext) -> Res
         }
     }
 
+    if let (Some(tcl_files_path), Some(source)) = (state.tcl_files_path, state.tcl_library_path) {
+        let dest = if tcl_files_path.is_empty() {
+            context.app_path.clone()
+        } else {
+            context.app_path.join(tcl_files_path)
+        };
+
+        warn!(
+            logger,
+            "installing Tcl/Tk library files to {}",
+            dest.display()
+        );
+
+        for entry in walkdir::WalkDir::new(&source) {
+            let entry = entry.or_else(|e| Err(e.to_string()))?;
+            let rel = entry
+                .path()
+                .strip_prefix(&source)
+                .or_else(|e| Err(e.to_string()))?;
+            let dest_path = dest.join(rel);
+
+            if entry.file_type().is_dir() {
+                create_dir_all(&dest_path).or_else(|e| Err(e.to_string()))?;
+            } else {
+                fs::copy(entry.path(), &dest_path).or_else(|e| Err(e.to_string()))?;
+            }
+        }
+    }
+
     if !state.app_relative_resources.is_empty() {
         warn!(
             logger,
@@ -1065,7 +1291,7 @@ pub fn package_project(logger: &slog::Logger, context: &mut BuildContext) -> Res
     }
 
     for (path, v) in &state.app_relative_resources {
-        install_app_relative(logger, context, path.as_str(), v).unwrap();
+        install_app_relative(logger, context, path.as_str(), v, &state.bytecode_cache_tag).unwrap();
     }
 
     warn!(
@@ -1104,6 +1330,9 @@ pub struct EmbeddedPythonConfig {
     /// Path to file containing packed Python resources data.
     pub resources_path: PathBuf,
 
+    /// Path to file containing packed zip archives data.
+    pub zip_archives_path: PathBuf,
+
     /// Path to library file containing Python.
     pub libpython_path: PathBuf,
 
@@ -1129,7 +1358,7 @@ pub fn process_config(
     logger: &slog::Logger,
     context: &mut BuildContext,
     opt_level: &str,
-) -> EmbeddedPythonConfig {
+) -> Result<EmbeddedPythonConfig, String> {
     let mut cargo_metadata: Vec<String> = Vec::new();
 
     let config = &context.config;
@@ -1147,7 +1376,7 @@ pub fn process_config(
     ));
 
     if !dest_dir.exists() {
-        create_dir_all(dest_dir).unwrap();
+        create_dir_all(dest_dir).or_else(|e| Err(e.to_string()))?;
     }
 
     if let PythonDistributionLocation::Local { local_path, .. } = &config.python_distribution {
@@ -1181,20 +1410,22 @@ pub fn process_config(
     let importlib = derive_importlib(&dist);
 
     let importlib_bootstrap_path = Path::new(&dest_dir).join("importlib_bootstrap");
-    let mut fh = fs::File::create(&importlib_bootstrap_path).unwrap();
-    fh.write_all(&importlib.bootstrap_bytecode).unwrap();
+    fs::write(&importlib_bootstrap_path, &importlib.bootstrap_bytecode)
+        .or_else(|e| Err(e.to_string()))?;
 
     let importlib_bootstrap_external_path =
         Path::new(&dest_dir).join("importlib_bootstrap_external");
-    let mut fh = fs::File::create(&importlib_bootstrap_external_path).unwrap();
-    fh.write_all(&importlib.bootstrap_external_bytecode)
-        .unwrap();
+    fs::write(
+        &importlib_bootstrap_external_path,
+        &importlib.bootstrap_external_bytecode,
+    )
+    .or_else(|e| Err(e.to_string()))?;
 
     warn!(
         logger,
         "resolving Python resources (modules, extensions, resource data, etc)..."
     );
-    let resources = resolve_python_resources(logger, context, &dist);
+    let resources = resolve_python_resources(logger, context, &dist)?;
 
     warn!(
         logger,
@@ -1248,34 +1479,115 @@ pub fn process_config(
     let module_names_path = Path::new(&dest_dir).join("py-module-names");
     let py_modules_path = Path::new(&dest_dir).join("py-modules");
     let resources_path = Path::new(&dest_dir).join("python-resources");
+    let zip_archives_path = Path::new(&dest_dir).join("python-zip-archives");
 
     let mut module_names_fh =
-        BufWriter::new(fs::File::create(&module_names_path).expect("error creating file"));
+        BufWriter::new(fs::File::create(&module_names_path).or_else(|e| Err(e.to_string()))?);
     let mut modules_fh =
-        BufWriter::new(fs::File::create(&py_modules_path).expect("error creating file"));
+        BufWriter::new(fs::File::create(&py_modules_path).or_else(|e| Err(e.to_string()))?);
     let mut resources_fh =
-        BufWriter::new(fs::File::create(&resources_path).expect("error creating file"));
-
-    resources
-        .embedded
-        .write_blobs(&mut module_names_fh, &mut modules_fh, &mut resources_fh);
+        BufWriter::new(fs::File::create(&resources_path).or_else(|e| Err(e.to_string()))?);
+    let mut zip_archives_fh =
+        BufWriter::new(fs::File::create(&zip_archives_path).or_else(|e| Err(e.to_string()))?);
+
+    resources.embedded.write_blobs(
+        &mut module_names_fh,
+        &mut modules_fh,
+        &mut resources_fh,
+        &mut zip_archives_fh,
+    );
 
-    module_names_fh.flush().unwrap();
-    modules_fh.flush().unwrap();
-    resources_fh.flush().unwrap();
+    module_names_fh.flush().or_else(|e| Err(e.to_string()))?;
+    modules_fh.flush().or_else(|e| Err(e.to_string()))?;
+    resources_fh.flush().or_else(|e| Err(e.to_string()))?;
+    zip_archives_fh.flush().or_else(|e| Err(e.to_string()))?;
 
     warn!(
         logger,
         "{} bytes of Python module data written to {}",
-        py_modules_path.metadata().unwrap().len(),
+        py_modules_path
+            .metadata()
+            .or_else(|e| Err(e.to_string()))?
+            .len(),
         py_modules_path.display()
     );
     warn!(
         logger,
         "{} bytes of resources data written to {}",
-        resources_path.metadata().unwrap().len(),
+        resources_path
+            .metadata()
+            .or_else(|e| Err(e.to_string()))?
+            .len(),
         resources_path.display()
     );
+    info!(
+        logger,
+        "{} bytes of zip archives data written to {}",
+        zip_archives_path
+            .metadata()
+            .or_else(|e| Err(e.to_string()))?
+            .len(),
+        zip_archives_path.display()
+    );
+
+    let terminfo_path = Path::new(&dest_dir).join("terminfo");
+    let terminfo_entries = resolve_terminfo_entries(
+        &config.embedded_python_config.terminfo_embed,
+        config
+            .embedded_python_config
+            .terminfo_embed_dir
+            .as_ref()
+            .map(|dir| dir.as_str()),
+    )?;
+    write_terminfo_entries(
+        BufWriter::new(fs::File::create(&terminfo_path).or_else(|e| Err(e.to_string()))?),
+        &terminfo_entries,
+    )
+    .or_else(|e| Err(format!("unable to write terminfo data: {}", e)))?;
+
+    info!(
+        logger,
+        "{} terminfo entries written to {}",
+        terminfo_entries.len(),
+        terminfo_path.display()
+    );
+
+    let ssl_ca_bundle_path = Path::new(&dest_dir).join("ssl-ca-bundle.pem");
+    let ssl_ca_bundle = match &config.embedded_python_config.ssl_ca_bundle {
+        Some(SslCaBundle::Certifi) => resources
+            .embedded
+            .resources
+            .get("certifi")
+            .and_then(|resources| resources.get("cacert.pem"))
+            .or_else(|| {
+                resources
+                    .app_relative
+                    .values()
+                    .filter_map(|app_relative| app_relative.resources.get("certifi"))
+                    .filter_map(|resources| resources.get("cacert.pem"))
+                    .next()
+            })
+            .cloned()
+            .ok_or_else(|| {
+                "ssl_ca_bundle is certifi but certifi/cacert.pem is not packaged".to_string()
+            })?,
+        Some(SslCaBundle::File(path)) => {
+            let path = context.config_parent_path.join(path);
+            cargo_metadata.push(format!("cargo:rerun-if-changed={}", path.display()));
+            fs::read(&path).or_else(|e| Err(format!("unable to read {}: {}", path.display(), e)))?
+        }
+        None => Vec::new(),
+    };
+    fs::write(&ssl_ca_bundle_path, &ssl_ca_bundle).or_else(|e| Err(e.to_string()))?;
+
+    if !ssl_ca_bundle.is_empty() {
+        info!(
+            logger,
+            "{} bytes of CA certificates written to {}",
+            ssl_ca_bundle.len(),
+            ssl_ca_bundle_path.display()
+        );
+    }
 
     // Produce a static library containing the Python bits we need.
     warn!(
@@ -1303,6 +1615,47 @@ pub fn process_config(
         "processing embedded python config: {:?}", config.embedded_python_config
     );
 
+    // Tcl/Tk library files are installed relative to the executable.
+    let (tcl_library, tk_library) = match &resources.tcl_files_path {
+        Some(path) => {
+            let (tcl, tk) = dist.tcl_tk_library_names();
+            let prefix = if path.is_empty() {
+                "$ORIGIN".to_string()
+            } else {
+                format!("$ORIGIN/{}", path)
+            };
+
+            (
+                tcl.map(|name| format!("{}/{}", prefix, name)),
+                tk.map(|name| format!("{}/{}", prefix, name)),
+            )
+        }
+        None => (None, None),
+    };
+
+    // Packed data is read from the artifacts directory at run-time so it can
+    // be regenerated without rebuilding the binary.
+    let packed_data_directory = if config.embedded_python_config.packed_data_at_runtime {
+        Some(canonicalize_path(dest_dir).or_else(|e| Err(e.to_string()))?)
+    } else {
+        None
+    };
+
+    // Packed data is linked into the binary as symbols when possible, as
+    // having rustc process large blobs via include_bytes!() is slow.
+    let linked_data =
+        packed_data_directory.is_none() && supports_linked_data(&context.target_triple);
+    if linked_data {
+        cargo_metadata.extend(link_packed_data(
+            logger,
+            &[&py_modules_path, &resources_path, &zip_archives_path],
+            dest_dir,
+            &context.host_triple,
+            &context.target_triple,
+            opt_level,
+        ));
+    }
+
     let python_config_rs = derive_python_config(
         &config.embedded_python_config,
         &config.run,
@@ -1310,6 +1663,13 @@ pub fn process_config(
         &importlib_bootstrap_external_path,
         &py_modules_path,
         &resources_path,
+        &zip_archives_path,
+        &packed_data_directory,
+        linked_data,
+        &terminfo_path,
+        &tcl_library,
+        &tk_library,
+        &ssl_ca_bundle_path,
     );
 
     let dest_path = Path::new(&dest_dir).join("data.rs");
@@ -1326,12 +1686,18 @@ pub fn process_config(
     // logic in this function.
     let cargo_metadata_path = Path::new(&dest_dir).join("cargo_metadata.txt");
     fs::write(&cargo_metadata_path, cargo_metadata.join("\n").as_bytes())
-        .expect("unable to write cargo_metadata.txt");
+        .or_else(|e| Err(e.to_string()))?;
 
     let packaging_state = PackagingState {
         license_files_path: resources.license_files_path,
         license_infos: libpython_info.license_infos,
         app_relative_resources: resources.app_relative,
+        bytecode_cache_tag: dist.cache_tag(),
+        tcl_library_path: match resources.tcl_files_path {
+            Some(_) => dist.tcl_library_path.clone(),
+            None => None,
+        },
+        tcl_files_path: resources.tcl_files_path,
     };
 
     let packaging_state_path = dest_dir.join("packaging_state.cbor");
@@ -1340,14 +1706,13 @@ pub fn process_config(
         "writing packaging state to {}",
         packaging_state_path.display()
     );
-    let mut fh = BufWriter::new(
-        fs::File::create(&packaging_state_path).expect("unable to create packaging_state.cbor"),
-    );
-    serde_cbor::to_writer(&mut fh, &packaging_state).unwrap();
+    let mut fh =
+        BufWriter::new(fs::File::create(&packaging_state_path).or_else(|e| Err(e.to_string()))?);
+    serde_cbor::to_writer(&mut fh, &packaging_state).or_else(|e| Err(e.to_string()))?;
 
     context.packaging_state = Some(packaging_state);
 
-    EmbeddedPythonConfig {
+    Ok(EmbeddedPythonConfig {
         config: config.clone(),
         python_distribution_path,
         importlib_bootstrap_path,
@@ -1355,11 +1720,12 @@ pub fn process_config(
         module_names_path,
         py_modules_path,
         resources_path,
+        zip_archives_path,
         libpython_path: libpython_info.path,
         cargo_metadata,
         python_config_rs,
         packaging_state_path,
-    }
+    })
 }
 
 /// Runs packaging/embedding from the context of a build script.
@@ -1418,7 +1784,12 @@ pub fn run_from_build(logger: &slog::Logger, build_script: &str) {
     )
     .unwrap();
 
-    for line in process_config(logger, &mut context, &opt_level).cargo_metadata {
+    let embedded = match process_config(logger, &mut context, &opt_level) {
+        Ok(v) => v,
+        Err(e) => panic!("error processing PyOxidizer config: {}", e),
+    };
+
+    for line in embedded.cargo_metadata {
         println!("{}", line);
     }
 }
@@ -17,8 +17,12 @@ use crate::py_packaging::resource::AppRelativeResources;
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PackagingState {
     pub app_relative_resources: BTreeMap<String, AppRelativeResources>,
+    /// Tag for bytecode files of the Python distribution. e.g. ``cpython-37``.
+    pub bytecode_cache_tag: String,
     pub license_files_path: Option<String>,
     pub license_infos: BTreeMap<String, Vec<LicenseInfo>>,
+    pub tcl_files_path: Option<String>,
+    pub tcl_library_path: Option<PathBuf>,
 }
 
 /// Represents environment for a build.
@@ -31,13 +31,21 @@ lazy_static! {
         let mut res: BTreeMap<&'static str, &'static [u8]> = BTreeMap::new();
 
         res.insert("config.rs", include_bytes!("pyembed/config.rs"));
+        res.insert("coroutine.rs", include_bytes!("pyembed/coroutine.rs"));
+        res.insert("crash.rs", include_bytes!("pyembed/crash.rs"));
         res.insert("lib.rs", include_bytes!("pyembed/lib.rs"));
         res.insert("data.rs", include_bytes!("pyembed/data.rs"));
+        res.insert("extension.rs", include_bytes!("pyembed/extension.rs"));
         res.insert("importer.rs", include_bytes!("pyembed/importer.rs"));
+        res.insert("json.rs", include_bytes!("pyembed/json.rs"));
         res.insert("osutils.rs", include_bytes!("pyembed/osutils.rs"));
+        res.insert("overlay.rs", include_bytes!("pyembed/overlay.rs"));
         res.insert("pyalloc.rs", include_bytes!("pyembed/pyalloc.rs"));
         res.insert("pyinterp.rs", include_bytes!("pyembed/pyinterp.rs"));
+        res.insert("pylogging.rs", include_bytes!("pyembed/pylogging.rs"));
         res.insert("pystr.rs", include_bytes!("pyembed/pystr.rs"));
+        res.insert("selftest.rs", include_bytes!("pyembed/selftest.rs"));
+        res.insert("zipimport.rs", include_bytes!("pyembed/zipimport.rs"));
 
         res
     };
@@ -429,7 +437,7 @@ fn build_pyoxidizer_artifacts(
         .expect("unable to canonicalize artifacts directory");
 
     if !artifacts_current(logger, &context.config_path, &pyoxidizer_artifacts_path) {
-        process_config(logger, context, "0");
+        process_config(logger, context, "0")?;
     }
 
     Ok(())
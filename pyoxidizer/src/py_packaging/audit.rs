@@ -0,0 +1,308 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Audit Python source for features that don't work when importing from memory.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Error, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+pub const SOURCE_AUDITOR: &[u8] = include_bytes!("sourceaudit.py");
+
+/// A use of a Python feature that is problematic when importing from memory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SourceUsage {
+    /// Module references `__file__`.
+    File,
+    /// Module imports `pkg_resources`.
+    PkgResources,
+    /// Module loads a shared library via `ctypes`.
+    CtypesLibrary,
+}
+
+impl SourceUsage {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SourceUsage::File => "__file__",
+            SourceUsage::PkgResources => "pkg_resources",
+            SourceUsage::CtypesLibrary => "ctypes",
+        }
+    }
+}
+
+pub fn resolve_source_usage(value: &str) -> Result<SourceUsage, String> {
+    match value {
+        "__file__" => Ok(SourceUsage::File),
+        "pkg_resources" => Ok(SourceUsage::PkgResources),
+        "ctypes" => Ok(SourceUsage::CtypesLibrary),
+        _ => Err(format!("invalid source usage: {}", value)),
+    }
+}
+
+/// A problematic feature use found in a module's source.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SourceFinding {
+    pub usage: SourceUsage,
+    pub line: usize,
+}
+
+/// An entity to audit Python source code.
+pub struct SourceAuditor {
+    _temp_dir: tempdir::TempDir,
+    command: process::Child,
+}
+
+impl SourceAuditor {
+    pub fn new(python: &Path) -> SourceAuditor {
+        let temp_dir =
+            tempdir::TempDir::new("source-auditor").expect("could not create temp directory");
+
+        let script_path = PathBuf::from(temp_dir.path()).join("sourceaudit.py");
+
+        {
+            let mut fh = File::create(&script_path).expect("could not create temp path");
+            fh.write_all(SOURCE_AUDITOR)
+                .expect("could not write sourceaudit.py");
+        }
+
+        let command = process::Command::new(python)
+            .arg(script_path.clone())
+            .stdin(process::Stdio::piped())
+            .stdout(process::Stdio::piped())
+            .spawn()
+            .expect("Python source auditor process invoked");
+
+        SourceAuditor {
+            _temp_dir: temp_dir,
+            command,
+        }
+    }
+
+    /// Find problematic feature uses in Python source.
+    ///
+    /// Source that can't be parsed has no findings.
+    pub fn audit(
+        self: &mut SourceAuditor,
+        source: &[u8],
+        filename: &str,
+    ) -> Result<Vec<SourceFinding>, Error> {
+        let stdin = self.command.stdin.as_mut().expect("failed to get stdin");
+        let stdout = self.command.stdout.as_mut().expect("failed to get stdout");
+
+        let mut reader = BufReader::new(stdout);
+
+        stdin.write_all(b"audit\n")?;
+        stdin.write_all(filename.len().to_string().as_bytes())?;
+        stdin.write_all(b"\n")?;
+        stdin.write_all(source.len().to_string().as_bytes())?;
+        stdin.write_all(b"\n")?;
+        stdin.write_all(filename.as_bytes())?;
+        stdin.write_all(source)?;
+        stdin.flush()?;
+
+        let mut count = String::new();
+        reader.read_line(&mut count)?;
+        let count = count
+            .trim_end()
+            .parse::<usize>()
+            .or_else(|_| Err(Error::new(ErrorKind::InvalidData, "invalid finding count")))?;
+
+        let mut findings = Vec::new();
+
+        for _ in 0..count {
+            let mut line = String::new();
+            reader.read_line(&mut line)?;
+
+            findings.push(parse_finding(line.trim_end()).ok_or_else(|| {
+                Error::new(ErrorKind::InvalidData, format!("invalid finding: {}", line))
+            })?);
+        }
+
+        Ok(findings)
+    }
+}
+
+impl Drop for SourceAuditor {
+    fn drop(&mut self) {
+        let stdin = self.command.stdin.as_mut().expect("failed to get stdin");
+        stdin.write_all(b"exit\n").expect("write failed");
+        stdin.flush().expect("flush failed");
+
+        self.command.wait().expect("auditor process did not exit");
+    }
+}
+
+/// Parse a `<usage> <line>` finding emitted by the auditor script.
+fn parse_finding(value: &str) -> Option<SourceFinding> {
+    let mut parts = value.splitn(2, ' ');
+
+    let usage = resolve_source_usage(parts.next()?).ok()?;
+    let line = parts.next()?.parse::<usize>().ok()?;
+
+    Some(SourceFinding { usage, line })
+}
+
+/// Obtain the top-level package of a module or package name.
+pub fn top_level_package(name: &str) -> &str {
+    match name.find('.') {
+        Some(idx) => &name[0..idx],
+        None => name,
+    }
+}
+
+/// Resolve top-level packages containing modules with findings of given usages.
+///
+/// `findings` is keyed by module name. Top-level packages in `excludes` are
+/// never flagged.
+pub fn flagged_packages(
+    findings: &BTreeMap<String, Vec<SourceFinding>>,
+    usages: &[SourceUsage],
+    excludes: &[String],
+) -> BTreeSet<String> {
+    findings
+        .iter()
+        .filter(|(_, findings)| findings.iter().any(|f| usages.contains(&f.usage)))
+        .map(|(name, _)| top_level_package(name))
+        .filter(|package| !excludes.iter().any(|e| e == package))
+        .map(|package| package.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::distribution::{
+        resolve_parsed_distribution, ParsedPythonDistribution, PythonDistributionLocation,
+    };
+    use super::*;
+    use crate::app_packaging::repackage::HOST;
+    use crate::logging::PrintlnDrain;
+    use crate::python_distributions::CPYTHON_BY_TRIPLE;
+    use slog::Drain;
+
+    fn default_distribution() -> ParsedPythonDistribution {
+        let logger = slog::Logger::root(
+            PrintlnDrain {
+                min_level: slog::Level::Error,
+            }
+            .fuse(),
+            slog::o!(),
+        );
+
+        let hosted = CPYTHON_BY_TRIPLE.get(HOST).unwrap();
+        let location = PythonDistributionLocation::Url {
+            url: hosted.url.clone(),
+            sha256: hosted.sha256.clone(),
+        };
+
+        let dest_dir = std::env::current_dir()
+            .unwrap()
+            .join("build")
+            .join("python_distributions");
+
+        resolve_parsed_distribution(&logger, &location, &dest_dir).unwrap()
+    }
+
+    fn audit_usages(auditor: &mut SourceAuditor, source: &str) -> Vec<SourceUsage> {
+        auditor
+            .audit(source.as_bytes(), "test.py")
+            .unwrap()
+            .iter()
+            .map(|f| f.usage)
+            .collect()
+    }
+
+    #[test]
+    fn test_source_auditor() {
+        let dist = default_distribution();
+        let mut auditor = SourceAuditor::new(&dist.python_exe);
+
+        assert_eq!(
+            auditor
+                .audit(
+                    b"import os\n\npath = os.path.dirname(__file__)\n",
+                    "test.py"
+                )
+                .unwrap(),
+            vec![SourceFinding {
+                usage: SourceUsage::File,
+                line: 3,
+            }]
+        );
+        assert!(audit_usages(&mut auditor, "import os\npath = os.__file__\n").is_empty());
+        assert_eq!(
+            audit_usages(&mut auditor, "from pkg_resources import resource_string\n"),
+            vec![SourceUsage::PkgResources]
+        );
+        assert_eq!(
+            audit_usages(
+                &mut auditor,
+                "import ctypes\nlib = ctypes.CDLL('libfoo.so')\n"
+            ),
+            vec![SourceUsage::CtypesLibrary]
+        );
+        assert!(audit_usages(&mut auditor, "lib = ctypes.CDLL('libfoo.so')\n").is_empty());
+        assert!(audit_usages(&mut auditor, "this is not python\n").is_empty());
+    }
+
+    #[test]
+    fn test_parse_finding() {
+        assert_eq!(
+            parse_finding("__file__ 42"),
+            Some(SourceFinding {
+                usage: SourceUsage::File,
+                line: 42
+            })
+        );
+        assert_eq!(
+            parse_finding("ctypes 1"),
+            Some(SourceFinding {
+                usage: SourceUsage::CtypesLibrary,
+                line: 1
+            })
+        );
+        assert_eq!(parse_finding("pkg_resources"), None);
+        assert_eq!(parse_finding("foo 1"), None);
+    }
+
+    #[test]
+    fn test_flagged_packages() {
+        let mut findings = BTreeMap::new();
+        findings.insert(
+            "foo.bar".to_string(),
+            vec![SourceFinding {
+                usage: SourceUsage::File,
+                line: 3,
+            }],
+        );
+        findings.insert(
+            "baz".to_string(),
+            vec![SourceFinding {
+                usage: SourceUsage::PkgResources,
+                line: 1,
+            }],
+        );
+        findings.insert("clean".to_string(), vec![]);
+
+        let all = [
+            SourceUsage::File,
+            SourceUsage::PkgResources,
+            SourceUsage::CtypesLibrary,
+        ];
+
+        assert_eq!(
+            flagged_packages(&findings, &all, &[]),
+            ["baz", "foo"].iter().map(|x| x.to_string()).collect()
+        );
+        assert_eq!(
+            flagged_packages(&findings, &[SourceUsage::File], &[]),
+            ["foo"].iter().map(|x| x.to_string()).collect()
+        );
+        assert_eq!(
+            flagged_packages(&findings, &all, &["foo".to_string()]),
+            ["baz"].iter().map(|x| x.to_string()).collect()
+        );
+    }
+}
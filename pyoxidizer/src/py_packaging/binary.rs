@@ -69,13 +69,20 @@ impl PreBuiltPythonExecutable {
         let mut module_names = Vec::new();
         let mut modules = Vec::new();
         let mut resources = Vec::new();
+        let mut zip_archives = Vec::new();
 
-        embedded_resources.write_blobs(&mut module_names, &mut modules, &mut resources);
+        embedded_resources.write_blobs(
+            &mut module_names,
+            &mut modules,
+            &mut resources,
+            &mut zip_archives,
+        );
 
         Ok(EmbeddedResourcesBlobs {
             module_names,
             modules,
             resources,
+            zip_archives,
         })
     }
 }
@@ -92,4 +99,5 @@ pub struct EmbeddedResourcesBlobs {
     pub module_names: Vec<u8>,
     pub modules: Vec<u8>,
     pub resources: Vec<u8>,
+    pub zip_archives: Vec<u8>,
 }
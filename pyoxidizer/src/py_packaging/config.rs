@@ -25,6 +25,71 @@ pub enum TerminfoResolution {
     Static(String),
 }
 
+/// Source of a CA certificate bundle to embed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SslCaBundle {
+    /// Use `cacert.pem` from the packaged `certifi` package.
+    Certifi,
+    /// Use a PEM file on the filesystem.
+    File(String),
+}
+
+/// `pyembed::PythonConfig` fields that may be overridden by a config overlay file.
+pub const OVERLAY_FIELDS: &[&str] = &[
+    "standard_io_encoding",
+    "standard_io_errors",
+    "opt_level",
+    "filesystem_importer",
+    "sys_paths",
+    "bytes_warning",
+    "import_site",
+    "import_user_site",
+    "ignore_python_env",
+    "inspect",
+    "interactive",
+    "isolated",
+    "dont_write_bytecode",
+    "unbuffered_stdio",
+    "parser_debug",
+    "quiet",
+    "use_hash_seed",
+    "verbose",
+    "utf8_mode",
+    "coerce_c_locale",
+    "warnoptions",
+    "xoptions",
+    "dev_mode",
+    "hash_seed",
+    "sys_frozen",
+    "sys_meipass",
+    "install_signal_handlers",
+    "ignore_signals",
+    "forward_signals",
+    "write_modules_directory_env",
+    "faulthandler",
+    "faulthandler_path",
+    "logging_bridge",
+    "crash_report_directory",
+    "run",
+];
+
+/// Signal names `pyembed` can resolve on all platforms.
+pub const SIGNAL_NAMES: &[&str] = &[
+    "SIGABRT", "SIGFPE", "SIGILL", "SIGINT", "SIGSEGV", "SIGTERM",
+];
+
+/// Signal names `pyembed` can resolve on POSIX platforms only.
+pub const POSIX_SIGNAL_NAMES: &[&str] = &[
+    "SIGALRM", "SIGCHLD", "SIGHUP", "SIGPIPE", "SIGQUIT", "SIGTSTP", "SIGUSR1", "SIGUSR2",
+    "SIGWINCH", "SIGXFSZ",
+];
+
+/// Whether a signal name can be resolved by `pyembed` on a build target.
+pub fn is_known_signal(name: &str, target: &str) -> bool {
+    SIGNAL_NAMES.contains(&name)
+        || (!target.contains("-windows-") && POSIX_SIGNAL_NAMES.contains(&name))
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct EmbeddedPythonConfig {
     pub bytes_warning: i32,
@@ -45,11 +110,41 @@ pub struct EmbeddedPythonConfig {
     pub unbuffered_stdio: bool,
     pub use_hash_seed: bool,
     pub verbose: i32,
+    pub utf8_mode: bool,
+    pub coerce_c_locale: bool,
+    pub warnoptions: Vec<String>,
+    pub xoptions: Vec<String>,
+    pub dev_mode: bool,
+    pub hash_seed: Option<u32>,
     pub filesystem_importer: bool,
+    pub import_allow: Vec<String>,
+    pub import_deny: Vec<String>,
     pub sys_frozen: bool,
     pub sys_meipass: bool,
     pub sys_paths: Vec<String>,
     pub raw_allocator: RawAllocator,
+    pub mem_allocator: Option<RawAllocator>,
+    pub obj_allocator: Option<RawAllocator>,
+    pub allocator_stats: bool,
     pub terminfo_resolution: TerminfoResolution,
+    pub terminfo_embed: Vec<String>,
+    pub terminfo_embed_dir: Option<String>,
+    pub install_signal_handlers: bool,
+    pub ignore_signals: Vec<String>,
+    pub forward_signals: Vec<String>,
+    pub overlay_path: Option<String>,
+    pub overlay_env: Option<String>,
+    pub overlay_fields: Vec<String>,
     pub write_modules_directory_env: Option<String>,
+    pub self_test_env: Option<String>,
+    pub faulthandler: bool,
+    pub faulthandler_path: Option<String>,
+    pub logging_bridge: bool,
+    pub crash_report_directory: Option<String>,
+    pub app_version: Option<String>,
+    pub sys_prefix: Option<String>,
+    pub sys_exec_prefix: Option<String>,
+    pub sysconfig_data: bool,
+    pub ssl_ca_bundle: Option<SslCaBundle>,
+    pub packed_data_at_runtime: bool,
 }
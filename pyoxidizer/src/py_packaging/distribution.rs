@@ -39,6 +39,14 @@ const PIP_EXE_BASENAME: &str = "pip3";
 // This needs to be kept in sync with *compiler.py
 const PYOXIDIZER_STATE_DIR: &str = "state/pyoxidizer";
 
+/// `X.Y` Python versions we know how to package.
+pub const SUPPORTED_PYTHON_VERSIONS: &[&str] = &["3.7", "3.8"];
+
+/// Obtain the `X.Y` version from a full Python version string.
+pub fn python_major_minor_version(version: &str) -> String {
+    version.split('.').take(2).join(".")
+}
+
 const STDLIB_TEST_PACKAGES: &[&str] = &[
     "bsddb.test",
     "ctypes.test",
@@ -378,9 +386,10 @@ pub fn resolve_python_paths(base: &Path, python_version: &str) -> PythonPaths {
     let mut pyoxidizer_state_dir = p.clone();
     pyoxidizer_state_dir.extend(PYOXIDIZER_STATE_DIR.split('/'));
 
-    let unix_lib_dir = p
-        .join("lib")
-        .join(format!("python{}", &python_version[0..3]));
+    let unix_lib_dir = p.join("lib").join(format!(
+        "python{}",
+        python_major_minor_version(python_version)
+    ));
 
     let stdlib = if unix_lib_dir.exists() {
         unix_lib_dir.clone()
@@ -457,6 +466,22 @@ impl ParsedPythonDistribution {
         analyze_python_distribution_tar_zst(dist_cursor, &extract_dir)
     }
 
+    /// The `X.Y` version of Python in this distribution.
+    pub fn major_minor_version(&self) -> String {
+        python_major_minor_version(&self.version)
+    }
+
+    /// The tag of bytecode files for this distribution in `__pycache__` directories.
+    ///
+    /// e.g. ``cpython-37``.
+    pub fn cache_tag(&self) -> String {
+        format!(
+            "{}-{}",
+            self.flavor,
+            self.major_minor_version().replace(".", "")
+        )
+    }
+
     pub fn as_minimal_info(&self) -> PythonDistributionMinimalInfo {
         PythonDistributionMinimalInfo {
             flavor: self.flavor.clone(),
@@ -586,6 +611,39 @@ impl ParsedPythonDistribution {
             .collect()
     }
 
+    /// Resolve the names of the Tcl and Tk library directories.
+    ///
+    /// Names are relative to `tcl_library_path`. e.g. `tcl8.6` and `tk8.6`.
+    pub fn tcl_tk_library_names(&self) -> (Option<String>, Option<String>) {
+        let mut tcl = None;
+        let mut tk = None;
+
+        let entries = match &self.tcl_library_path {
+            Some(path) => match fs::read_dir(path) {
+                Ok(entries) => entries,
+                Err(_) => return (None, None),
+            },
+            None => return (None, None),
+        };
+
+        for entry in entries.filter_map(Result::ok) {
+            if !entry.path().is_dir() {
+                continue;
+            }
+
+            let name = entry.file_name().to_string_lossy().to_string();
+
+            // There is also a `tcl8` directory holding Tcl modules.
+            if name.starts_with("tcl8.") {
+                tcl = Some(name);
+            } else if name.starts_with("tk8.") {
+                tk = Some(name);
+            }
+        }
+
+        (tcl, tk)
+    }
+
     /// Obtain resolved `ResourceData` instances for this distribution.
     ///
     /// This effectively resolves the raw file content for resource files
@@ -735,6 +793,15 @@ pub fn analyze_python_distribution_data(
 
     let pi = parse_python_json_from_distribution(dist_dir);
 
+    let major_minor = python_major_minor_version(&pi.python_version);
+    if !SUPPORTED_PYTHON_VERSIONS.contains(&major_minor.as_str()) {
+        return Err(format!(
+            "Python {} is not supported; supported versions are {}",
+            pi.python_version,
+            SUPPORTED_PYTHON_VERSIONS.join(", ")
+        ));
+    }
+
     if let Some(ref python_license_path) = pi.license_path {
         let license_path = python_path.join(python_license_path);
         let license_text = fs::read_to_string(&license_path).or_else(|e| {
@@ -1174,3 +1241,85 @@ pub fn resolve_parsed_distribution(
 
     ParsedPythonDistribution::from_path(logger, &path, &distribution_path)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::write;
+
+    /// Write a minimal Python distribution for a Python version.
+    fn write_fixture_distribution(dist_dir: &Path, python_version: &str) {
+        let python_path = dist_dir.join("python");
+        let major_minor = python_major_minor_version(python_version);
+        let stdlib = format!("install/lib/python{}", major_minor);
+
+        create_dir_all(python_path.join("install").join("include")).unwrap();
+        create_dir_all(python_path.join(&stdlib).join("importlib")).unwrap();
+        write(
+            python_path.join("install").join("include").join("Python.h"),
+            "",
+        )
+        .unwrap();
+        write(
+            python_path
+                .join(&stdlib)
+                .join("importlib")
+                .join("__init__.py"),
+            "",
+        )
+        .unwrap();
+        write(python_path.join(&stdlib).join("foo.py"), "").unwrap();
+
+        let python_json = serde_json::json!({
+            "arch": "x86_64",
+            "os": "linux",
+            "python_exe": "install/bin/python3",
+            "python_flavor": "cpython",
+            "python_include": "install/include",
+            "python_stdlib": stdlib,
+            "python_version": python_version,
+            "version": "4",
+            "build_info": {
+                "core": {"objs": [], "links": []},
+                "extensions": {},
+            },
+        });
+
+        write(
+            python_path.join("PYTHON.json"),
+            serde_json::to_vec(&python_json).unwrap(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_python_major_minor_version() {
+        assert_eq!(python_major_minor_version("3.7.5"), "3.7");
+        assert_eq!(python_major_minor_version("3.8.0"), "3.8");
+        assert_eq!(python_major_minor_version("3.10.1"), "3.10");
+    }
+
+    #[test]
+    fn test_analyze_distribution_versions() {
+        let td = tempdir::TempDir::new("pyoxidizer-test").unwrap();
+
+        for (version, cache_tag) in &[("3.7.5", "cpython-37"), ("3.8.0", "cpython-38")] {
+            let dist_dir = td.path().join(version);
+            write_fixture_distribution(&dist_dir, version);
+
+            let dist = analyze_python_distribution_data(&dist_dir).unwrap();
+            assert_eq!(&dist.version, version);
+            assert_eq!(&dist.cache_tag(), cache_tag);
+            assert!(dist.includes.contains_key("Python.h"));
+            assert!(dist.py_modules.contains_key("foo"));
+            assert!(dist.py_modules.contains_key("importlib"));
+        }
+
+        let dist_dir = td.path().join("3.9.0");
+        write_fixture_distribution(&dist_dir, "3.9.0");
+        assert_eq!(
+            analyze_python_distribution_data(&dist_dir).unwrap_err(),
+            "Python 3.9.0 is not supported; supported versions are 3.7, 3.8"
+        );
+    }
+}
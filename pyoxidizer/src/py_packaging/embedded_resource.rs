@@ -12,7 +12,7 @@ use super::bytecode::{BytecodeCompiler, CompileMode};
 use super::distribution::ExtensionModule;
 use super::resource::{
     BuiltExtensionModule, BytecodeModule, PackagedModuleBytecode, PackagedModuleSource,
-    ResourceData, SourceModule,
+    ResourceData, SourceModule, ZipArchive,
 };
 
 /// Represents Python resources to embed in a binary.
@@ -25,6 +25,7 @@ pub struct EmbeddedPythonResourcesPrePackaged {
     pub bytecode_modules: BTreeMap<String, BytecodeModule>,
     pub resources: BTreeMap<String, BTreeMap<String, Vec<u8>>>,
     pub extension_modules: BTreeMap<String, ExtensionModule>,
+    pub zip_archives: BTreeMap<String, ZipArchive>,
 }
 
 impl EmbeddedPythonResourcesPrePackaged {
@@ -59,6 +60,12 @@ impl EmbeddedPythonResourcesPrePackaged {
             .insert(module.module.clone(), module.clone());
     }
 
+    /// Add a zip archive to import modules from.
+    pub fn add_zip_archive(&mut self, archive: &ZipArchive) {
+        self.zip_archives
+            .insert(archive.name.clone(), archive.clone());
+    }
+
     pub fn package(&self, python_exe: &Path) -> Result<EmbeddedPythonResources, String> {
         let mut all_modules = BTreeSet::new();
         let mut all_packages = BTreeSet::new();
@@ -112,6 +119,12 @@ impl EmbeddedPythonResourcesPrePackaged {
 
         let extension_modules = self.extension_modules.clone();
 
+        let zip_archives = BTreeMap::from_iter(
+            self.zip_archives
+                .iter()
+                .map(|(k, v)| (k.clone(), v.files.clone())),
+        );
+
         Ok(EmbeddedPythonResources {
             module_sources,
             module_bytecodes,
@@ -120,6 +133,7 @@ impl EmbeddedPythonResourcesPrePackaged {
             resources,
             extension_modules,
             built_extension_modules: Default::default(),
+            zip_archives,
         })
     }
 }
@@ -134,6 +148,8 @@ pub struct EmbeddedPythonResources {
     pub resources: BTreeMap<String, BTreeMap<String, Vec<u8>>>,
     pub extension_modules: BTreeMap<String, ExtensionModule>,
     pub built_extension_modules: BTreeMap<String, BuiltExtensionModule>,
+    /// Files in zip archives, keyed by archive name.
+    pub zip_archives: BTreeMap<String, BTreeMap<String, Vec<u8>>>,
 }
 
 /// Represents a single module's data record.
@@ -173,7 +189,13 @@ impl EmbeddedPythonResources {
         records
     }
 
-    pub fn write_blobs<W: Write>(&self, module_names: &mut W, modules: &mut W, resources: &mut W) {
+    pub fn write_blobs<W: Write>(
+        &self,
+        module_names: &mut W,
+        modules: &mut W,
+        resources: &mut W,
+        zip_archives: &mut W,
+    ) {
         for name in &self.all_modules {
             module_names
                 .write_all(name.as_bytes())
@@ -184,6 +206,10 @@ impl EmbeddedPythonResources {
         write_modules_entries(modules, &self.modules_records()).unwrap();
 
         write_resources_entries(resources, &self.resources).unwrap();
+
+        // Archives use the resources format, with archive names in place of
+        // package names.
+        write_resources_entries(zip_archives, &self.zip_archives).unwrap();
     }
 
     pub fn embedded_extension_module_names(&self) -> BTreeSet<String> {
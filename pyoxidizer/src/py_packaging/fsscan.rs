@@ -473,7 +473,7 @@ impl Iterator for PythonResourceIterator {
                     // We have arrived at a known package. Shift collected parts in names
                     // accordingly.
                     shift_parts.reverse();
-                    let prepend = itertools::join(shift_parts, ".");
+                    let prepend = itertools::join(shift_parts, "/");
 
                     // Use / instead of . because this emulates filesystem behavior.
                     let stem = prepend + "/" + &resource.stem;
@@ -624,6 +624,41 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_nested_resources() {
+        let td = tempdir::TempDir::new("pyoxidizer-test").unwrap();
+        let tp = td.path();
+
+        let acme_path = tp.join("acme");
+        let templates_path = acme_path.join("templates").join("sub");
+
+        create_dir_all(&templates_path).unwrap();
+
+        write(acme_path.join("__init__.py"), "").unwrap();
+        write(acme_path.join("data.txt"), "").unwrap();
+        write(templates_path.join("page.html"), "").unwrap();
+
+        let resources = PythonResourceIterator::new(tp).collect_vec();
+        assert_eq!(resources.len(), 3);
+
+        assert!(
+            resources.contains(&PythonFileResource::Resource(FileBasedResource {
+                package: "acme".to_string(),
+                stem: "data.txt".to_string(),
+                full_name: "acme.data.txt".to_string(),
+                path: acme_path.join("data.txt"),
+            }))
+        );
+        assert!(
+            resources.contains(&PythonFileResource::Resource(FileBasedResource {
+                package: "acme".to_string(),
+                stem: "templates/sub/page.html".to_string(),
+                full_name: "acme.templates.sub.page.html".to_string(),
+                path: templates_path.join("page.html"),
+            }))
+        );
+    }
+
     #[test]
     fn test_extension_module() {
         let td = tempdir::TempDir::new("pyoxidizer-test").unwrap();
@@ -11,7 +11,9 @@ use std::fs::create_dir_all;
 use std::path::{Path, PathBuf};
 
 use super::bytecode::{BytecodeCompiler, CompileMode};
-use super::distribution::{ExtensionModule, LicenseInfo, ParsedPythonDistribution};
+use super::distribution::{
+    python_major_minor_version, ExtensionModule, LicenseInfo, ParsedPythonDistribution,
+};
 use super::embedded_resource::EmbeddedPythonResources;
 use super::resource::BuiltExtensionModule;
 
@@ -36,6 +38,20 @@ lazy_static! {
     };
 }
 
+/// Resolve the source code appended to `importlib._bootstrap_external`.
+///
+/// The code overrides `_install()`, whose signature and behavior depend on
+/// the Python version.
+fn importer_source(python_version: &str) -> Result<&'static [u8], String> {
+    match python_major_minor_version(python_version).as_str() {
+        "3.7" | "3.8" => Ok(PYTHON_IMPORTER),
+        _ => Err(format!(
+            "no in-memory importer available for Python {}",
+            python_version
+        )),
+    }
+}
+
 pub struct ImportlibData {
     pub bootstrap_source: Vec<u8>,
     pub bootstrap_bytecode: Vec<u8>,
@@ -64,8 +80,20 @@ pub fn derive_importlib(dist: &ParsedPythonDistribution) -> ImportlibData {
 
     let mut bootstrap_external_source =
         fs::read(&mod_bootstrap_external_path).expect("unable to read bootstrap_external source");
+
+    // Our importer replaces _install(). Make sure we are replacing what we think we are.
+    if !String::from_utf8_lossy(&bootstrap_external_source)
+        .contains("\ndef _install(_bootstrap_module):\n")
+    {
+        panic!(
+            "importlib._bootstrap_external for Python {} does not define _install()",
+            dist.version
+        );
+    }
+
+    let importer = importer_source(&dist.version).unwrap();
     bootstrap_external_source.extend("\n# END OF importlib/_bootstrap_external.py\n\n".bytes());
-    bootstrap_external_source.extend(PYTHON_IMPORTER);
+    bootstrap_external_source.extend(importer);
     let module_name = "<frozen importlib._bootstrap_external>";
     let bootstrap_external_bytecode = compiler
         .compile(
@@ -402,6 +430,19 @@ pub fn link_libpython(
         out_dir.display()
     ));
 
+    // pyembed picks the interpreter initialization API from the version of
+    // the distribution. These are the same cfg names python3-sys defines.
+    let minor_version = dist
+        .version
+        .split('.')
+        .nth(1)
+        .and_then(|v| v.parse::<u32>().ok())
+        .expect("unable to parse Python version");
+
+    for minor in 4..=minor_version {
+        cargo_metadata.push(format!("cargo:rustc-cfg=Py_3_{}", minor));
+    }
+
     let mut license_infos = BTreeMap::new();
 
     if let Some(li) = dist.license_infos.get("python") {
@@ -2,6 +2,7 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+pub mod audit;
 pub mod binary;
 pub mod bytecode;
 pub mod config;
@@ -13,3 +14,4 @@ pub mod libpython;
 pub mod pip;
 pub mod pyembed;
 pub mod resource;
+pub mod terminfo;
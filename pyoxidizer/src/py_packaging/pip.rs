@@ -11,7 +11,7 @@ use std::path::PathBuf;
 use super::distribution::ParsedPythonDistribution;
 use super::distutils::{prepare_hacked_distutils, read_built_extensions};
 use super::fsscan::{find_python_resources, PythonFileResource};
-use super::resource::PythonResource;
+use super::resource::{PythonResource, ZipArchive};
 
 /// Run `pip install` and return found resources.
 pub fn pip_install(
@@ -94,6 +94,11 @@ pub fn pip_install(
                 res.push(PythonResource::try_from(&r)?);
             }
 
+            // Zipped eggs are exploded into the resources they contain.
+            PythonFileResource::EggFile { path } => {
+                res.extend(ZipArchive::from_path(&path)?.python_resources()?);
+            }
+
             _ => {}
         }
     }
@@ -3,12 +3,177 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use itertools::Itertools;
+use slog::warn;
+use std::fs;
 use std::fs::File;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use super::config::{EmbeddedPythonConfig, RawAllocator, RunMode, TerminfoResolution};
 
+use crate::environment::canonicalize_path;
+
+fn raw_allocator_rs(allocator: &RawAllocator) -> &'static str {
+    match allocator {
+        RawAllocator::Jemalloc => "PythonRawAllocator::Jemalloc",
+        RawAllocator::Rust => "PythonRawAllocator::Rust",
+        RawAllocator::System => "PythonRawAllocator::System",
+    }
+}
+
+/// Whether packed data can be linked into binaries for a target.
+///
+/// MSVC's assembler doesn't support `.incbin`. We fall back to
+/// `include_bytes!()` there.
+pub fn supports_linked_data(target: &str) -> bool {
+    !target.contains("-msvc")
+}
+
+/// Obtain the prefix the target's C ABI adds to symbol names.
+fn symbol_prefix(target: &str) -> &'static str {
+    if target.contains("-apple-") || target.starts_with("i686-pc-windows") {
+        "_"
+    } else {
+        ""
+    }
+}
+
+/// Obtain the name of a symbol delimiting linked packed data.
+fn linked_data_symbol(path: &Path, position: &str) -> String {
+    let name = path
+        .file_name()
+        .expect("packed data path has no file name")
+        .to_string_lossy()
+        .replace(|c: char| !c.is_ascii_alphanumeric(), "_");
+
+    format!("pyembed_{}_{}", name, position)
+}
+
+/// Obtain assembly source defining symbols around the content of files.
+///
+/// Each file's content is pulled in via `.incbin` and is delimited by
+/// `pyembed_<name>_start` and `pyembed_<name>_end` symbols.
+pub fn linked_data_assembly(paths: &[&PathBuf], target: &str) -> String {
+    let prefix = symbol_prefix(target);
+
+    let section = if target.contains("-apple-") {
+        ".section __TEXT,__const"
+    } else if target.contains("-windows-") {
+        ".section .rdata,\"dr\""
+    } else {
+        ".section .rodata"
+    };
+
+    let mut lines = vec![section.to_string()];
+
+    for path in paths {
+        let start = format!("{}{}", prefix, linked_data_symbol(path, "start"));
+        let end = format!("{}{}", prefix, linked_data_symbol(path, "end"));
+        let path = path
+            .display()
+            .to_string()
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"");
+
+        lines.push(format!(".global {}", start));
+        lines.push(format!(".global {}", end));
+        lines.push(".balign 16".to_string());
+        lines.push(format!("{}:", start));
+        lines.push(format!(".incbin \"{}\"", path));
+        lines.push(format!("{}:", end));
+    }
+
+    // Prevent the linker from thinking we need an executable stack.
+    if !target.contains("-apple-") && !target.contains("-windows-") {
+        lines.push(".section .note.GNU-stack,\"\",%progbits".to_string());
+    }
+
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+/// Produce a static library exposing the content of packed data files as symbols.
+///
+/// Linking data this way avoids having rustc process the data via
+/// `include_bytes!()`, which is slow for large inputs. Returns cargo metadata
+/// lines needed to link the library.
+pub fn link_packed_data(
+    logger: &slog::Logger,
+    paths: &[&PathBuf],
+    out_dir: &Path,
+    host: &str,
+    target: &str,
+    opt_level: &str,
+) -> Vec<String> {
+    // .incbin resolves relative paths against the assembler's working directory.
+    let absolute_paths = paths
+        .iter()
+        .map(|p| canonicalize_path(p).expect("unable to canonicalize packed data path"))
+        .collect::<Vec<_>>();
+    let absolute_paths = absolute_paths.iter().collect::<Vec<_>>();
+
+    let asm_path = out_dir.join("pyembeddata.s");
+    fs::write(&asm_path, linked_data_assembly(&absolute_paths, target))
+        .expect("unable to write packed data assembly");
+
+    warn!(logger, "assembling packed data into static library");
+    cc::Build::new()
+        .out_dir(out_dir)
+        .host(host)
+        .target(target)
+        .opt_level_str(opt_level)
+        .file(&asm_path)
+        .cargo_metadata(false)
+        .compile("pyembeddata");
+
+    let mut cargo_metadata = vec![
+        "cargo:rustc-link-lib=static=pyembeddata".to_string(),
+        format!("cargo:rustc-link-search=native={}", out_dir.display()),
+    ];
+
+    for path in paths {
+        cargo_metadata.push(format!("cargo:rerun-if-changed={}", path.display()));
+    }
+
+    cargo_metadata
+}
+
+/// Obtain the Rust expression referencing packed data.
+///
+/// Data loaded from a directory at run-time isn't embedded. Linked data is
+/// referenced via the symbols defined by `link_packed_data()`.
+fn packed_data_rs(
+    path: &PathBuf,
+    packed_data_directory: &Option<PathBuf>,
+    linked_data: bool,
+) -> String {
+    if packed_data_directory.is_some() {
+        "&[]".to_string()
+    } else if linked_data {
+        let start = linked_data_symbol(path, "start");
+        let end = linked_data_symbol(path, "end");
+
+        format!(
+            "{{\n    \
+             extern \"C\" {{\n        \
+             static {start}: u8;\n        \
+             static {end}: u8;\n    \
+             }}\n\n    \
+             unsafe {{\n        \
+             let start = &{start} as *const u8;\n        \
+             let end = &{end} as *const u8;\n        \
+             std::slice::from_raw_parts(start, end as usize - start as usize)\n    \
+             }}\n\
+             }}",
+            start = start,
+            end = end
+        )
+        .replace('\n', "\n    ")
+    } else {
+        format!("include_bytes!(r#\"{}\"#)", path.display())
+    }
+}
+
 /// Obtain the Rust source code to construct a PythonConfig instance.
 pub fn derive_python_config(
     embedded: &EmbeddedPythonConfig,
@@ -17,6 +182,13 @@ pub fn derive_python_config(
     importlib_bootstrap_external_path: &PathBuf,
     py_modules_path: &PathBuf,
     py_resources_path: &PathBuf,
+    zip_archives_path: &PathBuf,
+    packed_data_directory: &Option<PathBuf>,
+    linked_data: bool,
+    terminfo_path: &PathBuf,
+    tcl_library: &Option<String>,
+    tk_library: &Option<String>,
+    ssl_ca_bundle_path: &PathBuf,
 ) -> String {
     format!(
         "PythonConfig {{\n    \
@@ -25,6 +197,8 @@ pub fn derive_python_config(
          opt_level: {},\n    \
          use_custom_importlib: true,\n    \
          filesystem_importer: {},\n    \
+         import_allow: [{}].to_vec(),\n    \
+         import_deny: [{}].to_vec(),\n    \
          sys_paths: [{}].to_vec(),\n    \
          bytes_warning: {},\n    \
          import_site: {},\n    \
@@ -41,17 +215,46 @@ pub fn derive_python_config(
          quiet: {},\n    \
          use_hash_seed: {},\n    \
          verbose: {},\n    \
+         utf8_mode: {},\n    \
+         coerce_c_locale: {},\n    \
+         warnoptions: [{}].to_vec(),\n    \
+         xoptions: [{}].to_vec(),\n    \
+         dev_mode: {},\n    \
+         hash_seed: {},\n    \
          frozen_importlib_data: include_bytes!(r#\"{}\"#),\n    \
          frozen_importlib_external_data: include_bytes!(r#\"{}\"#),\n    \
-         py_modules_data: include_bytes!(r#\"{}\"#),\n    \
-         py_resources_data: include_bytes!(r#\"{}\"#),\n    \
+         py_modules_data: {},\n    \
+         py_resources_data: {},\n    \
+         zip_archives_data: {},\n    \
+         packed_data_directory: {},\n    \
          extra_extension_modules: vec![],\n    \
          argvb: false,\n    \
          sys_frozen: {},\n    \
          sys_meipass: {},\n    \
          raw_allocator: {},\n    \
+         mem_allocator: {},\n    \
+         obj_allocator: {},\n    \
+         allocator_stats: {},\n    \
          terminfo_resolution: {},\n    \
+         terminfo_data: include_bytes!(r#\"{}\"#),\n    \
+         install_signal_handlers: {},\n    \
+         ignore_signals: [{}].to_vec(),\n    \
+         forward_signals: [{}].to_vec(),\n    \
+         config_overlay_path: {},\n    \
+         config_overlay_env: {},\n    \
+         config_overlay_fields: [{}].to_vec(),\n    \
          write_modules_directory_env: {},\n    \
+         self_test_env: {},\n    \
+         faulthandler: {},\n    \
+         faulthandler_path: {},\n    \
+         logging_bridge: {},\n    \
+         crash_report_directory: {},\n    \
+         app_version: {},\n    \
+         sys_prefix: {},\n    \
+         sys_exec_prefix: {},\n    \
+         tcl_library: {},\n    \
+         tk_library: {},\n    \
+         ssl_ca_bundle_data: include_bytes!(r#\"{}\"#),\n    \
          run: {},\n\
          }}",
         match &embedded.stdio_encoding_name {
@@ -64,6 +267,18 @@ pub fn derive_python_config(
         },
         embedded.optimize_level,
         embedded.filesystem_importer,
+        &embedded
+            .import_allow
+            .iter()
+            .map(|p| "r###\"".to_owned() + p + "\"###.to_string()")
+            .collect::<Vec<String>>()
+            .join(", "),
+        &embedded
+            .import_deny
+            .iter()
+            .map(|p| "r###\"".to_owned() + p + "\"###.to_string()")
+            .collect::<Vec<String>>()
+            .join(", "),
         &embedded
             .sys_paths
             .iter()
@@ -85,28 +300,120 @@ pub fn derive_python_config(
         embedded.quiet,
         embedded.use_hash_seed,
         embedded.verbose,
+        embedded.utf8_mode,
+        embedded.coerce_c_locale,
+        &embedded
+            .warnoptions
+            .iter()
+            .map(|o| "r###\"".to_owned() + o + "\"###.to_string()")
+            .collect::<Vec<String>>()
+            .join(", "),
+        &embedded
+            .xoptions
+            .iter()
+            .map(|o| "r###\"".to_owned() + o + "\"###.to_string()")
+            .collect::<Vec<String>>()
+            .join(", "),
+        embedded.dev_mode,
+        match embedded.hash_seed {
+            Some(seed) => format!("Some({})", seed),
+            None => "None".to_string(),
+        },
         importlib_bootstrap_path.display(),
         importlib_bootstrap_external_path.display(),
-        py_modules_path.display(),
-        py_resources_path.display(),
+        packed_data_rs(py_modules_path, packed_data_directory, linked_data),
+        packed_data_rs(py_resources_path, packed_data_directory, linked_data),
+        packed_data_rs(zip_archives_path, packed_data_directory, linked_data),
+        match packed_data_directory {
+            Some(path) => format!("Some(r###\"{}\"###.to_string())", path.display()),
+            None => "None".to_string(),
+        },
         embedded.sys_frozen,
         embedded.sys_meipass,
-        match embedded.raw_allocator {
-            RawAllocator::Jemalloc => "PythonRawAllocator::Jemalloc",
-            RawAllocator::Rust => "PythonRawAllocator::Rust",
-            RawAllocator::System => "PythonRawAllocator::System",
+        raw_allocator_rs(&embedded.raw_allocator),
+        match &embedded.mem_allocator {
+            Some(allocator) => format!("Some({})", raw_allocator_rs(allocator)),
+            None => "None".to_string(),
+        },
+        match &embedded.obj_allocator {
+            Some(allocator) => format!("Some({})", raw_allocator_rs(allocator)),
+            None => "None".to_string(),
         },
+        embedded.allocator_stats,
         match embedded.terminfo_resolution {
             TerminfoResolution::Dynamic => "TerminfoResolution::Dynamic".to_string(),
             TerminfoResolution::None => "TerminfoResolution::None".to_string(),
             TerminfoResolution::Static(ref v) => {
-                format!("TerminfoResolution::Static(r###\"{}\"###", v)
+                format!("TerminfoResolution::Static(r###\"{}\"###.to_string())", v)
             }
         },
+        terminfo_path.display(),
+        embedded.install_signal_handlers,
+        &embedded
+            .ignore_signals
+            .iter()
+            .map(|s| "r###\"".to_owned() + s + "\"###.to_string()")
+            .collect::<Vec<String>>()
+            .join(", "),
+        &embedded
+            .forward_signals
+            .iter()
+            .map(|s| "r###\"".to_owned() + s + "\"###.to_string()")
+            .collect::<Vec<String>>()
+            .join(", "),
+        match &embedded.overlay_path {
+            Some(path) => "Some(r###\"".to_owned() + &path + "\"###.to_string())",
+            _ => "None".to_owned(),
+        },
+        match &embedded.overlay_env {
+            Some(key) => "Some(\"".to_owned() + &key + "\".to_string())",
+            _ => "None".to_owned(),
+        },
+        &embedded
+            .overlay_fields
+            .iter()
+            .map(|f| "\"".to_owned() + f + "\".to_string()")
+            .collect::<Vec<String>>()
+            .join(", "),
         match &embedded.write_modules_directory_env {
             Some(path) => "Some(\"".to_owned() + &path + "\".to_string())",
             _ => "None".to_owned(),
         },
+        match &embedded.self_test_env {
+            Some(key) => "Some(\"".to_owned() + &key + "\".to_string())",
+            _ => "None".to_owned(),
+        },
+        embedded.faulthandler,
+        match &embedded.faulthandler_path {
+            Some(path) => "Some(r###\"".to_owned() + &path + "\"###.to_string())",
+            _ => "None".to_owned(),
+        },
+        embedded.logging_bridge,
+        match &embedded.crash_report_directory {
+            Some(path) => "Some(r###\"".to_owned() + &path + "\"###.to_string())",
+            _ => "None".to_owned(),
+        },
+        match &embedded.app_version {
+            Some(version) => "Some(r###\"".to_owned() + &version + "\"###.to_string())",
+            _ => "None".to_owned(),
+        },
+        match &embedded.sys_prefix {
+            Some(path) => "Some(r###\"".to_owned() + &path + "\"###.to_string())",
+            _ => "None".to_owned(),
+        },
+        match &embedded.sys_exec_prefix {
+            Some(path) => "Some(r###\"".to_owned() + &path + "\"###.to_string())",
+            _ => "None".to_owned(),
+        },
+        match tcl_library {
+            Some(path) => "Some(r###\"".to_owned() + &path + "\"###.to_string())",
+            _ => "None".to_owned(),
+        },
+        match tk_library {
+            Some(path) => "Some(r###\"".to_owned() + &path + "\"###.to_string())",
+            _ => "None".to_owned(),
+        },
+        ssl_ca_bundle_path.display(),
         match run_mode {
             RunMode::Noop => "PythonRunMode::None".to_owned(),
             RunMode::Repl => "PythonRunMode::Repl".to_owned(),
@@ -145,3 +452,84 @@ pub fn write_data_rs(path: &PathBuf, python_config_rs: &str) {
     ))
     .unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_linked_data_assembly() {
+        let modules = PathBuf::from("/build/py-modules");
+        let resources = PathBuf::from("/build/python-resources");
+
+        let linux = linked_data_assembly(&[&modules, &resources], "x86_64-unknown-linux-gnu");
+        assert!(linux.starts_with(".section .rodata\n"));
+        assert!(linux.contains(".global pyembed_py_modules_start\n"));
+        assert!(linux.contains(".global pyembed_python_resources_end\n"));
+        assert!(linux.contains(
+            "pyembed_py_modules_start:\n.incbin \"/build/py-modules\"\npyembed_py_modules_end:\n"
+        ));
+        assert!(linux.contains(".note.GNU-stack"));
+
+        let macos = linked_data_assembly(&[&modules], "x86_64-apple-darwin");
+        assert!(macos.starts_with(".section __TEXT,__const\n"));
+        assert!(macos.contains("_pyembed_py_modules_start:\n"));
+        assert!(!macos.contains(".note.GNU-stack"));
+
+        let windows = PathBuf::from("C:/build/py-modules");
+        let mingw = linked_data_assembly(&[&windows], "x86_64-pc-windows-gnu");
+        assert!(mingw.starts_with(".section .rdata,\"dr\"\n"));
+        assert!(mingw.contains(".incbin \"C:/build/py-modules\"\n"));
+        assert!(mingw.contains("\npyembed_py_modules_start:\n"));
+    }
+
+    #[test]
+    fn test_supports_linked_data() {
+        assert!(supports_linked_data("x86_64-unknown-linux-gnu"));
+        assert!(supports_linked_data("x86_64-apple-darwin"));
+        assert!(supports_linked_data("x86_64-pc-windows-gnu"));
+        assert!(!supports_linked_data("x86_64-pc-windows-msvc"));
+    }
+
+    #[test]
+    fn test_packed_data_rs() {
+        let path = PathBuf::from("/build/py-modules");
+
+        assert_eq!(
+            packed_data_rs(&path, &None, false),
+            "include_bytes!(r#\"/build/py-modules\"#)"
+        );
+        assert_eq!(
+            packed_data_rs(&path, &Some(PathBuf::from("/build")), true),
+            "&[]"
+        );
+
+        let linked = packed_data_rs(&path, &None, true);
+        assert!(linked.contains("static pyembed_py_modules_start: u8;"));
+        assert!(linked.contains("static pyembed_py_modules_end: u8;"));
+        assert!(linked.contains("std::slice::from_raw_parts(start, end as usize - start as usize)"));
+    }
+
+    #[test]
+    fn test_link_packed_data() {
+        let host = crate::app_packaging::repackage::HOST;
+        if !supports_linked_data(host) {
+            return;
+        }
+
+        let logger = crate::logging::logger_from_env(slog::Level::Warning).logger;
+        let td = tempdir::TempDir::new("pyoxidizer-test").unwrap();
+        let tp = td.path();
+
+        let modules = tp.join("py-modules");
+        fs::write(&modules, b"modules data").unwrap();
+
+        let cargo_metadata = link_packed_data(&logger, &[&modules], tp, host, host, "0");
+        assert_eq!(cargo_metadata[0], "cargo:rustc-link-lib=static=pyembeddata");
+
+        let library = fs::read(tp.join("libpyembeddata.a")).unwrap();
+        let contains = |needle: &[u8]| library.windows(needle.len()).any(|w| w == needle);
+        assert!(contains(b"pyembed_py_modules_start"));
+        assert!(contains(b"modules data"));
+    }
+}
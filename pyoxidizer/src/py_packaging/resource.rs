@@ -5,10 +5,28 @@
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet};
 use std::convert::TryFrom;
-use std::path::PathBuf;
+use std::ffi::OsStr;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
 
 use super::distribution::ExtensionModule;
-use super::fsscan::{is_package_from_path, PythonFileResource};
+use super::fsscan::{find_python_resources, is_package_from_path, PythonFileResource};
+
+/// Length of the header of `.pyc` files.
+///
+/// Python 3.7 and 3.8 both use a 16 byte header: a magic number, a bit field
+/// and 8 bytes of validation data (PEP 552).
+const BYTECODE_HEADER_LEN: usize = 16;
+
+/// Obtain the raw bytecode from the content of a `.pyc` file.
+pub fn strip_bytecode_header(path: &PathBuf, data: &[u8]) -> Result<Vec<u8>, String> {
+    // The magic number varies by Python version. But it always ends in \r\n.
+    if data.len() < BYTECODE_HEADER_LEN || &data[2..4] != b"\r\n" {
+        return Err(format!("{} is not a valid bytecode file", path.display()));
+    }
+
+    Ok(data[BYTECODE_HEADER_LEN..].to_vec())
+}
 
 pub fn packages_from_module_name(module: &str) -> BTreeSet<String> {
     let mut package_names = BTreeSet::new();
@@ -112,6 +130,117 @@ impl ResourceData {
     }
 }
 
+/// A zip archive containing Python resources, such as an egg.
+///
+/// Files are held uncompressed, keyed by their path in the archive.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ZipArchive {
+    /// File name of the archive.
+    ///
+    /// Embedded archives are importable from a `sys.path` entry of this name
+    /// in the directory of the executable.
+    pub name: String,
+    pub files: BTreeMap<String, Vec<u8>>,
+}
+
+impl ZipArchive {
+    /// Read a zip archive from a file.
+    pub fn from_path(path: &Path) -> Result<ZipArchive, String> {
+        let name = path
+            .file_name()
+            .and_then(OsStr::to_str)
+            .ok_or_else(|| format!("unable to resolve archive name of {}", path.display()))?;
+
+        let data =
+            std::fs::read(path).or_else(|_| Err(format!("unable to read {}", path.display())))?;
+
+        ZipArchive::from_data(name, &data).or_else(|e| {
+            Err(format!(
+                "unable to read zip archive {}: {}",
+                path.display(),
+                e
+            ))
+        })
+    }
+
+    /// Read a zip archive from its raw data.
+    pub fn from_data(name: &str, data: &[u8]) -> Result<ZipArchive, String> {
+        let cursor = std::io::Cursor::new(data);
+        let mut za = zip::ZipArchive::new(cursor).or_else(|e| Err(e.to_string()))?;
+
+        let mut files = BTreeMap::new();
+
+        for i in 0..za.len() {
+            let mut file = za.by_index(i).or_else(|e| Err(e.to_string()))?;
+
+            if file.is_dir() {
+                continue;
+            }
+
+            // Paths escaping the archive can't be represented as files in it.
+            let is_relative = Path::new(file.name()).components().all(|c| match c {
+                Component::Normal(_) => true,
+                _ => false,
+            });
+            if !is_relative {
+                return Err(format!("invalid path in archive: {}", file.name()));
+            }
+
+            let mut content = Vec::new();
+            file.read_to_end(&mut content)
+                .or_else(|e| Err(e.to_string()))?;
+
+            files.insert(file.name().to_string(), content);
+        }
+
+        Ok(ZipArchive {
+            name: name.to_string(),
+            files,
+        })
+    }
+
+    pub fn as_python_resource(&self) -> PythonResource {
+        PythonResource::ZipArchive(self.clone())
+    }
+
+    /// Obtain the Python module sources and resource data in this archive.
+    ///
+    /// This explodes the archive into resources as if it were extracted to a
+    /// package root. Egg metadata in `EGG-INFO` is ignored.
+    pub fn python_resources(&self) -> Result<Vec<PythonResource>, String> {
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-zip-archive")
+            .or_else(|e| Err(format!("error creating temporary directory: {}", e)))?;
+
+        // Extracting to a directory named like an egg makes the filesystem
+        // scanner treat its content like that of an unpacked egg.
+        let root = temp_dir.path().join("archive.egg");
+
+        for (name, data) in &self.files {
+            let path = root.join(name);
+
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent).or_else(|e| Err(e.to_string()))?;
+            }
+
+            std::fs::write(&path, data).or_else(|e| Err(e.to_string()))?;
+        }
+
+        let mut res = Vec::new();
+
+        for r in find_python_resources(temp_dir.path()) {
+            match r {
+                PythonFileResource::Source { .. } | PythonFileResource::Resource(..) => {
+                    res.push(PythonResource::try_from(&r)?);
+                }
+
+                _ => {}
+            }
+        }
+
+        Ok(res)
+    }
+}
+
 /// Represents an extension module built during packaging.
 ///
 /// This is like a light version of `ExtensionModule`.
@@ -155,6 +284,7 @@ pub enum PythonResource {
         data: Vec<u8>,
     },
     BuiltExtensionModule(BuiltExtensionModule),
+    ZipArchive(ZipArchive),
 }
 
 impl TryFrom<&PythonFileResource> for PythonResource {
@@ -181,8 +311,7 @@ impl TryFrom<&PythonFileResource> for PythonResource {
                 let bytecode = std::fs::read(&path)
                     .or_else(|_| Err(format!("unable to read {}", path.display())))?;
 
-                // First 16 bytes are a validation header.
-                let bytecode = bytecode[16..bytecode.len()].to_vec();
+                let bytecode = strip_bytecode_header(&path, &bytecode)?;
 
                 Ok(PythonResource::ModuleBytecode {
                     name: full_name.clone(),
@@ -198,8 +327,7 @@ impl TryFrom<&PythonFileResource> for PythonResource {
                 let bytecode = std::fs::read(&path)
                     .or_else(|_| Err(format!("unable to read {}", path.display())))?;
 
-                // First 16 bytes are a validation header.
-                let bytecode = bytecode[16..bytecode.len()].to_vec();
+                let bytecode = strip_bytecode_header(&path, &bytecode)?;
 
                 Ok(PythonResource::ModuleBytecode {
                     name: full_name.clone(),
@@ -215,8 +343,7 @@ impl TryFrom<&PythonFileResource> for PythonResource {
                 let bytecode = std::fs::read(&path)
                     .or_else(|_| Err(format!("unable to read {}", path.display())))?;
 
-                // First 16 bytes are a validation header.
-                let bytecode = bytecode[16..bytecode.len()].to_vec();
+                let bytecode = strip_bytecode_header(&path, &bytecode)?;
 
                 Ok(PythonResource::ModuleBytecode {
                     name: full_name.clone(),
@@ -242,8 +369,8 @@ impl TryFrom<&PythonFileResource> for PythonResource {
                 Err("converting ExtensionModule not yet supported".to_string())
             }
 
-            PythonFileResource::EggFile { .. } => {
-                Err("converting egg files not yet supported".to_string())
+            PythonFileResource::EggFile { path } => {
+                Ok(PythonResource::ZipArchive(ZipArchive::from_path(path)?))
             }
 
             PythonFileResource::PthFile { .. } => {
@@ -266,6 +393,8 @@ impl PythonResource {
             PythonResource::Resource { package, .. } => package,
             PythonResource::BuiltExtensionModule(em) => &em.name,
             PythonResource::ExtensionModule { name, .. } => name,
+            // Archives are importable from sys.path, not from a package.
+            PythonResource::ZipArchive(..) => return false,
         };
 
         for package in packages {
@@ -297,3 +426,112 @@ pub struct AppRelativeResources {
     pub module_bytecodes: BTreeMap<String, PackagedModuleBytecode>,
     pub resources: BTreeMap<String, BTreeMap<String, Vec<u8>>>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_bytecode_header() {
+        let path = PathBuf::from("foo.pyc");
+
+        // Python 3.7 and 3.8 magic numbers.
+        for magic in &[b"\x42\x0d\r\n", b"\x55\x0d\r\n"] {
+            let mut data = magic.to_vec();
+            data.extend_from_slice(&[0; 12]);
+            data.extend_from_slice(b"bytecode");
+
+            assert_eq!(strip_bytecode_header(&path, &data).unwrap(), b"bytecode");
+        }
+
+        assert_eq!(
+            strip_bytecode_header(&path, b"\x55\x0d\r\n").unwrap_err(),
+            "foo.pyc is not a valid bytecode file"
+        );
+        assert_eq!(
+            strip_bytecode_header(&path, &[0; 20]).unwrap_err(),
+            "foo.pyc is not a valid bytecode file"
+        );
+    }
+
+    fn zip_data(files: &[(&str, &[u8])]) -> Vec<u8> {
+        use std::io::Write;
+
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+
+        for (name, data) in files {
+            writer
+                .start_file(*name, zip::write::FileOptions::default())
+                .unwrap();
+            writer.write_all(data).unwrap();
+        }
+
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_zip_archive() {
+        let data = zip_data(&[
+            ("EGG-INFO/PKG-INFO", b"Name: foo\n"),
+            ("foo/__init__.py", b"import bar\n"),
+            ("foo/bar.py", b"X = 1\n"),
+            ("foo/templates/index.html", b"<html>"),
+        ]);
+
+        let archive = ZipArchive::from_data("foo-1.0-py3.7.egg", &data).unwrap();
+        assert_eq!(archive.name, "foo-1.0-py3.7.egg");
+        assert_eq!(
+            archive.files.keys().collect::<Vec<_>>(),
+            vec![
+                "EGG-INFO/PKG-INFO",
+                "foo/__init__.py",
+                "foo/bar.py",
+                "foo/templates/index.html"
+            ]
+        );
+        assert_eq!(archive.files["foo/bar.py"], b"X = 1\n");
+
+        let resources = archive.python_resources().unwrap();
+        assert_eq!(resources.len(), 3);
+
+        match &resources[0] {
+            PythonResource::ModuleSource {
+                name, is_package, ..
+            } => {
+                assert_eq!(name, "foo");
+                assert!(is_package);
+            }
+            r => panic!("unexpected resource: {:?}", r),
+        }
+        match &resources[1] {
+            PythonResource::ModuleSource {
+                name,
+                source,
+                is_package,
+            } => {
+                assert_eq!(name, "foo.bar");
+                assert_eq!(source, b"X = 1\n");
+                assert!(!is_package);
+            }
+            r => panic!("unexpected resource: {:?}", r),
+        }
+        match &resources[2] {
+            PythonResource::Resource { package, name, .. } => {
+                assert_eq!(package, "foo");
+                assert_eq!(name, "templates/index.html");
+            }
+            r => panic!("unexpected resource: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_zip_archive_invalid() {
+        assert!(ZipArchive::from_data("foo.zip", b"not a zip file").is_err());
+
+        let data = zip_data(&[("../escape.py", b"")]);
+        assert_eq!(
+            ZipArchive::from_data("foo.zip", &data).unwrap_err(),
+            "invalid path in archive: ../escape.py"
+        );
+    }
+}
@@ -0,0 +1,98 @@
+# This Source Code Form is subject to the terms of the Mozilla Public
+# License, v. 2.0. If a copy of the MPL was not distributed with this
+# file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+# This script is executed to audit Python source code by the repackager.
+#
+# When invoked, we start a server that listens for commands. We then
+# react to those commands and send results to the caller.
+#
+# Auditing looks for uses of Python features that are known to not work
+# when modules are imported from memory. Findings are emitted as
+# ``<usage> <line>`` lines.
+
+import ast
+import os
+import sys
+
+
+# Names in ctypes that load shared libraries from the filesystem.
+CTYPES_LOADERS = {
+    'CDLL',
+    'LoadLibrary',
+    'OleDLL',
+    'PyDLL',
+    'WinDLL',
+    'find_library',
+}
+
+
+def audit(source, filename):
+    try:
+        tree = ast.parse(source, filename)
+    except (SyntaxError, ValueError):
+        return []
+
+    findings = []
+    ctypes_calls = []
+    imports_ctypes = False
+
+    for node in ast.walk(tree):
+        # Only bare references locate the module itself. Attribute access
+        # (e.g. ``module.__file__``) inspects other modules.
+        if isinstance(node, ast.Name) and node.id == '__file__':
+            findings.append(('__file__', node.lineno))
+        elif isinstance(node, ast.Import):
+            for alias in node.names:
+                package = alias.name.split('.')[0]
+                if package == 'pkg_resources':
+                    findings.append(('pkg_resources', node.lineno))
+                elif package == 'ctypes':
+                    imports_ctypes = True
+        elif isinstance(node, ast.ImportFrom) and not node.level and node.module:
+            package = node.module.split('.')[0]
+            if package == 'pkg_resources':
+                findings.append(('pkg_resources', node.lineno))
+            elif package == 'ctypes':
+                imports_ctypes = True
+        elif isinstance(node, ast.Call):
+            func = node.func
+            if isinstance(func, ast.Attribute) and func.attr in CTYPES_LOADERS:
+                ctypes_calls.append(node.lineno)
+            elif isinstance(func, ast.Name) and func.id in CTYPES_LOADERS:
+                ctypes_calls.append(node.lineno)
+
+    # Loader names are generic enough that we only trust them in modules
+    # that actually import ctypes.
+    if imports_ctypes:
+        findings.extend(('ctypes', line) for line in ctypes_calls)
+
+    return sorted(set(findings), key=lambda x: (x[1], x[0]))
+
+
+stdin = sys.__stdin__.buffer
+stdout = sys.__stdout__.buffer
+
+
+while True:
+    command = stdin.readline().rstrip()
+
+    if command == b'exit':
+        sys.exit(0)
+    elif command == b'audit':
+        name_len = int(stdin.readline().rstrip())
+        source_len = int(stdin.readline().rstrip())
+
+        name = os.fsdecode(stdin.read(name_len))
+        source = stdin.read(source_len)
+
+        # ast.parse() handles PEP 263 encoding declarations and BOMs
+        # when given bytes.
+        findings = audit(source, name)
+
+        stdout.write(b'%d\n' % len(findings))
+        for usage, line in findings:
+            stdout.write(b'%s %d\n' % (usage.encode('ascii'), line))
+        stdout.flush()
+    else:
+        raise Exception('invalid command: %s' % command)
@@ -0,0 +1,127 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Directories on the build host that commonly contain a terminfo database.
+const HOST_TERMINFO_DIRS: &[&str] = &[
+    "/etc/terminfo",
+    "/lib/terminfo",
+    "/usr/share/terminfo",
+    "/usr/lib/terminfo",
+    "/usr/local/share/terminfo",
+];
+
+/// Find the file defining a terminfo entry in a terminfo directory.
+///
+/// Entries are stored in a directory named after their first character. macOS
+/// names the directory after the hex value of that character instead.
+fn find_terminfo_entry(dir: &Path, name: &str) -> Option<PathBuf> {
+    let first = name.chars().next()?;
+
+    for subdir in &[first.to_string(), format!("{:x}", first as u32)] {
+        let path = dir.join(subdir).join(name);
+
+        if path.is_file() {
+            return Some(path);
+        }
+    }
+
+    None
+}
+
+/// Resolve the data for named terminfo entries.
+///
+/// Entries are read from `source_dir` if defined. Otherwise well-known
+/// terminfo directories on the build host are searched.
+pub fn resolve_terminfo_entries(
+    names: &[String],
+    source_dir: Option<&str>,
+) -> Result<BTreeMap<String, Vec<u8>>, String> {
+    let dirs: Vec<PathBuf> = match source_dir {
+        Some(dir) => vec![PathBuf::from(dir)],
+        None => HOST_TERMINFO_DIRS.iter().map(PathBuf::from).collect(),
+    };
+
+    let mut res = BTreeMap::new();
+
+    for name in names {
+        let path = dirs
+            .iter()
+            .filter_map(|dir| find_terminfo_entry(dir, name))
+            .next()
+            .ok_or_else(|| format!("unable to find terminfo entry {}", name))?;
+
+        let data = fs::read(&path).or_else(|e| Err(e.to_string()))?;
+
+        res.insert(name.clone(), data);
+    }
+
+    Ok(res)
+}
+
+/// Serialize terminfo entries to a writer.
+///
+/// See the documentation in the `pyembed` crate for the data format.
+pub fn write_terminfo_entries<W: Write>(
+    mut dest: W,
+    entries: &BTreeMap<String, Vec<u8>>,
+) -> std::io::Result<()> {
+    dest.write_u32::<LittleEndian>(entries.len() as u32)?;
+
+    for (name, data) in entries {
+        dest.write_u32::<LittleEndian>(name.as_bytes().len() as u32)?;
+        dest.write_u32::<LittleEndian>(data.len() as u32)?;
+    }
+
+    for name in entries.keys() {
+        dest.write_all(name.as_bytes())?;
+    }
+
+    for data in entries.values() {
+        dest.write_all(data)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{create_dir_all, write};
+
+    #[test]
+    fn test_resolve_terminfo_entries() {
+        let td = tempdir::TempDir::new("pyoxidizer-test").unwrap();
+        let tp = td.path();
+
+        create_dir_all(tp.join("x")).unwrap();
+        create_dir_all(tp.join("6c")).unwrap();
+        write(tp.join("x").join("xterm"), "xterm data").unwrap();
+        write(tp.join("6c").join("linux"), "linux data").unwrap();
+
+        let names = vec!["xterm".to_string(), "linux".to_string()];
+        let entries = resolve_terminfo_entries(&names, tp.to_str()).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries["xterm"], b"xterm data");
+        assert_eq!(entries["linux"], b"linux data");
+
+        let err = resolve_terminfo_entries(&["vt100".to_string()], tp.to_str()).unwrap_err();
+        assert_eq!(err, "unable to find terminfo entry vt100");
+
+        let mut data = Vec::new();
+        write_terminfo_entries(&mut data, &entries).unwrap();
+
+        let mut wanted = Vec::new();
+        wanted.extend_from_slice(&[2, 0, 0, 0]);
+        wanted.extend_from_slice(&[5, 0, 0, 0, 10, 0, 0, 0]);
+        wanted.extend_from_slice(&[5, 0, 0, 0, 10, 0, 0, 0]);
+        wanted.extend_from_slice(b"linuxxtermlinux dataxterm data");
+        assert_eq!(data, wanted);
+    }
+}
@@ -7,8 +7,8 @@
 use python3_sys as pyffi;
 use std::ffi::CString;
 
-/// Defines which allocator to use for the raw domain.
-#[derive(Clone, Debug)]
+/// Defines which allocator to use for a Python memory domain.
+#[derive(Clone, Debug, PartialEq)]
 pub enum PythonRawAllocator {
     /// Use jemalloc.
     Jemalloc,
@@ -75,10 +75,27 @@ pub struct PythonConfig {
     /// Whether to load the filesystem-based sys.meta_path finder.
     pub filesystem_importer: bool,
 
+    /// Patterns of modules that may be imported.
+    ///
+    /// Patterns match module names, with ``*`` matching any characters, and
+    /// submodules of packages they match. If non-empty, importing modules
+    /// not matching any pattern raises ``ImportError``. Only enforced after
+    /// the interpreter is initialized. Modules imported during initialization
+    /// remain importable from ``sys.modules``.
+    pub import_allow: Vec<String>,
+
+    /// Patterns of modules that may not be imported.
+    ///
+    /// Takes precedence over ``import_allow``.
+    pub import_deny: Vec<String>,
+
     /// Filesystem paths to add to sys.path.
     ///
     /// ``$ORIGIN`` will resolve to the directory of the application at
     /// run-time.
+    ///
+    /// Without custom frozen ``importlib`` modules, these are Python's module
+    /// search path. If none are defined, Python computes it as usual.
     pub sys_paths: Vec<String>,
 
     /// Controls whether to detect comparing bytes/bytearray with str.
@@ -134,6 +151,42 @@ pub struct PythonConfig {
     /// Controls the level of the verbose mode for the interpreter.
     pub verbose: i32,
 
+    /// Whether to enable Python's UTF-8 mode (PEP 540).
+    pub utf8_mode: bool,
+
+    /// Whether to coerce the legacy C locale to a UTF-8 based locale (PEP 538).
+    ///
+    /// If the ``LC_CTYPE`` locale resolves to ``C`` or ``POSIX``, the
+    /// ``LC_CTYPE`` environment variable is set to the first available of
+    /// ``C.UTF-8``, ``C.utf8`` and ``UTF-8``. Has no effect on Windows.
+    pub coerce_c_locale: bool,
+
+    /// Warning filters to install, as if passed via ``-W``.
+    ///
+    /// e.g. ``error::DeprecationWarning``.
+    pub warnoptions: Vec<String>,
+
+    /// Implementation specific options, as if passed via ``-X``.
+    ///
+    /// Values are of the form ``name`` or ``name=value``. With Python 3.7,
+    /// the interpreter reads most options before these can be registered,
+    /// so they are only reflected in ``sys._xoptions``. ``dev`` and
+    /// ``tracemalloc`` are applied by ``pyembed`` itself.
+    pub xoptions: Vec<String>,
+
+    /// Whether to enable Python's development mode.
+    ///
+    /// This installs debug hooks on memory allocators, enables
+    /// ``faulthandler`` and shows warnings that are ignored by default.
+    pub dev_mode: bool,
+
+    /// Fixed seed for hashing ``str`` and ``bytes`` instances.
+    ///
+    /// ``0`` disables hash randomization. With Python 3.7, the seed is
+    /// passed via ``PYTHONHASHSEED``, which requires ``ignore_python_env``
+    /// to be false.
+    pub hash_seed: Option<u32>,
+
     /// Bytecode for the importlib._bootstrap / _frozen_importlib module.
     pub frozen_importlib_data: &'static [u8],
 
@@ -152,6 +205,26 @@ pub struct PythonConfig {
     /// likely comes from an include_bytes!(...) of a file generated by PyOxidizer.
     pub py_resources_data: &'static [u8],
 
+    /// Reference to raw zip archives data.
+    ///
+    /// Each archive holds the files of a zip archive, such as an egg, which
+    /// is importable from a `sys.path` entry named after the archive. The
+    /// referenced data is produced as part of PyOxidizer packaging.
+    pub zip_archives_data: &'static [u8],
+
+    /// Directory to load packed modules, resources and zip archives data from.
+    ///
+    /// If defined, the ``py-modules``, ``python-resources`` and
+    /// ``python-zip-archives`` files written by PyOxidizer are read from this
+    /// directory when the interpreter is created and replace the values of
+    /// ``py_modules_data``, ``py_resources_data`` and ``zip_archives_data``.
+    /// ``$ORIGIN`` is expanded to the directory of the current executable.
+    ///
+    /// This allows changing Python code without rebuilding the binary. Files
+    /// are read once per process and the loaded data is never freed. So
+    /// interpreters initialized later in the process use the same data.
+    pub packed_data_directory: Option<String>,
+
     /// Extra extension modules to make available to the interpreter.
     ///
     /// The values will effectively be passed to ``PyImport_ExtendInitTab()``.
@@ -178,9 +251,70 @@ pub struct PythonConfig {
     /// Which memory allocator to use for the raw domain.
     pub raw_allocator: PythonRawAllocator,
 
+    /// Which memory allocator to use for the mem domain.
+    ///
+    /// ``None`` keeps Python's default allocator (pymalloc).
+    pub mem_allocator: Option<PythonRawAllocator>,
+
+    /// Which memory allocator to use for the object domain.
+    ///
+    /// ``None`` keeps Python's default allocator (pymalloc).
+    pub obj_allocator: Option<PythonRawAllocator>,
+
+    /// Whether to record statistics about memory allocations.
+    ///
+    /// When enabled, the number of bytes and allocations in each memory
+    /// domain are tracked and can be queried via ``allocator_stats()``.
+    pub allocator_stats: bool,
+
     /// How to resolve the `terminfo` database.
     pub terminfo_resolution: TerminfoResolution,
 
+    /// Reference to raw terminfo entries data.
+    ///
+    /// If the entry for the current terminal isn't available on the system,
+    /// entries are extracted to a private directory at run-time and that
+    /// directory is added to ``TERMINFO_DIRS``.
+    pub terminfo_data: &'static [u8],
+
+    /// Whether Python should install its own signal handlers.
+    ///
+    /// This is the value passed to ``Py_InitializeEx()``. When false, Python
+    /// does not install a ``SIGINT`` handler raising ``KeyboardInterrupt``
+    /// and leaves any handlers set by the host application in place.
+    pub install_signal_handlers: bool,
+
+    /// Names of signals (e.g. ``SIGPIPE``) to ignore.
+    ///
+    /// Signals are ignored before the interpreter is initialized. Python will
+    /// not install a ``SIGINT`` handler if ``SIGINT`` is ignored. Previous
+    /// dispositions are restored when the interpreter is dropped.
+    pub ignore_signals: Vec<String>,
+
+    /// Names of signals to forward to the interpreter.
+    ///
+    /// After the interpreter is initialized, a handler is installed for each
+    /// signal which raises ``KeyboardInterrupt`` in the main thread. Previous
+    /// handlers are restored when the interpreter is dropped.
+    pub forward_signals: Vec<String>,
+
+    /// Path to a JSON file whose values override fields in this config.
+    ///
+    /// ``$ORIGIN`` will resolve to the directory of the application at
+    /// run-time. The file is ignored if it does not exist.
+    pub config_overlay_path: Option<String>,
+
+    /// Environment variable holding the path to a config overlay file.
+    ///
+    /// If the environment variable is set, its value takes precedence over
+    /// ``config_overlay_path``.
+    pub config_overlay_env: Option<String>,
+
+    /// Names of fields that a config overlay file is allowed to override.
+    ///
+    /// If empty, no config overlay file is loaded.
+    pub config_overlay_fields: Vec<String>,
+
     /// Environment variable holding the directory to write a loaded modules file.
     ///
     /// If this value is set and the environment it refers to is set,
@@ -189,7 +323,347 @@ pub struct PythonConfig {
     /// loaded in ``sys.modules``.
     pub write_modules_directory_env: Option<String>,
 
+    /// Environment variable enabling self-test mode.
+    ///
+    /// If this value is set and the environment variable it refers to is
+    /// set, every module embedded in memory is imported instead of running
+    /// the configured run mode and a report is printed to stdout. The
+    /// environment variable's value is a comma delimited list of module name
+    /// patterns. Patterns prefixed with ``-`` exclude modules.
+    pub self_test_env: Option<String>,
+
+    /// Whether to enable the ``faulthandler`` module during initialization.
+    ///
+    /// This dumps Python tracebacks when fatal signals are received.
+    pub faulthandler: bool,
+
+    /// Path to file to receive ``faulthandler`` output.
+    ///
+    /// ``$ORIGIN`` is expanded to the directory of the current executable.
+    /// If not defined, output is written to stderr.
+    pub faulthandler_path: Option<String>,
+
+    /// Whether to forward Python ``logging`` records to the ``log`` crate.
+    ///
+    /// If set, a handler is added to the root logger during initialization.
+    /// Records are logged with the logger name as target. The root logger
+    /// level can be set with ``set_python_log_level()``.
+    pub logging_bridge: bool,
+
+    /// Directory to write crash reports to.
+    ///
+    /// If defined, a ``crash-<UUID>.json`` file describing uncaught exceptions
+    /// is written to this directory. ``$ORIGIN`` is expanded to the directory
+    /// of the current executable.
+    pub crash_report_directory: Option<String>,
+
+    /// Version of the application recorded in crash reports.
+    pub app_version: Option<String>,
+
+    /// Value for ``sys.prefix`` and ``sys.base_prefix``.
+    ///
+    /// ``$ORIGIN`` is expanded to the directory of the current executable.
+    /// If not defined, the path of the current executable is used.
+    pub sys_prefix: Option<String>,
+
+    /// Value for ``sys.exec_prefix`` and ``sys.base_exec_prefix``.
+    ///
+    /// ``$ORIGIN`` is expanded to the directory of the current executable.
+    /// If not defined, ``sys_prefix`` is used.
+    pub sys_exec_prefix: Option<String>,
+
+    /// Value for the ``TCL_LIBRARY`` environment variable.
+    ///
+    /// Used by ``tkinter`` to locate Tcl library files. ``$ORIGIN`` is
+    /// expanded to the directory of the current executable. The environment
+    /// variable is only set if it isn't already defined.
+    pub tcl_library: Option<String>,
+
+    /// Value for the ``TK_LIBRARY`` environment variable.
+    ///
+    /// Semantics are the same as ``tcl_library``.
+    pub tk_library: Option<String>,
+
+    /// PEM encoded CA certificates used to verify TLS connections.
+    ///
+    /// If non-empty, the certificates are written to a private file at
+    /// run-time and ``SSL_CERT_FILE`` is set to it, making it the default
+    /// trust store of the ``ssl`` module. Nothing is done if ``SSL_CERT_FILE``
+    /// or ``SSL_CERT_DIR`` is already defined.
+    pub ssl_ca_bundle_data: &'static [u8],
+
     /// Defines what code to run by default.
     ///
     pub run: PythonRunMode,
 }
+
+/// Builds a `PythonConfig` from Rust code.
+///
+/// Instances are obtained via [`PythonConfigBuilder::new()`](#method.new),
+/// customized by chaining method calls, and turned into a
+/// [`PythonConfig`](struct.PythonConfig.html) via [`build()`](#method.build).
+/// Fields without a builder method can be set on the built config.
+///
+/// Defaults match those of PyOxidizer's ``EmbeddedPythonConfig()``, except
+/// that modules are imported from the filesystem and no code is run. Unless
+/// paths are added via [`sys_path()`](#method.sys_path), Python finds its
+/// standard library as it normally would. No data is embedded by default. Importing modules from memory requires the
+/// custom frozen ``importlib`` bytecode produced by PyOxidizer, which is
+/// registered via [`frozen_importlib()`](#method.frozen_importlib).
+pub struct PythonConfigBuilder {
+    config: PythonConfig,
+}
+
+impl Default for PythonConfigBuilder {
+    fn default() -> Self {
+        PythonConfigBuilder::new()
+    }
+}
+
+impl PythonConfigBuilder {
+    /// Start defining a config having default values.
+    pub fn new() -> PythonConfigBuilder {
+        PythonConfigBuilder {
+            config: PythonConfig {
+                standard_io_encoding: None,
+                standard_io_errors: None,
+                opt_level: 0,
+                use_custom_importlib: false,
+                filesystem_importer: true,
+                import_allow: Vec::new(),
+                import_deny: Vec::new(),
+                sys_paths: Vec::new(),
+                bytes_warning: 0,
+                import_site: false,
+                import_user_site: false,
+                ignore_python_env: true,
+                inspect: false,
+                interactive: false,
+                isolated: false,
+                legacy_windows_fs_encoding: false,
+                legacy_windows_stdio: false,
+                dont_write_bytecode: true,
+                unbuffered_stdio: false,
+                parser_debug: false,
+                quiet: false,
+                use_hash_seed: false,
+                verbose: 0,
+                utf8_mode: false,
+                coerce_c_locale: false,
+                warnoptions: Vec::new(),
+                xoptions: Vec::new(),
+                dev_mode: false,
+                hash_seed: None,
+                frozen_importlib_data: &[],
+                frozen_importlib_external_data: &[],
+                py_modules_data: &[],
+                py_resources_data: &[],
+                zip_archives_data: &[],
+                packed_data_directory: None,
+                extra_extension_modules: Vec::new(),
+                argvb: false,
+                sys_frozen: false,
+                sys_meipass: false,
+                raw_allocator: if cfg!(feature = "jemalloc-sys") {
+                    PythonRawAllocator::Jemalloc
+                } else {
+                    PythonRawAllocator::System
+                },
+                mem_allocator: None,
+                obj_allocator: None,
+                allocator_stats: false,
+                terminfo_resolution: TerminfoResolution::Dynamic,
+                terminfo_data: &[],
+                install_signal_handlers: true,
+                ignore_signals: Vec::new(),
+                forward_signals: Vec::new(),
+                config_overlay_path: None,
+                config_overlay_env: None,
+                config_overlay_fields: Vec::new(),
+                write_modules_directory_env: None,
+                self_test_env: None,
+                faulthandler: false,
+                faulthandler_path: None,
+                logging_bridge: false,
+                crash_report_directory: None,
+                app_version: None,
+                sys_prefix: None,
+                sys_exec_prefix: None,
+                tcl_library: None,
+                tk_library: None,
+                ssl_ca_bundle_data: &[],
+                run: PythonRunMode::None,
+            },
+        }
+    }
+
+    /// Use custom frozen ``importlib`` modules supporting in-memory imports.
+    ///
+    /// Values are the bytecode of the ``importlib._bootstrap`` and
+    /// ``importlib._bootstrap_external`` modules produced by PyOxidizer for
+    /// the linked Python. They are written to the ``importlib_bootstrap``
+    /// and ``importlib_bootstrap_external`` files in the build artifacts
+    /// directory.
+    pub fn frozen_importlib(
+        mut self,
+        bootstrap: &'static [u8],
+        bootstrap_external: &'static [u8],
+    ) -> PythonConfigBuilder {
+        self.config.use_custom_importlib = true;
+        self.config.frozen_importlib_data = bootstrap;
+        self.config.frozen_importlib_external_data = bootstrap_external;
+        self
+    }
+
+    /// Set packed Python modules data to import modules from.
+    pub fn py_modules_data(mut self, data: &'static [u8]) -> PythonConfigBuilder {
+        self.config.py_modules_data = data;
+        self
+    }
+
+    /// Set packed resources data to load resources from.
+    pub fn py_resources_data(mut self, data: &'static [u8]) -> PythonConfigBuilder {
+        self.config.py_resources_data = data;
+        self
+    }
+
+    /// Set packed zip archives data to import modules from.
+    pub fn zip_archives_data(mut self, data: &'static [u8]) -> PythonConfigBuilder {
+        self.config.zip_archives_data = data;
+        self
+    }
+
+    /// Load packed data from a directory at run-time.
+    pub fn packed_data_directory(mut self, path: &str) -> PythonConfigBuilder {
+        self.config.packed_data_directory = Some(path.to_string());
+        self
+    }
+
+    /// Set whether to import modules from the filesystem.
+    pub fn filesystem_importer(mut self, enabled: bool) -> PythonConfigBuilder {
+        self.config.filesystem_importer = enabled;
+        self
+    }
+
+    /// Add a path to ``sys.path``.
+    ///
+    /// ``$ORIGIN`` will resolve to the directory of the application at
+    /// run-time.
+    pub fn sys_path(mut self, path: &str) -> PythonConfigBuilder {
+        self.config.sys_paths.push(path.to_string());
+        self
+    }
+
+    /// Add an extension module to make available to the interpreter.
+    pub fn extension_module(mut self, module: ExtensionModule) -> PythonConfigBuilder {
+        self.config.extra_extension_modules.push(module);
+        self
+    }
+
+    /// Set the code to run by default.
+    pub fn run(mut self, run: PythonRunMode) -> PythonConfigBuilder {
+        self.config.run = run;
+        self
+    }
+
+    /// Obtain the `PythonConfig`.
+    ///
+    /// Fails if packed data is defined without custom frozen ``importlib``
+    /// modules, as only those can import from memory.
+    pub fn build(self) -> Result<PythonConfig, &'static str> {
+        let config = self.config;
+
+        let has_packed_data = !config.py_modules_data.is_empty()
+            || !config.py_resources_data.is_empty()
+            || !config.zip_archives_data.is_empty()
+            || config.packed_data_directory.is_some();
+
+        if has_packed_data && !config.use_custom_importlib {
+            return Err("packed data requires frozen importlib data");
+        }
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static BOOTSTRAP: &[u8] = b"bootstrap";
+    static BOOTSTRAP_EXTERNAL: &[u8] = b"bootstrap_external";
+
+    #[test]
+    fn test_builder_defaults() {
+        let config = PythonConfigBuilder::new().build().unwrap();
+
+        assert!(!config.use_custom_importlib);
+        assert!(config.filesystem_importer);
+        assert!(config.frozen_importlib_data.is_empty());
+        assert!(config.frozen_importlib_external_data.is_empty());
+        assert!(config.py_modules_data.is_empty());
+        assert!(config.py_resources_data.is_empty());
+        assert!(config.zip_archives_data.is_empty());
+        assert!(config.packed_data_directory.is_none());
+        assert!(config.sys_paths.is_empty());
+        assert!(config.extra_extension_modules.is_empty());
+        assert!(config.ignore_python_env);
+        assert!(!config.import_site);
+        match config.run {
+            PythonRunMode::None => {}
+            _ => panic!("expected no-op run mode"),
+        }
+    }
+
+    #[test]
+    fn test_builder_fields() {
+        let config = PythonConfigBuilder::new()
+            .frozen_importlib(BOOTSTRAP, BOOTSTRAP_EXTERNAL)
+            .py_modules_data(b"modules")
+            .py_resources_data(b"resources")
+            .zip_archives_data(b"zip")
+            .packed_data_directory("$ORIGIN/data")
+            .filesystem_importer(false)
+            .sys_path("$ORIGIN/lib")
+            .sys_path("/usr/lib/app")
+            .run(PythonRunMode::Module {
+                module: "app".to_string(),
+            })
+            .build()
+            .unwrap();
+
+        assert!(config.use_custom_importlib);
+        assert_eq!(config.frozen_importlib_data, BOOTSTRAP);
+        assert_eq!(config.frozen_importlib_external_data, BOOTSTRAP_EXTERNAL);
+        assert_eq!(config.py_modules_data, b"modules");
+        assert_eq!(config.py_resources_data, b"resources");
+        assert_eq!(config.zip_archives_data, b"zip");
+        assert_eq!(
+            config.packed_data_directory,
+            Some("$ORIGIN/data".to_string())
+        );
+        assert!(!config.filesystem_importer);
+        assert_eq!(config.sys_paths, ["$ORIGIN/lib", "/usr/lib/app"]);
+        match config.run {
+            PythonRunMode::Module { ref module } => assert_eq!(module, "app"),
+            _ => panic!("expected module run mode"),
+        }
+    }
+
+    #[test]
+    fn test_builder_packed_data_requires_importlib() {
+        let builders = vec![
+            PythonConfigBuilder::new().py_modules_data(b"modules"),
+            PythonConfigBuilder::new().py_resources_data(b"resources"),
+            PythonConfigBuilder::new().zip_archives_data(b"zip"),
+            PythonConfigBuilder::new().packed_data_directory("data"),
+        ];
+
+        for builder in builders {
+            assert_eq!(
+                builder.build().err(),
+                Some("packed data requires frozen importlib data")
+            );
+        }
+    }
+}
@@ -0,0 +1,395 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Bridge Python awaitables and Rust futures.
+//!
+//! Python awaitables are driven by an asyncio event loop running on a
+//! dedicated thread and exposed to Rust as a `Future`. Rust futures are
+//! exposed to Python as asyncio futures and polled by the event loop they
+//! are awaited from.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+use std::thread;
+
+use cpython::{
+    py_class, py_class_impl, py_coerce_item, GILGuard, NoArgs, ObjectProtocol, PyClone, PyDict,
+    PyErr, PyObject, PyResult, Python, PythonObject, ToPyObject,
+};
+
+/// Python source defining a coroutine that awaits an arbitrary awaitable.
+///
+/// `asyncio.run_coroutine_threadsafe()` only accepts coroutine objects.
+const AWAIT_SOURCE: &str = "async def await_awaitable(aw):\n    return await aw\n";
+
+/// Result of a Python awaitable and the waker to notify when it is available.
+struct CompletionState {
+    done: bool,
+    result: Option<PyResult<PyObject>>,
+    waker: Option<Waker>,
+}
+
+py_class!(class CompletionCallback |py| {
+    data state: Arc<Mutex<CompletionState>>;
+
+    def __call__(&self, future: PyObject) -> PyResult<PyObject> {
+        let result = future.call_method(py, "result", NoArgs, None);
+
+        let waker = {
+            let mut state = match self.state(py).lock() {
+                Ok(state) => state,
+                Err(_) => return Ok(py.None()),
+            };
+
+            state.done = true;
+            state.result = Some(result);
+            state.waker.take()
+        };
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+
+        Ok(py.None())
+    }
+});
+
+/// A `Future` resolving to the result of a Python awaitable.
+///
+/// Instances are obtained from [`EventLoop::spawn()`](struct.EventLoop.html#method.spawn).
+/// Dropping an instance before it resolves cancels the awaitable.
+pub struct AwaitableFuture {
+    future: PyObject,
+    state: Arc<Mutex<CompletionState>>,
+}
+
+impl Future for AwaitableFuture {
+    type Output = PyResult<PyObject>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut state = self
+            .state
+            .lock()
+            .expect("unable to lock awaitable completion state");
+
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Drop for AwaitableFuture {
+    fn drop(&mut self) {
+        let done = match self.state.lock() {
+            Ok(state) => state.done,
+            Err(_) => true,
+        };
+
+        if !done {
+            let gil = GILGuard::acquire();
+            let py = gil.python();
+
+            let _ = self.future.call_method(py, "cancel", NoArgs, None);
+        }
+    }
+}
+
+/// An asyncio event loop running on a dedicated thread.
+///
+/// Python awaitables can be submitted to the loop via
+/// [`spawn()`](#method.spawn), which returns a Rust `Future` resolving to the
+/// awaitable's result.
+///
+/// The loop is stopped and its thread joined when the instance is dropped.
+/// Instances must be dropped before the interpreter is finalized.
+pub struct EventLoop {
+    event_loop: PyObject,
+    await_fn: PyObject,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl EventLoop {
+    /// Create a new asyncio event loop and start running it on a new thread.
+    ///
+    /// The loop can't make progress until the calling thread releases the GIL.
+    pub fn new(py: Python) -> PyResult<EventLoop> {
+        let asyncio = py.import("asyncio")?;
+        let event_loop = asyncio.call(py, "new_event_loop", NoArgs, None)?;
+
+        let globals = PyDict::new(py);
+        py.run(AWAIT_SOURCE, Some(&globals), None)?;
+        let await_fn = match globals.get_item(py, "await_awaitable") {
+            Some(f) => f,
+            None => {
+                return Err(PyErr::new::<cpython::exc::RuntimeError, _>(
+                    py,
+                    "unable to define awaitable helper",
+                ))
+            }
+        };
+
+        let thread_loop = event_loop.clone_ref(py);
+
+        let thread = thread::Builder::new()
+            .name("pyembed-asyncio".to_string())
+            .spawn(move || {
+                let event_loop = thread_loop;
+                let gil = GILGuard::acquire();
+                let py = gil.python();
+
+                let res = py.import("asyncio").and_then(|asyncio| {
+                    asyncio.call(py, "set_event_loop", (&event_loop,), None)?;
+                    event_loop.call_method(py, "run_forever", NoArgs, None)?;
+                    event_loop.call_method(py, "close", NoArgs, None)
+                });
+
+                if let Err(e) = res {
+                    e.print(py);
+                }
+            })
+            .or_else(|_| {
+                Err(PyErr::new::<cpython::exc::RuntimeError, _>(
+                    py,
+                    "unable to start event loop thread",
+                ))
+            })?;
+
+        Ok(EventLoop {
+            event_loop,
+            await_fn,
+            thread: Some(thread),
+        })
+    }
+
+    /// The Python event loop object.
+    pub fn event_loop(&self) -> &PyObject {
+        &self.event_loop
+    }
+
+    /// Schedule a Python awaitable to run on the event loop.
+    ///
+    /// Returns a `Future` that resolves to the awaitable's result. Exceptions
+    /// raised by the awaitable are returned as errors.
+    pub fn spawn(&self, py: Python, awaitable: PyObject) -> PyResult<AwaitableFuture> {
+        let asyncio = py.import("asyncio")?;
+
+        let coroutine = self.await_fn.call(py, (awaitable,), None)?;
+        let future = asyncio.call(
+            py,
+            "run_coroutine_threadsafe",
+            (coroutine, &self.event_loop),
+            None,
+        )?;
+
+        let state = Arc::new(Mutex::new(CompletionState {
+            done: false,
+            result: None,
+            waker: None,
+        }));
+
+        let callback = CompletionCallback::create_instance(py, state.clone())?;
+        future.call_method(py, "add_done_callback", (callback,), None)?;
+
+        Ok(AwaitableFuture { future, state })
+    }
+}
+
+impl Drop for EventLoop {
+    fn drop(&mut self) {
+        let gil = GILGuard::acquire();
+        let py = gil.python();
+
+        let res = self.event_loop.getattr(py, "stop").and_then(|stop| {
+            self.event_loop
+                .call_method(py, "call_soon_threadsafe", (stop,), None)
+        });
+
+        if res.is_err() {
+            return;
+        }
+
+        // The loop thread needs the GIL to stop.
+        if let Some(thread) = self.thread.take() {
+            py.allow_threads(move || {
+                let _ = thread.join();
+            });
+        }
+    }
+}
+
+type BoxedPyFuture = Pin<Box<dyn Future<Output = PyResult<PyObject>> + Send>>;
+
+/// Adapts a future to resolve to a `PyObject`.
+///
+/// Must only be polled with the GIL held.
+struct IntoPyObjectFuture<F> {
+    inner: Pin<Box<F>>,
+}
+
+impl<F, T> Future for IntoPyObjectFuture<F>
+where
+    F: Future<Output = PyResult<T>>,
+    T: ToPyObject,
+{
+    type Output = PyResult<PyObject>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        match self.inner.as_mut().poll(cx) {
+            Poll::Ready(Ok(value)) => {
+                let py = unsafe { Python::assume_gil_acquired() };
+                Poll::Ready(Ok(value.to_py_object(py).into_object()))
+            }
+            Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// A Rust future being driven by an asyncio event loop.
+struct Task {
+    future: Mutex<Option<BoxedPyFuture>>,
+    event_loop: PyObject,
+    py_future: PyObject,
+}
+
+py_class!(class TaskStep |py| {
+    data task: Arc<Task>;
+
+    def __call__(&self) -> PyResult<PyObject> {
+        poll_task(py, self.task(py))?;
+
+        Ok(py.None())
+    }
+});
+
+/// Schedule a task to be polled by its event loop.
+fn schedule_task(task: &Arc<Task>) {
+    let gil = GILGuard::acquire();
+    let py = gil.python();
+
+    // Errors are ignored because there's nothing we can do about them. e.g.
+    // the event loop was closed.
+    if let Ok(step) = TaskStep::create_instance(py, task.clone()) {
+        let _ = task
+            .event_loop
+            .call_method(py, "call_soon_threadsafe", (step,), None);
+    }
+}
+
+unsafe fn waker_clone(data: *const ()) -> RawWaker {
+    let task = Arc::from_raw(data as *const Task);
+    let cloned = task.clone();
+    std::mem::forget(task);
+
+    RawWaker::new(Arc::into_raw(cloned) as *const (), &WAKER_VTABLE)
+}
+
+unsafe fn waker_wake(data: *const ()) {
+    let task = Arc::from_raw(data as *const Task);
+    schedule_task(&task);
+}
+
+unsafe fn waker_wake_by_ref(data: *const ()) {
+    let task = Arc::from_raw(data as *const Task);
+    schedule_task(&task);
+    std::mem::forget(task);
+}
+
+unsafe fn waker_drop(data: *const ()) {
+    drop(Arc::from_raw(data as *const Task));
+}
+
+static WAKER_VTABLE: RawWakerVTable =
+    RawWakerVTable::new(waker_clone, waker_wake, waker_wake_by_ref, waker_drop);
+
+/// Poll a task's future and resolve its Python future if it completed.
+///
+/// Called on the event loop's thread with the GIL held.
+fn poll_task(py: Python, task: &Arc<Task>) -> PyResult<()> {
+    let mut future = match task.future.lock() {
+        Ok(future) => future,
+        Err(_) => return Ok(()),
+    };
+
+    // The Python future was cancelled. Drop the Rust future to cancel it.
+    if task
+        .py_future
+        .call_method(py, "done", NoArgs, None)?
+        .is_true(py)?
+    {
+        *future = None;
+        return Ok(());
+    }
+
+    let waker = unsafe {
+        Waker::from_raw(RawWaker::new(
+            Arc::into_raw(task.clone()) as *const (),
+            &WAKER_VTABLE,
+        ))
+    };
+    let mut cx = Context::from_waker(&waker);
+
+    let result = match future.as_mut() {
+        Some(f) => match f.as_mut().poll(&mut cx) {
+            Poll::Ready(result) => result,
+            Poll::Pending => return Ok(()),
+        },
+        None => return Ok(()),
+    };
+
+    *future = None;
+    drop(future);
+
+    match result {
+        Ok(value) => {
+            task.py_future
+                .call_method(py, "set_result", (value,), None)?;
+        }
+        Err(mut e) => {
+            task.py_future
+                .call_method(py, "set_exception", (e.instance(py),), None)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Expose a Rust future to Python as an asyncio future.
+///
+/// The future is polled by the current thread's asyncio event loop with the
+/// GIL held, so it should not block. Futures that depend on a specific
+/// executor (e.g. for timers or I/O) should be spawned on that executor and
+/// a handle to its result passed to this function instead.
+///
+/// This is typically called by functions of extension modules implemented
+/// in Rust, whose result can then be awaited by Python code.
+pub fn future_into_py<F, T>(py: Python, future: F) -> PyResult<PyObject>
+where
+    F: Future<Output = PyResult<T>> + Send + 'static,
+    T: ToPyObject,
+{
+    let asyncio = py.import("asyncio")?;
+    let event_loop = asyncio.call(py, "get_event_loop", NoArgs, None)?;
+    let py_future = event_loop.call_method(py, "create_future", NoArgs, None)?;
+
+    let task = Arc::new(Task {
+        future: Mutex::new(Some(Box::pin(IntoPyObjectFuture {
+            inner: Box::pin(future),
+        }))),
+        event_loop,
+        py_future: py_future.clone_ref(py),
+    });
+
+    let step = TaskStep::create_instance(py, task.clone())?;
+    task.event_loop
+        .call_method(py, "call_soon_threadsafe", (step,), None)?;
+
+    Ok(py_future)
+}
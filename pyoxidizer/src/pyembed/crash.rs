@@ -0,0 +1,140 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Report crashes of embedded Python applications.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use cpython::{ObjectProtocol, PyClone, PyDict, PyErr, Python, PythonObject};
+
+use super::json::JsonValue;
+
+/// Enable the `faulthandler` module.
+///
+/// Output is appended to the file at `path` or written to stderr if no path
+/// is given.
+pub fn enable_faulthandler(py: Python, path: Option<&str>) -> Result<(), &'static str> {
+    let faulthandler = py
+        .import("faulthandler")
+        .or_else(|_| Err("unable to import faulthandler"))?;
+
+    let kwargs = PyDict::new(py);
+    kwargs
+        .set_item(py, "all_threads", true)
+        .or_else(|_| Err("unable to set faulthandler arguments"))?;
+
+    if let Some(path) = path {
+        // faulthandler holds a reference to the file, keeping it open.
+        let io = py.import("io").or_else(|_| Err("unable to import io"))?;
+        let f = io
+            .call(py, "open", (path, "a"), None)
+            .or_else(|_| Err("unable to open faulthandler file"))?;
+
+        kwargs
+            .set_item(py, "file", f)
+            .or_else(|_| Err("unable to set faulthandler arguments"))?;
+    }
+
+    faulthandler
+        .call(py, "enable", cpython::NoArgs, Some(&kwargs))
+        .or_else(|_| Err("unable to enable faulthandler"))?;
+
+    Ok(())
+}
+
+/// Write a JSON crash report describing an uncaught exception.
+///
+/// The report is written to a ``crash-<UUID>.json`` file in `directory`,
+/// which is created if necessary. Returns the path of the written file.
+pub fn write_crash_report(
+    py: Python,
+    directory: &Path,
+    app_version: Option<&str>,
+    err: &mut PyErr,
+) -> Result<PathBuf, &'static str> {
+    let sys = py
+        .import("sys")
+        .or_else(|_| Err("could not obtain sys module"))?;
+    let traceback = py
+        .import("traceback")
+        .or_else(|_| Err("could not obtain traceback module"))?;
+
+    let value = err.instance(py);
+    let exception_type = value.get_type(py);
+
+    let exception_type_name = match exception_type.as_object().getattr(py, "__qualname__") {
+        Ok(name) => name.to_string(),
+        Err(_) => exception_type.name(py).to_string(),
+    };
+    let message = value
+        .str(py)
+        .map(|s| s.to_string_lossy(py).to_string())
+        .unwrap_or_default();
+
+    let tb = match &err.ptraceback {
+        Some(tb) => tb.clone_ref(py),
+        None => py.None(),
+    };
+
+    let formatted = traceback
+        .call(
+            py,
+            "format_exception",
+            (exception_type.as_object(), &value, tb),
+            None,
+        )
+        .and_then(|lines| lines.extract::<Vec<String>>(py))
+        .or_else(|_| Err("could not format traceback"))?
+        .concat();
+
+    let python_version = sys
+        .get(py, "version")
+        .and_then(|v| v.extract::<String>(py))
+        .or_else(|_| Err("could not obtain sys.version"))?;
+
+    let argv = sys
+        .get(py, "argv")
+        .and_then(|v| v.extract::<Vec<String>>(py))
+        .unwrap_or_default();
+
+    let modules = sys
+        .get(py, "modules")
+        .or_else(|_| Err("could not obtain sys.modules"))?;
+    let modules = modules
+        .cast_as::<PyDict>(py)
+        .or_else(|_| Err("sys.modules is not a dict"))?;
+
+    let mut names = BTreeSet::new();
+    for (key, _value) in modules.items(py) {
+        if let Ok(name) = key.extract::<String>(py) {
+            names.insert(name);
+        }
+    }
+
+    let report = JsonValue::object(vec![
+        ("app_version", app_version.into()),
+        ("python_version", python_version.into()),
+        ("argv", argv.into()),
+        (
+            "exception",
+            JsonValue::object(vec![
+                ("type", exception_type_name.into()),
+                ("message", message.into()),
+                ("traceback", formatted.into()),
+            ]),
+        ),
+        ("modules", names.into_iter().collect::<Vec<_>>().into()),
+    ]);
+
+    fs::create_dir_all(directory)
+        .or_else(|_| Err("could not create directory for crash reports"))?;
+
+    let path = directory.join(format!("crash-{}.json", uuid::Uuid::new_v4().to_string()));
+
+    fs::write(&path, report.to_string_pretty()).or_else(|_| Err("could not write crash report"))?;
+
+    Ok(path)
+}
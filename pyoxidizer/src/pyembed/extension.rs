@@ -0,0 +1,263 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Define Python extension modules implemented in Rust.
+
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+
+use cpython::exc::{ImportError, SystemError};
+use cpython::py_class::PythonObjectFromPyClassMacro;
+use cpython::{ObjectProtocol, PyErr, PyModule, PyObject, PyResult, Python};
+use lazy_static::lazy_static;
+use libc::{c_int, c_void};
+use python3_sys as pyffi;
+
+use super::config::ExtensionModule;
+
+/// A function that populates a Python module object.
+///
+/// Functions are called with the GIL held when the module is imported.
+pub type ModuleInitializer = dyn Fn(Python, &PyModule) -> PyResult<()> + Send + Sync;
+
+/// Holds everything needed to populate a module registered via the builder.
+struct ModuleDefinition {
+    doc: Option<String>,
+    initializers: Vec<Box<ModuleInitializer>>,
+}
+
+lazy_static! {
+    /// Registered module definitions, keyed by module name.
+    ///
+    /// Python's `_inittab` initialization functions receive no arguments.
+    /// So we use a single initialization function for all modules and look
+    /// up the definition by the name of the module being created.
+    static ref MODULE_DEFINITIONS: Mutex<HashMap<String, Arc<ModuleDefinition>>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Name reported for the shared module definition.
+///
+/// Modules get their actual name from the spec they are imported with.
+const MODULE_DEF_NAME: &[u8] = b"pyembed_extension\0";
+
+static mut MODULE_SLOTS: [pyffi::PyModuleDef_Slot; 2] = [
+    pyffi::PyModuleDef_Slot {
+        slot: 0,
+        value: 0 as *mut _,
+    },
+    pyffi::PyModuleDef_Slot {
+        slot: 0,
+        value: 0 as *mut _,
+    },
+];
+
+static mut MODULE_DEF: pyffi::PyModuleDef = pyffi::PyModuleDef {
+    m_base: pyffi::PyModuleDef_HEAD_INIT,
+    m_name: std::ptr::null(),
+    m_doc: std::ptr::null(),
+    m_size: 0,
+    m_methods: 0 as *mut _,
+    m_slots: 0 as *mut _,
+    m_traverse: None,
+    m_clear: None,
+    m_free: None,
+};
+
+/// Builds an `ExtensionModule` whose contents are defined by Rust code.
+///
+/// Instances are obtained via [`ExtensionModuleBuilder::new()`](#method.new),
+/// customized by chaining method calls, and turned into an
+/// [`ExtensionModule`](struct.ExtensionModule.html) via
+/// [`build()`](#method.build). The resulting value should be added to
+/// `PythonConfig.extra_extension_modules` before the interpreter is created.
+///
+/// Modules use multi-phase initialization (PEP 489). Registered
+/// initializers run in the order they were added each time the module is
+/// imported.
+pub struct ExtensionModuleBuilder {
+    name: String,
+    doc: Option<String>,
+    initializers: Vec<Box<ModuleInitializer>>,
+}
+
+impl ExtensionModuleBuilder {
+    /// Start defining a module having the given fully qualified name.
+    pub fn new(name: &str) -> ExtensionModuleBuilder {
+        ExtensionModuleBuilder {
+            name: name.to_string(),
+            doc: None,
+            initializers: Vec::new(),
+        }
+    }
+
+    /// Set the module's docstring.
+    pub fn doc(mut self, doc: &str) -> ExtensionModuleBuilder {
+        self.doc = Some(doc.to_string());
+        self
+    }
+
+    /// Register a function to populate the module when it is imported.
+    pub fn init<F>(mut self, f: F) -> ExtensionModuleBuilder
+    where
+        F: Fn(Python, &PyModule) -> PyResult<()> + Send + Sync + 'static,
+    {
+        self.initializers.push(Box::new(f));
+        self
+    }
+
+    /// Add a class defined via the `py_class!` macro to the module.
+    pub fn add_class<T>(self) -> ExtensionModuleBuilder
+    where
+        T: PythonObjectFromPyClassMacro,
+    {
+        self.init(|py, m| m.add_class::<T>(py))
+    }
+
+    /// Register the module definition and obtain an `ExtensionModule`.
+    ///
+    /// Definitions are registered for the lifetime of the process. Building
+    /// a module having the name of a previously built module is an error.
+    /// The returned `ExtensionModule` can be reused by later interpreters.
+    pub fn build(self) -> Result<ExtensionModule, &'static str> {
+        let name = CString::new(self.name.clone())
+            .or_else(|_| Err("unable to convert module name to C string"))?;
+
+        let mut definitions = MODULE_DEFINITIONS
+            .lock()
+            .or_else(|_| Err("unable to lock module definitions"))?;
+
+        if definitions.contains_key(&self.name) {
+            return Err("extension module with this name already defined");
+        }
+
+        definitions.insert(
+            self.name,
+            Arc::new(ModuleDefinition {
+                doc: self.doc,
+                initializers: self.initializers,
+            }),
+        );
+
+        Ok(ExtensionModule {
+            name,
+            init_func: PyInit_pyembed_extension,
+        })
+    }
+}
+
+/// Execute a module created from our shared module definition.
+///
+/// This is the `Py_mod_exec` slot and is called with the GIL held.
+extern "C" fn exec_module(module: *mut pyffi::PyObject) -> c_int {
+    let py = unsafe { Python::assume_gil_acquired() };
+
+    let module = match unsafe { PyObject::from_borrowed_ptr(py, module).cast_into::<PyModule>(py) }
+    {
+        Ok(m) => m,
+        Err(e) => {
+            PyErr::from(e).restore(py);
+            return -1;
+        }
+    };
+
+    // Unwinding across the FFI boundary is undefined behavior. So panics in
+    // initializers are converted to Python exceptions.
+    let res = match catch_unwind(AssertUnwindSafe(|| populate_module(py, &module))) {
+        Ok(res) => res,
+        Err(_) => Err(PyErr::new::<SystemError, _>(
+            py,
+            "panic while initializing extension module",
+        )),
+    };
+
+    match res {
+        Ok(()) => 0,
+        Err(e) => {
+            e.restore(py);
+            -1
+        }
+    }
+}
+
+/// Convert an initializer error to an `ImportError` caused by it.
+fn import_error(py: Python, name: &str, mut cause: PyErr) -> PyErr {
+    if cause.matches(py, py.get_type::<ImportError>()) {
+        return cause;
+    }
+
+    let mut err =
+        PyErr::new::<ImportError, _>(py, format!("error initializing extension module {}", name));
+    let instance = err.instance(py);
+
+    match instance.setattr(py, "__cause__", cause.instance(py)) {
+        Ok(()) => PyErr::from_instance(py, instance),
+        Err(e) => e,
+    }
+}
+
+fn populate_module(py: Python, module: &PyModule) -> PyResult<()> {
+    let name = module.name(py)?.to_string();
+
+    // Clone the Arc so the lock isn't held while running arbitrary code,
+    // which may import other modules defined by the builder.
+    let definition = match MODULE_DEFINITIONS.lock() {
+        Ok(definitions) => definitions.get(&name).cloned(),
+        Err(_) => None,
+    };
+
+    let definition = match definition {
+        Some(v) => v,
+        None => {
+            return Err(PyErr::new::<cpython::exc::ImportError, _>(
+                py,
+                format!("no extension module definition for {}", name),
+            ));
+        }
+    };
+
+    if let Some(doc) = &definition.doc {
+        module.add(py, "__doc__", doc.as_str())?;
+    }
+
+    for init in &definition.initializers {
+        init(py, module).or_else(|e| Err(import_error(py, &name, e)))?;
+    }
+
+    Ok(())
+}
+
+/// Module initialization function shared by all builder-defined modules.
+///
+/// Returns the shared module definition. Python creates a module named
+/// after the import spec and calls `exec_module()` to populate it.
+#[allow(non_snake_case)]
+extern "C" fn PyInit_pyembed_extension() -> *mut pyffi::PyObject {
+    unsafe {
+        if MODULE_DEF.m_name.is_null() {
+            MODULE_SLOTS[0].slot = pyffi::Py_mod_exec;
+            MODULE_SLOTS[0].value = exec_module as *mut c_void;
+            MODULE_DEF.m_name = MODULE_DEF_NAME.as_ptr() as *const _;
+            MODULE_DEF.m_slots = MODULE_SLOTS.as_mut_ptr();
+        }
+
+        pyffi::PyModuleDef_Init(&mut MODULE_DEF)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_duplicate_name() {
+        assert!(ExtensionModuleBuilder::new("duplicate").build().is_ok());
+        assert_eq!(
+            ExtensionModuleBuilder::new("duplicate").build().err(),
+            Some("extension module with this name already defined")
+        );
+    }
+}
@@ -10,35 +10,50 @@ for importing Python modules from memory.
 */
 
 use std::cell::RefCell;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::env;
 use std::ffi::CStr;
+use std::fs;
 use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 
 use byteorder::{LittleEndian, ReadBytesExt};
-use cpython::exc::{FileNotFoundError, ImportError, RuntimeError, ValueError};
+use cpython::exc::{FileNotFoundError, ImportError, OSError, RuntimeError, ValueError};
 use cpython::{
-    py_class, py_class_impl, py_coerce_item, py_fn, NoArgs, ObjectProtocol, PyClone, PyDict, PyErr,
-    PyList, PyModule, PyObject, PyResult, PyString, PyTuple, Python, PythonObject, ToPyObject,
+    py_class, py_class_impl, py_coerce_item, py_fn, NoArgs, ObjectProtocol, PyBytes, PyClone,
+    PyDict, PyErr, PyList, PyModule, PyObject, PyResult, PyString, PyTuple, Python, PythonObject,
+    ToPyObject,
 };
 use python3_sys as pyffi;
 use python3_sys::{PyBUF_READ, PyMemoryView_FromMemory};
 
+use lazy_static::lazy_static;
+
+use super::config::PythonConfig;
+#[cfg(feature = "build-mode-pyoxidizer")]
+use super::data::default_python_config;
+use super::pyalloc::allocator_stats_py;
+#[cfg(feature = "build-mode-pyoxidizer")]
+use super::pyinterp::load_packed_data;
 use super::pyinterp::PYOXIDIZER_IMPORTER_NAME;
+use super::selftest::pattern_matches;
+use super::zipimport::{create_zip_importer, PyOxidizerZipImporter, ZipImporterFunctions};
 
 /// Obtain a Python memoryview referencing a memory slice.
 ///
 /// New memoryview allows Python to access the underlying memory without
 /// copying it.
 #[inline]
-fn get_memory_view(py: Python, data: &'static [u8]) -> Option<PyObject> {
+pub(crate) fn get_memory_view(py: Python, data: &'static [u8]) -> Option<PyObject> {
     let ptr = unsafe { PyMemoryView_FromMemory(data.as_ptr() as _, data.len() as _, PyBUF_READ) };
     unsafe { PyObject::from_owned_ptr_opt(py, ptr) }
 }
 
 /// Holds pointers to Python module data in memory.
 #[derive(Debug)]
-struct PythonModuleData {
+pub(crate) struct PythonModuleData {
     source: Option<&'static [u8]>,
     bytecode: Option<&'static [u8]>,
 }
@@ -61,20 +76,88 @@ impl PythonModuleData {
     }
 }
 
+/// Whether the import policy is being enforced.
+///
+/// Modules imported during interpreter initialization aren't subject to the
+/// policy.
+static IMPORT_POLICY_ENFORCED: AtomicBool = AtomicBool::new(false);
+
+/// Number of imports refused by the import policy.
+static DENIED_IMPORTS: AtomicUsize = AtomicUsize::new(0);
+
+/// Start or stop enforcing the import policy.
+pub(crate) fn set_import_policy_enforced(enforced: bool) {
+    IMPORT_POLICY_ENFORCED.store(enforced, Ordering::Relaxed);
+}
+
+/// Obtain the number of imports refused by the import policy.
+///
+/// See `PythonConfig.import_allow` and `PythonConfig.import_deny`.
+pub fn denied_import_count() -> usize {
+    DENIED_IMPORTS.load(Ordering::Relaxed)
+}
+
+/// Module name patterns controlling which modules may be imported.
+#[derive(Clone, Debug, Default)]
+pub struct ImportPolicy {
+    /// Patterns of modules that may be imported. Empty allows all modules.
+    pub allow: Vec<String>,
+
+    /// Patterns of modules that may not be imported.
+    pub deny: Vec<String>,
+}
+
+/// Whether a module name matches an import policy pattern.
+///
+/// Patterns also match submodules of packages they match.
+fn policy_pattern_matches(pattern: &str, name: &str) -> bool {
+    let mut search = name;
+
+    loop {
+        if pattern_matches(pattern, search) {
+            return true;
+        }
+
+        match search.rfind('.') {
+            Some(idx) => search = &search[0..idx],
+            None => return false,
+        }
+    }
+}
+
+impl ImportPolicy {
+    /// Whether a module may be imported.
+    fn allows(&self, name: &str) -> bool {
+        let allowed =
+            self.allow.is_empty() || self.allow.iter().any(|p| policy_pattern_matches(p, name));
+
+        allowed && !self.deny.iter().any(|p| policy_pattern_matches(p, name))
+    }
+}
+
 /// Represents Python modules data in memory.
 ///
 /// This is essentially an index over a raw backing blob.
-struct PythonModulesData {
+pub struct PythonModulesData {
     /// Packages in this set of modules.
-    packages: HashSet<&'static str>,
+    pub(crate) packages: HashSet<&'static str>,
 
     /// Maps module name to source/bytecode.
-    data: HashMap<&'static str, PythonModuleData>,
+    pub(crate) data: HashMap<&'static str, PythonModuleData>,
 }
 
 impl PythonModulesData {
     /// Construct a new instance from a memory slice.
-    fn from(data: &'static [u8]) -> Result<PythonModulesData, &'static str> {
+    ///
+    /// Empty data holds no modules.
+    pub fn from(data: &'static [u8]) -> Result<PythonModulesData, &'static str> {
+        if data.is_empty() {
+            return Ok(PythonModulesData {
+                packages: HashSet::new(),
+                data: HashMap::new(),
+            });
+        }
+
         let mut reader = Cursor::new(data);
 
         let count = reader
@@ -163,17 +246,48 @@ impl PythonModulesData {
             data: res,
         })
     }
+
+    /// Names of modules having source or bytecode data.
+    pub fn module_names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.data.keys().cloned()
+    }
+
+    /// Whether a module is a package.
+    pub fn is_package(&self, name: &str) -> bool {
+        self.packages.contains(name)
+    }
+
+    /// Obtain the source code of a module.
+    pub fn source(&self, name: &str) -> Option<&'static [u8]> {
+        self.data.get(name).and_then(|module| module.source)
+    }
+
+    /// Obtain the bytecode of a module.
+    ///
+    /// This is a marshalled code object without a ``.pyc`` header.
+    pub fn bytecode(&self, name: &str) -> Option<&'static [u8]> {
+        self.data.get(name).and_then(|module| module.bytecode)
+    }
 }
 
 /// Represents Python resources data in memory.
 ///
 /// This is essentially an index over a raw backing blob.
-struct PythonResourcesData {
-    packages: HashMap<&'static str, Arc<Box<HashMap<&'static str, &'static [u8]>>>>,
+pub struct PythonResourcesData {
+    pub(crate) packages: HashMap<&'static str, Arc<Box<HashMap<&'static str, &'static [u8]>>>>,
 }
 
 impl PythonResourcesData {
-    fn from(data: &'static [u8]) -> Result<PythonResourcesData, &'static str> {
+    /// Construct a new instance from a memory slice.
+    ///
+    /// Empty data holds no resources.
+    pub fn from(data: &'static [u8]) -> Result<PythonResourcesData, &'static str> {
+        if data.is_empty() {
+            return Ok(PythonResourcesData {
+                packages: HashMap::new(),
+            });
+        }
+
         let mut reader = Cursor::new(data);
 
         let package_count = reader
@@ -249,6 +363,64 @@ impl PythonResourcesData {
 
         Ok(PythonResourcesData { packages: res })
     }
+
+    /// Names of packages having resources.
+    pub fn package_names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.packages.keys().cloned()
+    }
+
+    /// Obtain the resources of a package, keyed by resource name.
+    pub fn package_resources(
+        &self,
+        package: &str,
+    ) -> Option<&HashMap<&'static str, &'static [u8]>> {
+        self.packages
+            .get(package)
+            .map(|resources| resources.as_ref().as_ref())
+    }
+
+    /// Obtain the data of a resource in a package.
+    pub fn resource(&self, package: &str, name: &str) -> Option<&'static [u8]> {
+        self.package_resources(package)
+            .and_then(|resources| resources.get(name))
+            .cloned()
+    }
+}
+
+/// Obtain the default config with packed data loaded from its directory.
+#[cfg(feature = "build-mode-pyoxidizer")]
+fn packed_data_config() -> Result<PythonConfig, &'static str> {
+    let mut config = default_python_config();
+    load_packed_data(&mut config)?;
+
+    Ok(config)
+}
+
+/// Without a default config, no data is embedded.
+#[cfg(not(feature = "build-mode-pyoxidizer"))]
+fn packed_data_config() -> Result<PythonConfig, &'static str> {
+    Err("pyembed was built without the build-mode-pyoxidizer feature")
+}
+
+lazy_static! {
+    static ref EMBEDDED_MODULES: Result<PythonModulesData, &'static str> =
+        packed_data_config().and_then(|config| PythonModulesData::from(config.py_modules_data));
+    static ref EMBEDDED_RESOURCES: Result<PythonResourcesData, &'static str> =
+        packed_data_config().and_then(|config| PythonResourcesData::from(config.py_resources_data));
+}
+
+/// Obtain the Python modules embedded in this binary.
+///
+/// Data is indexed on first use. The Python interpreter isn't needed.
+pub fn embedded_modules() -> Result<&'static PythonModulesData, &'static str> {
+    EMBEDDED_MODULES.as_ref().map_err(|e| *e)
+}
+
+/// Obtain the Python resources embedded in this binary.
+///
+/// Data is indexed on first use. The Python interpreter isn't needed.
+pub fn embedded_resources() -> Result<&'static PythonResourcesData, &'static str> {
+    EMBEDDED_RESOURCES.as_ref().map_err(|e| *e)
 }
 
 #[allow(unused_doc_comments)]
@@ -270,12 +442,27 @@ py_class!(class PyOxidizerFinder |py| {
     data known_modules: KnownModules;
     data resources: HashMap<&'static str, Arc<Box<HashMap<&'static str, &'static [u8]>>>>;
     data resource_readers: RefCell<Box<HashMap<String, PyObject>>>;
+    data import_policy: ImportPolicy;
+    data zip_importers: Vec<PyOxidizerZipImporter>;
 
     // Start of importlib.abc.MetaPathFinder interface.
 
     def find_spec(&self, fullname: &PyString, path: &PyObject, target: Option<PyObject> = None) -> PyResult<PyObject> {
         let key = fullname.to_string(py)?;
 
+        // Raising stops the search of sys.meta_path. So finders after us,
+        // like PathFinder, can't import denied modules either. Modules in
+        // sys.modules are returned without consulting finders. So modules
+        // imported during initialization aren't subject to the policy.
+        if IMPORT_POLICY_ENFORCED.load(Ordering::Relaxed) && !self.import_policy(py).allows(&key) {
+            DENIED_IMPORTS.fetch_add(1, Ordering::Relaxed);
+
+            return Err(PyErr::new::<ImportError, _>(
+                py,
+                format!("import of {} is denied by the import policy", key),
+            ));
+        }
+
         if let Some(flavor) = self.known_modules(py).get(&*key) {
             match flavor {
                 KnownModuleFlavor::Builtin => {
@@ -299,7 +486,31 @@ py_class!(class PyOxidizerFinder |py| {
                 }
             }
         } else {
-            Ok(py.None())
+            // Fall back to modules in embedded zip archives.
+            let mut spec = py.None();
+
+            for importer in self.zip_importers(py) {
+                spec = importer.find_spec(py, fullname, None, None)?;
+
+                if spec != py.None() {
+                    break;
+                }
+            }
+
+            // Our pkg_resources provider needs to be registered however
+            // pkg_resources is imported. So we find it on behalf of the
+            // finders after us.
+            if key == "pkg_resources" {
+                if spec == py.None() {
+                    spec = find_spec_after(py, self.as_object(), fullname, path, target)?;
+                }
+
+                if spec != py.None() {
+                    wrap_pkg_resources_loader(py, self, &spec)?;
+                }
+            }
+
+            Ok(spec)
         }
     }
 
@@ -340,7 +551,13 @@ py_class!(class PyOxidizerFinder |py| {
                             let exec_fn = self.exec_fn(py);
                             let dict = module.getattr(py, "__dict__")?;
 
-                            self.call_with_frames_removed(py).call(py, (exec_fn, code, dict), None)
+                            let res = self.call_with_frames_removed(py).call(py, (exec_fn, code, dict), None)?;
+
+                            if key == "pkg_resources" {
+                                register_pkg_resources_provider(py, self, module)?;
+                            }
+
+                            Ok(res)
                         },
                         None => {
                             Err(PyErr::new::<ImportError, _>(py, ("cannot find code in memory", name)))
@@ -453,6 +670,121 @@ py_class!(class PyOxidizerFinder |py| {
             Ok(py.None())
         }
     }
+
+    // Provider factory registered with pkg_resources.register_loader_type().
+    def pkg_resources_provider(&self, module: &PyObject) -> PyResult<PyObject> {
+        // Resources are relative to the package of a module.
+        let package = module.getattr(py, "__package__")?.extract::<String>(py)?;
+
+        let resources = match self.resources(py).get(&*package) {
+            Some(v) => v.clone(),
+            None => {
+                let h: Box<HashMap<&'static str, &'static [u8]>> = Box::new(HashMap::new());
+                Arc::new(h)
+            }
+        };
+
+        Ok(PyOxidizerResourceProvider::create_instance(py, package, resources)?.into_object())
+    }
+});
+
+/// Register a pkg_resources provider for modules imported by a finder.
+///
+/// pkg_resources looks up resource providers by the type of a module's
+/// loader. Without one, its resource APIs don't work for in-memory modules.
+fn register_pkg_resources_provider(
+    py: Python,
+    finder: &PyOxidizerFinder,
+    pkg_resources: &PyObject,
+) -> PyResult<()> {
+    let finder = finder.as_object();
+    let factory = finder.getattr(py, "pkg_resources_provider")?;
+
+    pkg_resources.call_method(
+        py,
+        "register_loader_type",
+        (finder.get_type(py), factory),
+        None,
+    )?;
+
+    Ok(())
+}
+
+/// Find a module spec using the sys.meta_path finders after a finder.
+fn find_spec_after(
+    py: Python,
+    finder: &PyObject,
+    fullname: &PyString,
+    path: &PyObject,
+    target: Option<PyObject>,
+) -> PyResult<PyObject> {
+    let meta_path = py.import("sys")?.get(py, "meta_path")?;
+    let mut after = false;
+
+    for entry in meta_path.iter(py)? {
+        let entry = entry?;
+
+        if !after {
+            after = entry == *finder;
+            continue;
+        }
+
+        if !entry.hasattr(py, "find_spec")? {
+            continue;
+        }
+
+        let spec = entry.call_method(py, "find_spec", (fullname, path, &target), None)?;
+
+        if spec != py.None() {
+            return Ok(spec);
+        }
+    }
+
+    Ok(py.None())
+}
+
+/// Wrap the loader of a pkg_resources module spec to register our provider.
+fn wrap_pkg_resources_loader(
+    py: Python,
+    finder: &PyOxidizerFinder,
+    spec: &PyObject,
+) -> PyResult<()> {
+    let loader = spec.getattr(py, "loader")?;
+
+    if loader == py.None() || !loader.hasattr(py, "exec_module")? {
+        return Ok(());
+    }
+
+    let wrapper = PkgResourcesLoader::create_instance(py, finder.clone_ref(py), loader)?;
+    spec.setattr(py, "loader", wrapper)?;
+
+    Ok(())
+}
+
+#[allow(unused_doc_comments)]
+/// Loader registering our pkg_resources provider once pkg_resources is executed.
+///
+/// Wraps the loader of a pkg_resources module found by another finder. The
+/// original loader is restored on the module before it is executed.
+py_class!(class PkgResourcesLoader |py| {
+    data finder: PyOxidizerFinder;
+    data loader: PyObject;
+
+    def create_module(&self, spec: &PyObject) -> PyResult<PyObject> {
+        self.loader(py).call_method(py, "create_module", (spec,), None)
+    }
+
+    def exec_module(&self, module: &PyObject) -> PyResult<PyObject> {
+        let loader = self.loader(py);
+
+        module.getattr(py, "__spec__")?.setattr(py, "loader", loader)?;
+        module.setattr(py, "__loader__", loader)?;
+
+        let res = loader.call_method(py, "exec_module", (module,), None)?;
+        register_pkg_resources_provider(py, self.finder(py), module)?;
+
+        Ok(res)
+    }
 });
 
 #[allow(unused_doc_comments)]
@@ -527,6 +859,182 @@ py_class!(class PyOxidizerResourceReader |py| {
     }
 });
 
+/// Create a ResourceReader for in-memory resources.
+pub(crate) fn create_resource_reader(
+    py: Python,
+    resources: Arc<Box<HashMap<&'static str, &'static [u8]>>>,
+) -> PyResult<PyObject> {
+    Ok(PyOxidizerResourceReader::create_instance(py, resources)?.into_object())
+}
+
+/// Obtain resources in a directory, keyed by name.
+///
+/// Resource names use `/` as a directory separator. An empty directory
+/// name refers to the package itself.
+fn directory_resources(
+    resources: &HashMap<&'static str, &'static [u8]>,
+    directory: &str,
+) -> Vec<(&'static str, &'static [u8])> {
+    let directory = directory.trim_end_matches('/');
+    let prefix = format!("{}/", directory);
+
+    resources
+        .iter()
+        .filter(|(name, _)| directory.is_empty() || name.starts_with(&prefix))
+        .map(|(name, data)| (*name, *data))
+        .collect()
+}
+
+/// Name of the directory in the pkg_resources cache holding extracted resources.
+fn pkg_resources_archive_name() -> String {
+    let name = env::current_exe()
+        .ok()
+        .and_then(|exe| exe.file_stem().map(|s| s.to_string_lossy().to_string()))
+        .unwrap_or_else(|| "python".to_string());
+
+    format!("{}-resources", name)
+}
+
+/// Write resource data to a file unless it already has that content.
+///
+/// Data is written to a temporary file which is renamed, so other processes
+/// never see a partially written file.
+fn write_resource_file(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    if let Ok(metadata) = fs::metadata(path) {
+        if metadata.len() == data.len() as u64 && fs::read(path)? == data {
+            return Ok(());
+        }
+    }
+
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(format!(".tmp-{}", uuid::Uuid::new_v4()));
+    let temp_path = PathBuf::from(temp_path);
+
+    fs::write(&temp_path, data)?;
+    fs::rename(&temp_path, path).or_else(|e| {
+        let _ = fs::remove_file(&temp_path);
+        Err(e)
+    })
+}
+
+#[allow(unused_doc_comments)]
+/// Implements pkg_resources' IResourceProvider for in-memory resources.
+///
+/// Resources needing a filesystem path are extracted to the cache directory
+/// of the pkg_resources.ResourceManager.
+py_class!(class PyOxidizerResourceProvider |py| {
+    data package: String;
+    data resources: Arc<Box<HashMap<&'static str, &'static [u8]>>>;
+
+    /// Does the package contain the named resource?
+    def has_resource(&self, resource_name: &PyString) -> PyResult<bool> {
+        let key = resource_name.to_string(py)?;
+
+        Ok(self.resources(py).contains_key(&*key)
+            || !directory_resources(self.resources(py), &key).is_empty())
+    }
+
+    /// Is the named resource a directory?
+    def resource_isdir(&self, resource_name: &PyString) -> PyResult<bool> {
+        let key = resource_name.to_string(py)?;
+
+        Ok(!self.resources(py).contains_key(&*key)
+            && !directory_resources(self.resources(py), &key).is_empty())
+    }
+
+    /// List the contents of the named resource directory.
+    def resource_listdir(&self, resource_name: &PyString) -> PyResult<PyObject> {
+        let key = resource_name.to_string(py)?;
+        let directory = key.trim_end_matches('/');
+
+        let names = directory_resources(self.resources(py), directory)
+            .iter()
+            .map(|(name, _)| {
+                let relative = if directory.is_empty() {
+                    name
+                } else {
+                    &name[directory.len() + 1..]
+                };
+
+                relative.split('/').next().unwrap_or_default().to_string()
+            })
+            .collect::<BTreeSet<String>>();
+
+        Ok(names.into_iter().collect::<Vec<_>>().to_py_object(py).into_object())
+    }
+
+    /// Return the named resource's contents as bytes.
+    def get_resource_string(&self, _manager: &PyObject, resource_name: &PyString) -> PyResult<PyBytes> {
+        let key = resource_name.to_string(py)?;
+
+        match self.resources(py).get(&*key) {
+            Some(data) => Ok(PyBytes::new(py, data)),
+            None => Err(PyErr::new::<FileNotFoundError, _>(py, "resource not found")),
+        }
+    }
+
+    /// Return a readable file-like object for the named resource.
+    def get_resource_stream(&self, _manager: &PyObject, resource_name: &PyString) -> PyResult<PyObject> {
+        let key = resource_name.to_string(py)?;
+
+        if let Some(data) = self.resources(py).get(&*key) {
+            match get_memory_view(py, data) {
+                Some(mv) => {
+                    let io_module = py.import("io")?;
+                    let bytes_io = io_module.get(py, "BytesIO")?;
+
+                    bytes_io.call(py, (mv,), None)
+                }
+                None => Err(PyErr::fetch(py))
+            }
+        } else {
+            Err(PyErr::new::<FileNotFoundError, _>(py, "resource not found"))
+        }
+    }
+
+    /// Return a true filesystem path for the named resource.
+    ///
+    /// Resources are extracted to the pkg_resources cache directory. For
+    /// directories, all resources in the directory are extracted.
+    def get_resource_filename(&self, manager: &PyObject, resource_name: &PyString) -> PyResult<PyObject> {
+        let key = resource_name.to_string(py)?;
+        let resources = self.resources(py);
+
+        let extract = match resources.get(&*key) {
+            Some(data) => vec![(&*key, *data)],
+            None => {
+                let entries = directory_resources(resources, &key);
+
+                if entries.is_empty() {
+                    return Err(PyErr::new::<FileNotFoundError, _>(py, "resource not found"));
+                }
+
+                entries
+            }
+        };
+
+        let archive_name = pkg_resources_archive_name();
+        let package_parts = self.package(py).split('.').collect::<Vec<_>>();
+
+        let cache_path = |name: &str| -> PyResult<PyObject> {
+            let mut names = package_parts.clone();
+            names.extend(name.split('/').filter(|part| !part.is_empty()));
+
+            manager.call_method(py, "get_cache_path", (&archive_name, names), None)
+        };
+
+        for (name, data) in extract {
+            let path = cache_path(name)?.extract::<String>(py)?;
+
+            write_resource_file(Path::new(&path), data).or_else(|e| {
+                Err(PyErr::new::<OSError, _>(py, format!("unable to extract resource to {}: {}", path, e)))
+            })?;
+        }
+
+        cache_path(&key)
+    }
+});
+
 const DOC: &[u8] = b"Binary representation of Python modules\0";
 
 /// Represents global module state to be passed at interpreter initialization time.
@@ -538,11 +1046,20 @@ pub struct InitModuleState {
     /// Values to set on sys.path.
     pub sys_paths: Vec<String>,
 
+    /// Which modules may be imported.
+    pub import_policy: ImportPolicy,
+
     /// Raw data constituting Python module source code.
     pub py_modules_data: &'static [u8],
 
     /// Raw data constituting Python resources data.
     pub py_resources_data: &'static [u8],
+
+    /// Raw data constituting zip archives.
+    pub zip_archives_data: &'static [u8],
+
+    /// Directory zip archives appear in on sys.path.
+    pub zip_archives_path: String,
 }
 
 /// Holds reference to next module state struct.
@@ -574,12 +1091,21 @@ struct ModuleState {
     /// Values to set on sys.path.
     sys_paths: Vec<String>,
 
+    /// Which modules may be imported.
+    import_policy: ImportPolicy,
+
     /// Raw data constituting Python module source code.
     py_modules_data: &'static [u8],
 
     /// Raw data constituting Python resources data.
     py_resources_data: &'static [u8],
 
+    /// Raw data constituting zip archives.
+    zip_archives_data: &'static [u8],
+
+    /// Directory zip archives appear in on sys.path.
+    zip_archives_path: String,
+
     /// Whether setup() has been called.
     setup_called: bool,
 }
@@ -619,12 +1145,17 @@ fn module_init(py: Python, m: &PyModule) -> PyResult<()> {
         state.register_filesystem_importer = (*NEXT_MODULE_STATE).register_filesystem_importer;
         // TODO we could move the value if we wanted to avoid the clone().
         state.sys_paths = (*NEXT_MODULE_STATE).sys_paths.clone();
+        state.import_policy = (*NEXT_MODULE_STATE).import_policy.clone();
         state.py_modules_data = (*NEXT_MODULE_STATE).py_modules_data;
         state.py_resources_data = (*NEXT_MODULE_STATE).py_resources_data;
+        state.zip_archives_data = (*NEXT_MODULE_STATE).zip_archives_data;
+        state.zip_archives_path = (*NEXT_MODULE_STATE).zip_archives_path.clone();
     }
 
     state.setup_called = false;
 
+    m.add(py, "allocator_stats", py_fn!(py, allocator_stats_py()))?;
+
     m.add(
         py,
         "_setup",
@@ -788,6 +1319,51 @@ fn module_setup(
     let resource_readers: RefCell<Box<HashMap<String, PyObject>>> =
         RefCell::new(Box::new(HashMap::new()));
 
+    // Zip archives data uses the resources data format, with archive names
+    // in place of package names. Empty data means there are no archives.
+    let zip_archives = match PythonResourcesData::from(state.zip_archives_data) {
+        Ok(v) => v.packages,
+        Err(msg) => return Err(PyErr::new::<ValueError, _>(py, msg)),
+    };
+
+    let frozen_importlib_external = py.import("_frozen_importlib_external")?;
+
+    let zip_importer_functions = ZipImporterFunctions {
+        marshal_loads: marshal_loads.clone_ref(py),
+        magic_number: frozen_importlib_external
+            .get(py, "MAGIC_NUMBER")?
+            .extract::<Vec<u8>>(py)?,
+        compile_fn: match builtins_module.get_item(py, "compile") {
+            Some(v) => v,
+            None => {
+                return Err(PyErr::new::<ValueError, _>(
+                    py,
+                    "could not obtain __builtins__.compile",
+                ));
+            }
+        },
+        exec_fn: exec_fn.clone_ref(py),
+        call_with_frames_removed: call_with_frames_removed.clone_ref(py),
+        decode_source: decode_source.clone_ref(py),
+        spec_from_file_location: frozen_importlib_external.get(py, "spec_from_file_location")?,
+        load_module_shim: bootstrap_module.get(py, "_load_module_shim")?,
+    };
+
+    // Archives are sorted by name so sys.path order is deterministic.
+    let mut zip_archives = zip_archives.into_iter().collect::<Vec<_>>();
+    zip_archives.sort_by_key(|(name, _)| *name);
+
+    let mut zip_importers = Vec::with_capacity(zip_archives.len());
+    for (name, files) in zip_archives {
+        let archive = Path::new(&state.zip_archives_path)
+            .join(name)
+            .display()
+            .to_string();
+
+        let importer = create_zip_importer(py, archive.clone(), files, &zip_importer_functions)?;
+        zip_importers.push((archive, importer));
+    }
+
     let unified_importer = PyOxidizerFinder::create_instance(
         py,
         imp_module,
@@ -802,6 +1378,11 @@ fn module_setup(
         known_modules,
         resources_data.packages,
         resource_readers,
+        state.import_policy.clone(),
+        zip_importers
+            .iter()
+            .map(|(_, importer)| importer.clone_ref(py))
+            .collect(),
     )?;
     meta_path_object.call_method(py, "clear", NoArgs, None)?;
     meta_path_object.call_method(py, "append", (unified_importer,), None)?;
@@ -819,8 +1400,6 @@ fn module_setup(
         // supported_loaders = _get_supported_file_loaders()
         // sys.path_hooks.extend([FileFinder.path_hook(*supported_loaders)])
         // sys.meta_path.append(PathFinder)
-        let frozen_importlib_external = py.import("_frozen_importlib_external")?;
-
         let loaders =
             frozen_importlib_external.call(py, "_get_supported_file_loaders", NoArgs, None)?;
         let loaders_list = loaders.cast_as::<PyList>(py)?;
@@ -853,6 +1432,18 @@ fn module_setup(
         sys_path.call_method(py, "append", (py_path,), None)?;
     }
 
+    // Embedded zip archives are importable like zip files on sys.path.
+    // Registering their importers for those entries lets tools like pkgutil
+    // find them.
+    let path_importer_cache = sys_module.get(py, "path_importer_cache")?;
+
+    for (archive, importer) in zip_importers {
+        let archive = PyString::new(py, &archive);
+
+        sys_path.call_method(py, "append", (&archive,), None)?;
+        path_importer_cache.set_item(py, archive, importer)?;
+    }
+
     Ok(py.None())
 }
 
@@ -909,3 +1500,144 @@ pub extern "C" fn PyInit__pyoxidizer_importer() -> *mut pyffi::PyObject {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use byteorder::WriteBytesExt;
+
+    #[test]
+    fn test_modules_data() {
+        let mut data = Vec::new();
+        data.write_u32::<LittleEndian>(2).unwrap();
+        for (name_length, source_length, bytecode_length, flags) in &[(3, 5, 0, 1), (7, 0, 2, 0)] {
+            data.write_u32::<LittleEndian>(*name_length).unwrap();
+            data.write_u32::<LittleEndian>(*source_length).unwrap();
+            data.write_u32::<LittleEndian>(*bytecode_length).unwrap();
+            data.write_u32::<LittleEndian>(*flags).unwrap();
+        }
+        data.extend_from_slice(b"foofoo.barx = 1bc");
+
+        let data: &'static [u8] = Box::leak(data.into_boxed_slice());
+        let modules = PythonModulesData::from(data).unwrap();
+
+        let mut names = modules.module_names().collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, vec!["foo", "foo.bar"]);
+        assert!(modules.is_package("foo"));
+        assert!(!modules.is_package("foo.bar"));
+        assert_eq!(modules.source("foo"), Some(&b"x = 1"[..]));
+        assert_eq!(modules.bytecode("foo"), None);
+        assert_eq!(modules.source("foo.bar"), None);
+        assert_eq!(modules.bytecode("foo.bar"), Some(&b"bc"[..]));
+        assert_eq!(modules.source("missing"), None);
+
+        let modules = PythonModulesData::from(&[]).unwrap();
+        assert_eq!(modules.module_names().count(), 0);
+    }
+
+    #[test]
+    fn test_directory_resources() {
+        let mut resources: HashMap<&'static str, &'static [u8]> = HashMap::new();
+        resources.insert("data.txt", b"hello");
+        resources.insert("templates/index.html", b"<html/>");
+        resources.insert("templates/sub/page.html", b"<p/>");
+        resources.insert("templatesx.txt", b"x");
+
+        let names = |directory: &str| {
+            let mut names = directory_resources(&resources, directory)
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+
+        assert_eq!(names("").len(), 4);
+        assert_eq!(
+            names("templates"),
+            vec!["templates/index.html", "templates/sub/page.html"]
+        );
+        assert_eq!(names("templates/"), names("templates"));
+        assert_eq!(names("templates/sub"), vec!["templates/sub/page.html"]);
+        assert!(names("temp").is_empty());
+        assert!(names("data.txt").is_empty());
+        assert!(names("missing").is_empty());
+
+        let entries = directory_resources(&resources, "templates/sub");
+        assert_eq!(entries, vec![("templates/sub/page.html", &b"<p/>"[..])]);
+    }
+
+    #[test]
+    fn test_policy_pattern_matches() {
+        assert!(policy_pattern_matches("json", "json"));
+        assert!(policy_pattern_matches("json", "json.decoder"));
+        assert!(policy_pattern_matches("xml", "xml.etree.ElementTree"));
+        assert!(policy_pattern_matches("xml.etree", "xml.etree.ElementTree"));
+        assert!(policy_pattern_matches("email.*", "email.mime.text"));
+        assert!(policy_pattern_matches("*", "anything"));
+        assert!(!policy_pattern_matches("json", "jsonschema"));
+        assert!(!policy_pattern_matches("json.decoder", "json"));
+        assert!(!policy_pattern_matches("xml.etree", "xml.dom"));
+    }
+
+    #[test]
+    fn test_import_policy_allows() {
+        let policy = ImportPolicy::default();
+        assert!(policy.allows("json"));
+        assert!(policy.allows("anything.at.all"));
+
+        let policy = ImportPolicy {
+            allow: vec!["myapp".to_string(), "json".to_string()],
+            deny: vec!["myapp.internal".to_string(), "json".to_string()],
+        };
+        assert!(policy.allows("myapp"));
+        assert!(policy.allows("myapp.views"));
+        assert!(!policy.allows("myapp.internal"));
+        assert!(!policy.allows("myapp.internal.secrets"));
+        assert!(!policy.allows("json"));
+        assert!(!policy.allows("json.decoder"));
+        assert!(!policy.allows("socket"));
+
+        let policy = ImportPolicy {
+            allow: Vec::new(),
+            deny: vec!["ctypes".to_string()],
+        };
+        assert!(policy.allows("socket"));
+        assert!(!policy.allows("ctypes"));
+        assert!(!policy.allows("ctypes.util"));
+    }
+
+    #[test]
+    fn test_resources_data() {
+        let mut data = Vec::new();
+        data.write_u32::<LittleEndian>(1).unwrap();
+        for value in &[3, 2, 3, 5, 4, 3] {
+            data.write_u32::<LittleEndian>(*value).unwrap();
+        }
+        data.extend_from_slice(b"foobardatahelloabc");
+
+        let data: &'static [u8] = Box::leak(data.into_boxed_slice());
+        let resources = PythonResourcesData::from(data).unwrap();
+
+        assert_eq!(resources.package_names().collect::<Vec<_>>(), vec!["foo"]);
+        assert_eq!(resources.package_resources("foo").unwrap().len(), 2);
+        assert_eq!(resources.resource("foo", "bar"), Some(&b"hello"[..]));
+        assert_eq!(resources.resource("foo", "data"), Some(&b"abc"[..]));
+        assert_eq!(resources.resource("foo", "missing"), None);
+        assert!(resources.package_resources("missing").is_none());
+
+        let resources = PythonResourcesData::from(&[]).unwrap();
+        assert_eq!(resources.package_names().count(), 0);
+    }
+
+    #[test]
+    #[cfg(feature = "build-mode-pyoxidizer")]
+    fn test_embedded_data() {
+        let modules = embedded_modules().unwrap();
+        assert!(modules.is_package("encodings"));
+        assert!(modules.source("encodings").is_some());
+
+        assert!(embedded_resources().is_ok());
+    }
+}
@@ -0,0 +1,451 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Minimal JSON support.
+//!
+//! Config overlays, crash reports, and self-test results are the only JSON
+//! we deal with. So rather than depending on a general purpose JSON crate,
+//! we implement the subset we need. Numbers are limited to integers.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Integer(i64),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(BTreeMap<String, JsonValue>),
+}
+
+impl JsonValue {
+    /// Construct an object from key-value pairs.
+    pub fn object(items: Vec<(&str, JsonValue)>) -> JsonValue {
+        JsonValue::Object(items.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            JsonValue::Bool(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            JsonValue::Integer(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<JsonValue>> {
+        match self {
+            JsonValue::Array(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&BTreeMap<String, JsonValue>> {
+        match self {
+            JsonValue::Object(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Obtain the value of a key in an object.
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        self.as_object().and_then(|o| o.get(key))
+    }
+
+    /// Serialize with each array element and object member on its own line.
+    pub fn to_string_pretty(&self) -> String {
+        let mut s = String::new();
+        self.write(&mut s, Some("  "), 0);
+        s
+    }
+
+    fn write(&self, s: &mut String, indent: Option<&str>, depth: usize) {
+        let newline = |s: &mut String, depth: usize| {
+            if let Some(indent) = indent {
+                s.push('\n');
+                for _ in 0..depth {
+                    s.push_str(indent);
+                }
+            }
+        };
+
+        match self {
+            JsonValue::Null => s.push_str("null"),
+            JsonValue::Bool(v) => s.push_str(if *v { "true" } else { "false" }),
+            JsonValue::Integer(v) => s.push_str(&v.to_string()),
+            JsonValue::String(v) => write_string(s, v),
+            JsonValue::Array(values) if values.is_empty() => s.push_str("[]"),
+            JsonValue::Array(values) => {
+                s.push('[');
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        s.push(',');
+                    }
+                    newline(s, depth + 1);
+                    value.write(s, indent, depth + 1);
+                }
+                newline(s, depth);
+                s.push(']');
+            }
+            JsonValue::Object(items) if items.is_empty() => s.push_str("{}"),
+            JsonValue::Object(items) => {
+                s.push('{');
+                for (i, (key, value)) in items.iter().enumerate() {
+                    if i > 0 {
+                        s.push(',');
+                    }
+                    newline(s, depth + 1);
+                    write_string(s, key);
+                    s.push(':');
+                    if indent.is_some() {
+                        s.push(' ');
+                    }
+                    value.write(s, indent, depth + 1);
+                }
+                newline(s, depth);
+                s.push('}');
+            }
+        }
+    }
+}
+
+/// Serializes to a single line.
+impl fmt::Display for JsonValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut s = String::new();
+        self.write(&mut s, None, 0);
+        f.write_str(&s)
+    }
+}
+
+impl From<bool> for JsonValue {
+    fn from(v: bool) -> Self {
+        JsonValue::Bool(v)
+    }
+}
+
+impl From<usize> for JsonValue {
+    fn from(v: usize) -> Self {
+        JsonValue::Integer(v as i64)
+    }
+}
+
+impl From<&str> for JsonValue {
+    fn from(v: &str) -> Self {
+        JsonValue::String(v.to_string())
+    }
+}
+
+impl From<String> for JsonValue {
+    fn from(v: String) -> Self {
+        JsonValue::String(v)
+    }
+}
+
+impl<T: Into<JsonValue>> From<Option<T>> for JsonValue {
+    fn from(v: Option<T>) -> Self {
+        match v {
+            Some(v) => v.into(),
+            None => JsonValue::Null,
+        }
+    }
+}
+
+impl<T: Into<JsonValue>> From<Vec<T>> for JsonValue {
+    fn from(v: Vec<T>) -> Self {
+        JsonValue::Array(v.into_iter().map(|x| x.into()).collect())
+    }
+}
+
+fn write_string(s: &mut String, value: &str) {
+    s.push('"');
+
+    for c in value.chars() {
+        match c {
+            '"' => s.push_str("\\\""),
+            '\\' => s.push_str("\\\\"),
+            '\n' => s.push_str("\\n"),
+            '\r' => s.push_str("\\r"),
+            '\t' => s.push_str("\\t"),
+            c if (c as u32) < 0x20 => s.push_str(&format!("\\u{:04x}", c as u32)),
+            c => s.push(c),
+        }
+    }
+
+    s.push('"');
+}
+
+/// Parse a JSON document.
+pub fn parse(data: &[u8]) -> Result<JsonValue, &'static str> {
+    let data = std::str::from_utf8(data).or_else(|_| Err("JSON is not valid UTF-8"))?;
+    let mut chars = data.chars().peekable();
+
+    let value = parse_value(&mut chars)?;
+
+    skip_whitespace(&mut chars);
+    if chars.peek().is_some() {
+        return Err("trailing data after JSON value");
+    }
+
+    Ok(value)
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while let Some(' ') | Some('\t') | Some('\n') | Some('\r') = chars.peek() {
+        chars.next();
+    }
+}
+
+fn expect_literal(
+    chars: &mut Peekable<Chars>,
+    literal: &str,
+    value: JsonValue,
+) -> Result<JsonValue, &'static str> {
+    for expected in literal.chars() {
+        if chars.next() != Some(expected) {
+            return Err("invalid JSON literal");
+        }
+    }
+
+    Ok(value)
+}
+
+fn parse_value(chars: &mut Peekable<Chars>) -> Result<JsonValue, &'static str> {
+    skip_whitespace(chars);
+
+    match chars.peek() {
+        Some('n') => expect_literal(chars, "null", JsonValue::Null),
+        Some('t') => expect_literal(chars, "true", JsonValue::Bool(true)),
+        Some('f') => expect_literal(chars, "false", JsonValue::Bool(false)),
+        Some('"') => Ok(JsonValue::String(parse_string(chars)?)),
+        Some('[') => parse_array(chars),
+        Some('{') => parse_object(chars),
+        Some('-') | Some('0'..='9') => parse_integer(chars),
+        Some(_) => Err("unexpected character in JSON"),
+        None => Err("unexpected end of JSON"),
+    }
+}
+
+fn parse_integer(chars: &mut Peekable<Chars>) -> Result<JsonValue, &'static str> {
+    let mut s = String::new();
+
+    if chars.peek() == Some(&'-') {
+        s.push('-');
+        chars.next();
+    }
+
+    while let Some(c @ '0'..='9') = chars.peek() {
+        s.push(*c);
+        chars.next();
+    }
+
+    if let Some('.') | Some('e') | Some('E') = chars.peek() {
+        return Err("only integer JSON numbers are supported");
+    }
+
+    s.parse::<i64>()
+        .map(JsonValue::Integer)
+        .or_else(|_| Err("invalid JSON integer"))
+}
+
+fn parse_hex_escape(chars: &mut Peekable<Chars>) -> Result<u32, &'static str> {
+    let mut value = 0;
+
+    for _ in 0..4 {
+        let digit = chars
+            .next()
+            .and_then(|c| c.to_digit(16))
+            .ok_or_else(|| "invalid JSON unicode escape")?;
+        value = value * 16 + digit;
+    }
+
+    Ok(value)
+}
+
+fn parse_string(chars: &mut Peekable<Chars>) -> Result<String, &'static str> {
+    // Opening quote.
+    chars.next();
+
+    let mut s = String::new();
+
+    loop {
+        match chars.next().ok_or_else(|| "unterminated JSON string")? {
+            '"' => return Ok(s),
+            '\\' => match chars.next().ok_or_else(|| "unterminated JSON string")? {
+                '"' => s.push('"'),
+                '\\' => s.push('\\'),
+                '/' => s.push('/'),
+                'b' => s.push('\u{8}'),
+                'f' => s.push('\u{c}'),
+                'n' => s.push('\n'),
+                'r' => s.push('\r'),
+                't' => s.push('\t'),
+                'u' => {
+                    let mut code = parse_hex_escape(chars)?;
+
+                    // Characters outside the BMP are escaped as surrogate pairs.
+                    if (0xd800..0xdc00).contains(&code) {
+                        if chars.next() != Some('\\') || chars.next() != Some('u') {
+                            return Err("invalid JSON unicode escape");
+                        }
+                        let low = parse_hex_escape(chars)?;
+                        if !(0xdc00..0xe000).contains(&low) {
+                            return Err("invalid JSON unicode escape");
+                        }
+                        code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+                    }
+
+                    s.push(std::char::from_u32(code).ok_or_else(|| "invalid JSON unicode escape")?);
+                }
+                _ => return Err("invalid JSON string escape"),
+            },
+            c if (c as u32) < 0x20 => return Err("control character in JSON string"),
+            c => s.push(c),
+        }
+    }
+}
+
+fn parse_array(chars: &mut Peekable<Chars>) -> Result<JsonValue, &'static str> {
+    // Opening bracket.
+    chars.next();
+
+    let mut values = Vec::new();
+
+    skip_whitespace(chars);
+    if chars.peek() == Some(&']') {
+        chars.next();
+        return Ok(JsonValue::Array(values));
+    }
+
+    loop {
+        values.push(parse_value(chars)?);
+
+        skip_whitespace(chars);
+        match chars.next() {
+            Some(',') => continue,
+            Some(']') => return Ok(JsonValue::Array(values)),
+            _ => return Err("expected , or ] in JSON array"),
+        }
+    }
+}
+
+fn parse_object(chars: &mut Peekable<Chars>) -> Result<JsonValue, &'static str> {
+    // Opening brace.
+    chars.next();
+
+    let mut items = BTreeMap::new();
+
+    skip_whitespace(chars);
+    if chars.peek() == Some(&'}') {
+        chars.next();
+        return Ok(JsonValue::Object(items));
+    }
+
+    loop {
+        skip_whitespace(chars);
+        if chars.peek() != Some(&'"') {
+            return Err("expected string key in JSON object");
+        }
+        let key = parse_string(chars)?;
+
+        skip_whitespace(chars);
+        if chars.next() != Some(':') {
+            return Err("expected : in JSON object");
+        }
+
+        items.insert(key, parse_value(chars)?);
+
+        skip_whitespace(chars);
+        match chars.next() {
+            Some(',') => continue,
+            Some('}') => return Ok(JsonValue::Object(items)),
+            _ => return Err("expected , or } in JSON object"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            parse(br#" {"a": [1, -2, true, null], "b": {"c": "d\n\u00e9\ud83d\ude00"}} "#),
+            Ok(JsonValue::object(vec![
+                (
+                    "a",
+                    JsonValue::Array(vec![
+                        JsonValue::Integer(1),
+                        JsonValue::Integer(-2),
+                        JsonValue::Bool(true),
+                        JsonValue::Null,
+                    ])
+                ),
+                (
+                    "b",
+                    JsonValue::object(vec![("c", "d\n\u{e9}\u{1f600}".into())])
+                ),
+            ]))
+        );
+        assert_eq!(parse(b"[]"), Ok(JsonValue::Array(vec![])));
+        assert_eq!(parse(b"{}"), Ok(JsonValue::object(vec![])));
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(parse(b"").is_err());
+        assert!(parse(b"{").is_err());
+        assert!(parse(b"[1,]").is_err());
+        assert!(parse(b"{\"a\" 1}").is_err());
+        assert!(parse(b"{a: 1}").is_err());
+        assert!(parse(b"tru").is_err());
+        assert!(parse(b"\"abc").is_err());
+        assert!(parse(b"\"\\x\"").is_err());
+        assert!(parse(b"1 2").is_err());
+        assert_eq!(
+            parse(b"1.5"),
+            Err("only integer JSON numbers are supported")
+        );
+        assert_eq!(parse(b"99999999999999999999"), Err("invalid JSON integer"));
+    }
+
+    #[test]
+    fn test_to_string() {
+        let value = JsonValue::object(vec![
+            ("module", "foo".into()),
+            ("error", Some("a \"b\"\n\u{1}").into()),
+            ("count", 3usize.into()),
+            ("names", vec!["x", "y"].into()),
+            ("empty", Vec::<String>::new().into()),
+        ]);
+
+        assert_eq!(
+            value.to_string(),
+            r#"{"count":3,"empty":[],"error":"a \"b\"\n\u0001","module":"foo","names":["x","y"]}"#
+        );
+        assert_eq!(
+            value.to_string_pretty(),
+            "{\n  \"count\": 3,\n  \"empty\": [],\n  \"error\": \"a \\\"b\\\"\\n\\u0001\",\n  \
+             \"module\": \"foo\",\n  \"names\": [\n    \"x\",\n    \"y\"\n  ]\n}"
+        );
+        assert_eq!(parse(value.to_string().as_bytes()), Ok(value));
+    }
+}
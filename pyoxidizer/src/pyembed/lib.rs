@@ -15,21 +15,59 @@ The most important types are [`PythonConfig`](struct.PythonConfig.html) and
 defines how a Python interpreter is to behave. A `MainPythonInterpreter`
 creates and manages that interpreter and serves as a high-level interface for
 running code in the interpreter.
+
+By default, the crate's build script runs PyOxidizer, which produces the
+default `PythonConfig` returned by
+[`default_python_config()`](fn.default_python_config.html) and links a
+Python library. This is controlled by the default `build-mode-pyoxidizer`
+feature. Without it, the crate is an ordinary library linking the Python
+discovered by `python3-sys` and configs are defined via
+[`PythonConfigBuilder`](struct.PythonConfigBuilder.html).
 */
 
 mod config;
+mod coroutine;
+mod crash;
+#[cfg(feature = "build-mode-pyoxidizer")]
 mod data;
+mod extension;
 mod importer;
+mod json;
 mod osutils;
+mod overlay;
 mod pyalloc;
 mod pyinterp;
+mod pylogging;
 mod pystr;
+mod selftest;
+mod zipimport;
 
 #[allow(unused_imports)]
-pub use crate::config::PythonConfig;
+pub use crate::config::{
+    ExtensionModule, PythonConfig, PythonConfigBuilder, PythonRawAllocator, PythonRunMode,
+    TerminfoResolution,
+};
 
 #[allow(unused_imports)]
+pub use crate::coroutine::{future_into_py, AwaitableFuture, EventLoop};
+
+#[cfg(feature = "build-mode-pyoxidizer")]
 pub use crate::data::default_python_config;
 
 #[allow(unused_imports)]
-pub use crate::pyinterp::MainPythonInterpreter;
+pub use crate::extension::{ExtensionModuleBuilder, ModuleInitializer};
+
+#[allow(unused_imports)]
+pub use crate::importer::{
+    denied_import_count, embedded_modules, embedded_resources, PythonModulesData,
+    PythonResourcesData,
+};
+
+#[allow(unused_imports)]
+pub use crate::pyalloc::{allocator_stats, AllocatorStats, DomainStats};
+
+#[allow(unused_imports)]
+pub use crate::pyinterp::{raise_keyboard_interrupt, InterpreterHandle, MainPythonInterpreter};
+
+#[allow(unused_imports)]
+pub use crate::pylogging::set_python_log_level;
@@ -2,7 +2,12 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+use byteorder::{LittleEndian, ReadBytesExt};
 use lazy_static::lazy_static;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io::Cursor;
 use std::path::{Path, PathBuf};
 
 /// terminfo directories for Debian based distributions.
@@ -145,3 +150,251 @@ pub fn resolve_terminfo_dirs() -> Option<String> {
         OsVariant::Other => None,
     }
 }
+
+/// Names of the directories a terminfo entry may be stored in.
+///
+/// Most systems use the entry's first character. macOS uses its hex value.
+fn terminfo_entry_subdirs(name: &str) -> Vec<String> {
+    match name.chars().next() {
+        Some(c) => vec![c.to_string(), format!("{:x}", c as u32)],
+        None => Vec::new(),
+    }
+}
+
+/// Parse embedded terminfo entries data.
+///
+/// See the `pyembed` documentation for the data format.
+fn parse_terminfo_data(
+    data: &'static [u8],
+) -> Result<BTreeMap<&'static str, &'static [u8]>, &'static str> {
+    let mut reader = Cursor::new(data);
+
+    let count = reader
+        .read_u32::<LittleEndian>()
+        .or_else(|_| Err("failed reading terminfo count"))?;
+
+    let mut index = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let name_length = reader
+            .read_u32::<LittleEndian>()
+            .or_else(|_| Err("failed reading terminfo name length"))?
+            as usize;
+        let data_length = reader
+            .read_u32::<LittleEndian>()
+            .or_else(|_| Err("failed reading terminfo data length"))?
+            as usize;
+
+        index.push((name_length, data_length));
+    }
+
+    let mut offset = reader.position() as usize;
+    let mut names = Vec::with_capacity(index.len());
+    for (name_length, _) in &index {
+        let name = data
+            .get(offset..offset + name_length)
+            .ok_or_else(|| "terminfo data is truncated")?;
+        names.push(std::str::from_utf8(name).or_else(|_| Err("terminfo name is not UTF-8"))?);
+        offset += name_length;
+    }
+
+    let mut res = BTreeMap::new();
+    for (name, (_, data_length)) in names.into_iter().zip(index) {
+        let value = data
+            .get(offset..offset + data_length)
+            .ok_or_else(|| "terminfo data is truncated")?;
+        res.insert(name, value);
+        offset += data_length;
+    }
+
+    Ok(res)
+}
+
+/// Install embedded terminfo entries if the system lacks the current terminal's.
+///
+/// If the `TERM` environment variable refers to an embedded entry that can't
+/// be found in the terminfo directories ncurses searches, all embedded entries
+/// are extracted to a new directory which is appended to `TERMINFO_DIRS`.
+///
+/// Returns the path to the directory entries were extracted to, if any. The
+/// caller is responsible for removing it.
+pub fn install_embedded_terminfo(data: &'static [u8]) -> Result<Option<PathBuf>, &'static str> {
+    // Windows doesn't use the terminfo database.
+    if data.is_empty() || cfg!(windows) {
+        return Ok(None);
+    }
+
+    let term = match env::var("TERM") {
+        Ok(term) if !term.is_empty() => term,
+        _ => return Ok(None),
+    };
+
+    let entries = parse_terminfo_data(data)?;
+
+    if !entries.contains_key(term.as_str()) {
+        return Ok(None);
+    }
+
+    let mut search_dirs = Vec::new();
+    if let Some(path) = env::var_os("TERMINFO") {
+        search_dirs.push(PathBuf::from(path));
+    }
+    if let Some(home) = env::var_os("HOME") {
+        search_dirs.push(PathBuf::from(home).join(".terminfo"));
+    }
+    if let Ok(dirs) = env::var("TERMINFO_DIRS") {
+        search_dirs.extend(dirs.split(':').filter(|d| !d.is_empty()).map(PathBuf::from));
+    }
+    search_dirs.extend(TERMINFO_DIRS_COMMON.iter().cloned());
+
+    let subdirs = terminfo_entry_subdirs(&term);
+    if search_dirs.iter().any(|dir| {
+        subdirs
+            .iter()
+            .any(|subdir| dir.join(subdir).join(&term).is_file())
+    }) {
+        return Ok(None);
+    }
+
+    let dir = env::temp_dir().join(format!("pyembed-terminfo-{}", uuid::Uuid::new_v4()));
+
+    for (name, value) in entries {
+        for subdir in terminfo_entry_subdirs(name) {
+            let subdir = dir.join(subdir);
+            fs::create_dir_all(&subdir).or_else(|_| Err("unable to create terminfo directory"))?;
+            fs::write(subdir.join(name), value)
+                .or_else(|_| Err("unable to write terminfo entry"))?;
+        }
+    }
+
+    let value = match env::var("TERMINFO_DIRS") {
+        Ok(dirs) if !dirs.is_empty() => format!("{}:{}", dirs, dir.display()),
+        _ => dir.display().to_string(),
+    };
+    env::set_var("TERMINFO_DIRS", value);
+
+    Ok(Some(dir))
+}
+
+/// Install embedded CA certificates as OpenSSL's default certificate file.
+///
+/// The certificates are written to a new file and `SSL_CERT_FILE` is pointed
+/// at it. Nothing is done if `SSL_CERT_FILE` or `SSL_CERT_DIR` is defined, as
+/// those express the user's choice of certificates.
+///
+/// Returns the path to the written file, if any. The caller is responsible
+/// for removing it and unsetting `SSL_CERT_FILE`.
+pub fn install_ssl_ca_bundle(data: &'static [u8]) -> Result<Option<PathBuf>, &'static str> {
+    if data.is_empty()
+        || env::var_os("SSL_CERT_FILE").is_some()
+        || env::var_os("SSL_CERT_DIR").is_some()
+    {
+        return Ok(None);
+    }
+
+    let path = env::temp_dir().join(format!("pyembed-cacert-{}.pem", uuid::Uuid::new_v4()));
+
+    fs::write(&path, data).or_else(|_| Err("unable to write CA certificates"))?;
+    env::set_var("SSL_CERT_FILE", &path);
+
+    Ok(Some(path))
+}
+
+/// Resolve a signal name (e.g. `SIGINT`) to its number on the current platform.
+pub fn resolve_signal(name: &str) -> Option<libc::c_int> {
+    let signal = match name {
+        "SIGABRT" => libc::SIGABRT,
+        "SIGFPE" => libc::SIGFPE,
+        "SIGILL" => libc::SIGILL,
+        "SIGINT" => libc::SIGINT,
+        "SIGSEGV" => libc::SIGSEGV,
+        "SIGTERM" => libc::SIGTERM,
+        _ => return resolve_posix_signal(name),
+    };
+
+    Some(signal)
+}
+
+#[cfg(unix)]
+fn resolve_posix_signal(name: &str) -> Option<libc::c_int> {
+    let signal = match name {
+        "SIGALRM" => libc::SIGALRM,
+        "SIGCHLD" => libc::SIGCHLD,
+        "SIGHUP" => libc::SIGHUP,
+        "SIGPIPE" => libc::SIGPIPE,
+        "SIGQUIT" => libc::SIGQUIT,
+        "SIGTSTP" => libc::SIGTSTP,
+        "SIGUSR1" => libc::SIGUSR1,
+        "SIGUSR2" => libc::SIGUSR2,
+        "SIGWINCH" => libc::SIGWINCH,
+        "SIGXFSZ" => libc::SIGXFSZ,
+        _ => return None,
+    };
+
+    Some(signal)
+}
+
+#[cfg(windows)]
+fn resolve_posix_signal(_name: &str) -> Option<libc::c_int> {
+    None
+}
+
+/// Locales the legacy C locale is coerced to, in order of preference.
+#[cfg(all(unix, not(Py_3_8)))]
+const C_LOCALE_COERCION_TARGETS: &[&str] = &["C.UTF-8", "C.utf8", "UTF-8"];
+
+/// Name of the current `LC_CTYPE` locale after applying the environment.
+#[cfg(all(unix, not(Py_3_8)))]
+fn ctype_locale_from_env() -> Option<String> {
+    let res = unsafe { libc::setlocale(libc::LC_CTYPE, b"\0".as_ptr() as *const libc::c_char) };
+
+    if res.is_null() {
+        None
+    } else {
+        Some(
+            unsafe { std::ffi::CStr::from_ptr(res) }
+                .to_string_lossy()
+                .to_string(),
+        )
+    }
+}
+
+/// Coerce the legacy C locale to a UTF-8 based locale.
+///
+/// This mirrors what the `python` executable does per PEP 538. `LC_CTYPE`
+/// is set in the environment so child processes inherit the coerced locale.
+///
+/// Returns the locale that was coerced to, if any.
+///
+/// Python 3.8 coerces the locale during pre-initialization.
+#[cfg(all(unix, not(Py_3_8)))]
+pub fn coerce_c_locale() -> Option<&'static str> {
+    match ctype_locale_from_env() {
+        Some(ref name) if name == "C" || name == "POSIX" => {}
+        _ => return None,
+    }
+
+    let original = env::var_os("LC_CTYPE");
+
+    for target in C_LOCALE_COERCION_TARGETS {
+        env::set_var("LC_CTYPE", target);
+
+        // LC_ALL takes precedence over LC_CTYPE, so verify the locale changed.
+        match ctype_locale_from_env() {
+            Some(ref name) if name != "C" && name != "POSIX" => return Some(target),
+            _ => {}
+        }
+    }
+
+    match original {
+        Some(value) => env::set_var("LC_CTYPE", value),
+        None => env::remove_var("LC_CTYPE"),
+    }
+    ctype_locale_from_env();
+
+    None
+}
+
+#[cfg(all(windows, not(Py_3_8)))]
+pub fn coerce_c_locale() -> Option<&'static str> {
+    None
+}
@@ -0,0 +1,281 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Apply run-time overrides to a `PythonConfig`.
+//!
+//! Overlay files are JSON documents whose keys are names of `PythonConfig`
+//! fields. Only fields listed in `PythonConfig.config_overlay_fields` may be
+//! overridden.
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use super::config::{PythonConfig, PythonRunMode};
+use super::json::{parse, JsonValue};
+
+/// Resolve the path of the overlay file to load, if any.
+///
+/// The environment variable named by `config_overlay_env` takes precedence
+/// over `config_overlay_path`. A file named by the environment variable must
+/// exist. A file named by `config_overlay_path` is optional.
+fn resolve_overlay_path(config: &PythonConfig) -> Result<Option<PathBuf>, &'static str> {
+    if let Some(key) = &config.config_overlay_env {
+        if let Some(path) = env::var_os(key) {
+            return Ok(Some(PathBuf::from(path)));
+        }
+    }
+
+    if let Some(path) = &config.config_overlay_path {
+        let exe = env::current_exe().or_else(|_| Err("could not obtain current exe"))?;
+        let origin = exe
+            .parent()
+            .ok_or_else(|| "unable to get exe parent")?
+            .display()
+            .to_string();
+
+        let path = PathBuf::from(path.replace("$ORIGIN", &origin));
+
+        if path.exists() {
+            return Ok(Some(path));
+        }
+    }
+
+    Ok(None)
+}
+
+fn bool_value(value: &JsonValue) -> Result<bool, &'static str> {
+    value
+        .as_bool()
+        .ok_or_else(|| "config overlay value must be a boolean")
+}
+
+fn i32_value(value: &JsonValue) -> Result<i32, &'static str> {
+    let value = value
+        .as_i64()
+        .ok_or_else(|| "config overlay value must be an integer")?;
+
+    i32::try_from(value).or_else(|_| Err("config overlay integer value out of range"))
+}
+
+fn optional_u32_value(value: &JsonValue) -> Result<Option<u32>, &'static str> {
+    match value {
+        JsonValue::Null => Ok(None),
+        JsonValue::Integer(value) => {
+            Ok(Some(u32::try_from(*value).or_else(|_| {
+                Err("config overlay integer value out of range")
+            })?))
+        }
+        _ => Err("config overlay value must be an integer or null"),
+    }
+}
+
+fn optional_string_value(value: &JsonValue) -> Result<Option<String>, &'static str> {
+    match value {
+        JsonValue::Null => Ok(None),
+        JsonValue::String(s) => Ok(Some(s.clone())),
+        _ => Err("config overlay value must be a string or null"),
+    }
+}
+
+fn string_list_value(value: &JsonValue) -> Result<Vec<String>, &'static str> {
+    value
+        .as_array()
+        .ok_or_else(|| "config overlay value must be an array of strings")?
+        .iter()
+        .map(|v| {
+            v.as_str()
+                .map(|s| s.to_string())
+                .ok_or_else(|| "config overlay value must be an array of strings")
+        })
+        .collect()
+}
+
+fn run_mode_value(value: &JsonValue) -> Result<PythonRunMode, &'static str> {
+    let mode = value
+        .get("mode")
+        .and_then(JsonValue::as_str)
+        .ok_or_else(|| "config overlay run value must define a mode")?;
+
+    match mode {
+        "none" => Ok(PythonRunMode::None),
+        "repl" => Ok(PythonRunMode::Repl),
+        "module" => Ok(PythonRunMode::Module {
+            module: value
+                .get("module")
+                .and_then(JsonValue::as_str)
+                .ok_or_else(|| "config overlay module run mode must define a module")?
+                .to_string(),
+        }),
+        "eval" => Ok(PythonRunMode::Eval {
+            code: value
+                .get("code")
+                .and_then(JsonValue::as_str)
+                .ok_or_else(|| "config overlay eval run mode must define code")?
+                .to_string(),
+        }),
+        _ => Err("config overlay run mode must be none, repl, module, or eval"),
+    }
+}
+
+/// Merge parsed overlay values into a config.
+fn merge_overlay(
+    config: &mut PythonConfig,
+    overlay: &BTreeMap<String, JsonValue>,
+) -> Result<(), &'static str> {
+    for (key, value) in overlay {
+        if !config.config_overlay_fields.iter().any(|f| f == key) {
+            return Err("config overlay defines a field not allowed to be overridden");
+        }
+
+        match key.as_str() {
+            "standard_io_encoding" => config.standard_io_encoding = optional_string_value(value)?,
+            "standard_io_errors" => config.standard_io_errors = optional_string_value(value)?,
+            "opt_level" => config.opt_level = i32_value(value)?,
+            "filesystem_importer" => config.filesystem_importer = bool_value(value)?,
+            "sys_paths" => config.sys_paths = string_list_value(value)?,
+            "bytes_warning" => config.bytes_warning = i32_value(value)?,
+            "import_site" => config.import_site = bool_value(value)?,
+            "import_user_site" => config.import_user_site = bool_value(value)?,
+            "ignore_python_env" => config.ignore_python_env = bool_value(value)?,
+            "inspect" => config.inspect = bool_value(value)?,
+            "interactive" => config.interactive = bool_value(value)?,
+            "isolated" => config.isolated = bool_value(value)?,
+            "dont_write_bytecode" => config.dont_write_bytecode = bool_value(value)?,
+            "unbuffered_stdio" => config.unbuffered_stdio = bool_value(value)?,
+            "parser_debug" => config.parser_debug = bool_value(value)?,
+            "quiet" => config.quiet = bool_value(value)?,
+            "use_hash_seed" => config.use_hash_seed = bool_value(value)?,
+            "verbose" => config.verbose = i32_value(value)?,
+            "utf8_mode" => config.utf8_mode = bool_value(value)?,
+            "coerce_c_locale" => config.coerce_c_locale = bool_value(value)?,
+            "warnoptions" => config.warnoptions = string_list_value(value)?,
+            "xoptions" => config.xoptions = string_list_value(value)?,
+            "dev_mode" => config.dev_mode = bool_value(value)?,
+            "hash_seed" => config.hash_seed = optional_u32_value(value)?,
+            "sys_frozen" => config.sys_frozen = bool_value(value)?,
+            "sys_meipass" => config.sys_meipass = bool_value(value)?,
+            "install_signal_handlers" => config.install_signal_handlers = bool_value(value)?,
+            "ignore_signals" => config.ignore_signals = string_list_value(value)?,
+            "forward_signals" => config.forward_signals = string_list_value(value)?,
+            "write_modules_directory_env" => {
+                config.write_modules_directory_env = optional_string_value(value)?
+            }
+            "faulthandler" => config.faulthandler = bool_value(value)?,
+            "faulthandler_path" => config.faulthandler_path = optional_string_value(value)?,
+            "logging_bridge" => config.logging_bridge = bool_value(value)?,
+            "crash_report_directory" => {
+                config.crash_report_directory = optional_string_value(value)?
+            }
+            "run" => config.run = run_mode_value(value)?,
+            _ => return Err("config overlay defines a field that cannot be overridden"),
+        }
+    }
+
+    Ok(())
+}
+
+/// Apply a config overlay file, if one is present, to a config.
+///
+/// This is a no-op if no fields are allowed to be overridden.
+pub fn apply_config_overlay(config: &mut PythonConfig) -> Result<(), &'static str> {
+    if config.config_overlay_fields.is_empty() {
+        return Ok(());
+    }
+
+    let path = match resolve_overlay_path(config)? {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+
+    let data = fs::read(&path).or_else(|_| Err("unable to read config overlay file"))?;
+    let overlay = parse(&data).or_else(|_| Err("unable to parse config overlay file"))?;
+
+    let overlay = overlay
+        .as_object()
+        .ok_or_else(|| "config overlay file must contain a JSON object")?;
+
+    merge_overlay(config, overlay)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::config::PythonConfigBuilder;
+    use super::*;
+
+    fn config_with_fields(fields: &[&str]) -> PythonConfig {
+        let mut config = PythonConfigBuilder::new().build().unwrap();
+        config.config_overlay_fields = fields.iter().map(|f| f.to_string()).collect();
+
+        config
+    }
+
+    fn overlay(data: &str) -> BTreeMap<String, JsonValue> {
+        parse(data.as_bytes()).unwrap().as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn test_merge_overlay() {
+        let mut config = config_with_fields(&["verbose", "sys_paths", "hash_seed", "run"]);
+
+        merge_overlay(
+            &mut config,
+            &overlay(
+                r#"{"verbose": 2, "sys_paths": ["$ORIGIN/lib"], "hash_seed": 42,
+                    "run": {"mode": "module", "module": "app"}}"#,
+            ),
+        )
+        .unwrap();
+
+        assert_eq!(config.verbose, 2);
+        assert_eq!(config.sys_paths, ["$ORIGIN/lib"]);
+        assert_eq!(config.hash_seed, Some(42));
+        match config.run {
+            PythonRunMode::Module { ref module } => assert_eq!(module, "app"),
+            _ => panic!("expected module run mode"),
+        }
+
+        merge_overlay(&mut config, &overlay(r#"{"hash_seed": null}"#)).unwrap();
+        assert_eq!(config.hash_seed, None);
+    }
+
+    #[test]
+    fn test_merge_overlay_disallowed_field() {
+        let mut config = config_with_fields(&["verbose"]);
+
+        assert_eq!(
+            merge_overlay(&mut config, &overlay(r#"{"quiet": true}"#)),
+            Err("config overlay defines a field not allowed to be overridden")
+        );
+    }
+
+    #[test]
+    fn test_merge_overlay_invalid_values() {
+        let mut config = config_with_fields(&["verbose", "hash_seed", "quiet", "run"]);
+
+        assert_eq!(
+            merge_overlay(&mut config, &overlay(r#"{"verbose": 4294967297}"#)),
+            Err("config overlay integer value out of range")
+        );
+        assert_eq!(config.verbose, 0);
+        assert_eq!(
+            merge_overlay(&mut config, &overlay(r#"{"hash_seed": -1}"#)),
+            Err("config overlay integer value out of range")
+        );
+        assert_eq!(
+            merge_overlay(&mut config, &overlay(r#"{"hash_seed": 4294967296}"#)),
+            Err("config overlay integer value out of range")
+        );
+        assert_eq!(
+            merge_overlay(&mut config, &overlay(r#"{"quiet": 1}"#)),
+            Err("config overlay value must be a boolean")
+        );
+        assert_eq!(
+            merge_overlay(&mut config, &overlay(r#"{"run": {"mode": "module"}}"#)),
+            Err("config overlay module run mode must define a module")
+        );
+    }
+}
@@ -12,6 +12,9 @@ use std::alloc;
 use std::collections::HashMap;
 #[cfg(feature = "jemalloc-sys")]
 use std::ptr::null_mut;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use cpython::{PyDict, PyObject, PyResult, Python, PythonObject};
 
 const MIN_ALIGN: usize = 16;
 
@@ -219,3 +222,395 @@ pub fn make_raw_jemalloc_allocator() -> pyffi::PyMemAllocatorEx {
         free: Some(raw_jemalloc_free),
     }
 }
+
+// An allocator calling directly into the C runtime's allocator. Python's
+// default raw allocator already does this. This allows the mem and object
+// domains to bypass pymalloc.
+
+extern "C" fn system_malloc(_ctx: *mut c_void, size: size_t) -> *mut c_void {
+    let size = match size {
+        0 => 1,
+        val => val,
+    };
+
+    unsafe { libc::malloc(size) }
+}
+
+extern "C" fn system_calloc(_ctx: *mut c_void, nelem: size_t, elsize: size_t) -> *mut c_void {
+    let (nelem, elsize) = match nelem * elsize {
+        0 => (1, 1),
+        _ => (nelem, elsize),
+    };
+
+    unsafe { libc::calloc(nelem, elsize) }
+}
+
+extern "C" fn system_realloc(_ctx: *mut c_void, ptr: *mut c_void, new_size: size_t) -> *mut c_void {
+    let new_size = match new_size {
+        0 => 1,
+        val => val,
+    };
+
+    unsafe { libc::realloc(ptr, new_size) }
+}
+
+extern "C" fn system_free(_ctx: *mut c_void, ptr: *mut c_void) {
+    unsafe { libc::free(ptr) }
+}
+
+pub fn make_system_allocator() -> pyffi::PyMemAllocatorEx {
+    pyffi::PyMemAllocatorEx {
+        ctx: std::ptr::null_mut(),
+        malloc: Some(system_malloc),
+        calloc: Some(system_calloc),
+        realloc: Some(system_realloc),
+        free: Some(system_free),
+    }
+}
+
+/// A custom memory allocator registered for a Python memory domain.
+pub enum DomainAllocator {
+    /// An allocator without state.
+    Static(pyffi::PyMemAllocatorEx),
+    /// An allocator backed by Rust's global allocator.
+    Rust(RawAllocator),
+}
+
+impl DomainAllocator {
+    /// Obtain the allocator to pass to `PyMem_SetAllocator()`.
+    pub fn allocator(&self) -> &pyffi::PyMemAllocatorEx {
+        match self {
+            DomainAllocator::Static(allocator) => allocator,
+            DomainAllocator::Rust(allocator) => &allocator.allocator,
+        }
+    }
+}
+
+/// Statistics about allocations in a single memory domain.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DomainStats {
+    /// Number of bytes currently allocated.
+    pub allocated_bytes: usize,
+    /// Number of allocations that haven't been freed.
+    pub live_allocations: usize,
+    /// Number of allocations made since accounting began.
+    pub total_allocations: usize,
+}
+
+/// Statistics about allocations in all Python memory domains.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AllocatorStats {
+    /// The `PYMEM_DOMAIN_RAW` domain.
+    pub raw: DomainStats,
+    /// The `PYMEM_DOMAIN_MEM` domain.
+    pub mem: DomainStats,
+    /// The `PYMEM_DOMAIN_OBJ` domain.
+    pub obj: DomainStats,
+}
+
+struct DomainCounters {
+    allocated_bytes: AtomicUsize,
+    live_allocations: AtomicUsize,
+    total_allocations: AtomicUsize,
+}
+
+impl DomainCounters {
+    const fn new() -> DomainCounters {
+        DomainCounters {
+            allocated_bytes: AtomicUsize::new(0),
+            live_allocations: AtomicUsize::new(0),
+            total_allocations: AtomicUsize::new(0),
+        }
+    }
+
+    fn record_alloc(&self, size: usize) {
+        self.allocated_bytes.fetch_add(size, Ordering::Relaxed);
+        self.live_allocations.fetch_add(1, Ordering::Relaxed);
+        self.total_allocations.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn record_free(&self, size: usize) {
+        self.allocated_bytes.fetch_sub(size, Ordering::Relaxed);
+        self.live_allocations.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    fn stats(&self) -> DomainStats {
+        DomainStats {
+            allocated_bytes: self.allocated_bytes.load(Ordering::Relaxed),
+            live_allocations: self.live_allocations.load(Ordering::Relaxed),
+            total_allocations: self.total_allocations.load(Ordering::Relaxed),
+        }
+    }
+}
+
+static STATS_ENABLED: AtomicBool = AtomicBool::new(false);
+static RAW_COUNTERS: DomainCounters = DomainCounters::new();
+static MEM_COUNTERS: DomainCounters = DomainCounters::new();
+static OBJ_COUNTERS: DomainCounters = DomainCounters::new();
+
+/// Size of the header prepended to tracked allocations to record their size.
+///
+/// 16 bytes preserves the alignment of the wrapped allocator.
+const TRACKING_HEADER_SIZE: usize = 16;
+
+struct TrackingState {
+    inner: pyffi::PyMemAllocatorEx,
+    counters: &'static DomainCounters,
+}
+
+/// An allocator recording statistics before calling into another allocator.
+///
+/// Each allocation is prefixed with a header holding its size so that frees
+/// can be accounted for regardless of the wrapped allocator.
+pub struct TrackingAllocator {
+    pub allocator: pyffi::PyMemAllocatorEx,
+    _state: Box<TrackingState>,
+}
+
+unsafe fn tracking_finish(state: &TrackingState, ptr: *mut c_void, size: usize) -> *mut c_void {
+    if ptr.is_null() {
+        return ptr;
+    }
+
+    *(ptr as *mut usize) = size;
+    state.counters.record_alloc(size);
+
+    (ptr as *mut u8).add(TRACKING_HEADER_SIZE) as *mut c_void
+}
+
+extern "C" fn tracking_malloc(ctx: *mut c_void, size: size_t) -> *mut c_void {
+    let tracked_size = match size.checked_add(TRACKING_HEADER_SIZE) {
+        Some(size) => size,
+        None => return std::ptr::null_mut(),
+    };
+
+    unsafe {
+        let state = &*(ctx as *const TrackingState);
+        let res = state.inner.malloc.unwrap()(state.inner.ctx, tracked_size);
+
+        tracking_finish(state, res, size)
+    }
+}
+
+extern "C" fn tracking_calloc(ctx: *mut c_void, nelem: size_t, elsize: size_t) -> *mut c_void {
+    let size = match nelem.checked_mul(elsize) {
+        Some(size) => size,
+        None => return std::ptr::null_mut(),
+    };
+    let tracked_size = match size.checked_add(TRACKING_HEADER_SIZE) {
+        Some(size) => size,
+        None => return std::ptr::null_mut(),
+    };
+
+    unsafe {
+        let state = &*(ctx as *const TrackingState);
+        let res = state.inner.calloc.unwrap()(state.inner.ctx, 1, tracked_size);
+
+        tracking_finish(state, res, size)
+    }
+}
+
+extern "C" fn tracking_realloc(
+    ctx: *mut c_void,
+    ptr: *mut c_void,
+    new_size: size_t,
+) -> *mut c_void {
+    if ptr.is_null() {
+        return tracking_malloc(ctx, new_size);
+    }
+
+    // The original allocation is left untouched on failure.
+    let tracked_size = match new_size.checked_add(TRACKING_HEADER_SIZE) {
+        Some(size) => size,
+        None => return std::ptr::null_mut(),
+    };
+
+    unsafe {
+        let state = &*(ctx as *const TrackingState);
+        let base = (ptr as *mut u8).sub(TRACKING_HEADER_SIZE) as *mut c_void;
+        let old_size = *(base as *const usize);
+
+        let res = state.inner.realloc.unwrap()(state.inner.ctx, base, tracked_size);
+
+        // The original allocation is still valid on failure.
+        if res.is_null() {
+            return res;
+        }
+
+        state.counters.record_free(old_size);
+        tracking_finish(state, res, new_size)
+    }
+}
+
+extern "C" fn tracking_free(ctx: *mut c_void, ptr: *mut c_void) {
+    if ptr.is_null() {
+        return;
+    }
+
+    unsafe {
+        let state = &*(ctx as *const TrackingState);
+        let base = (ptr as *mut u8).sub(TRACKING_HEADER_SIZE) as *mut c_void;
+        let size = *(base as *const usize);
+
+        state.counters.record_free(size);
+        state.inner.free.unwrap()(state.inner.ctx, base);
+    }
+}
+
+/// Wrap the allocator currently registered for a domain with accounting.
+///
+/// Must be called before any memory is allocated in the domain.
+pub fn make_tracking_allocator(domain: pyffi::PyMemAllocatorDomain) -> TrackingAllocator {
+    let mut inner = make_system_allocator();
+
+    let counters = match domain {
+        pyffi::PyMemAllocatorDomain::PYMEM_DOMAIN_RAW => &RAW_COUNTERS,
+        pyffi::PyMemAllocatorDomain::PYMEM_DOMAIN_MEM => &MEM_COUNTERS,
+        pyffi::PyMemAllocatorDomain::PYMEM_DOMAIN_OBJ => &OBJ_COUNTERS,
+    };
+
+    unsafe {
+        pyffi::PyMem_GetAllocator(domain, &mut inner);
+    }
+
+    wrap_tracking_allocator(inner, counters)
+}
+
+fn wrap_tracking_allocator(
+    inner: pyffi::PyMemAllocatorEx,
+    counters: &'static DomainCounters,
+) -> TrackingAllocator {
+    let state = Box::new(TrackingState { inner, counters });
+
+    let allocator = pyffi::PyMemAllocatorEx {
+        ctx: &*state as *const TrackingState as *mut c_void,
+        malloc: Some(tracking_malloc),
+        calloc: Some(tracking_calloc),
+        realloc: Some(tracking_realloc),
+        free: Some(tracking_free),
+    };
+
+    STATS_ENABLED.store(true, Ordering::Relaxed);
+
+    TrackingAllocator {
+        allocator,
+        _state: state,
+    }
+}
+
+/// Obtain statistics about Python memory allocations.
+///
+/// Returns `None` unless the interpreter was configured with
+/// `PythonConfig.allocator_stats`.
+pub fn allocator_stats() -> Option<AllocatorStats> {
+    if !STATS_ENABLED.load(Ordering::Relaxed) {
+        return None;
+    }
+
+    Some(AllocatorStats {
+        raw: RAW_COUNTERS.stats(),
+        mem: MEM_COUNTERS.stats(),
+        obj: OBJ_COUNTERS.stats(),
+    })
+}
+
+fn domain_stats_to_dict(py: Python, stats: &DomainStats) -> PyResult<PyDict> {
+    let d = PyDict::new(py);
+    d.set_item(py, "allocated_bytes", stats.allocated_bytes)?;
+    d.set_item(py, "live_allocations", stats.live_allocations)?;
+    d.set_item(py, "total_allocations", stats.total_allocations)?;
+
+    Ok(d)
+}
+
+/// Python function returning allocator statistics as a dict.
+///
+/// Returns `None` if allocator accounting is not enabled.
+pub fn allocator_stats_py(py: Python) -> PyResult<PyObject> {
+    let stats = match allocator_stats() {
+        Some(stats) => stats,
+        None => return Ok(py.None()),
+    };
+
+    let d = PyDict::new(py);
+    d.set_item(py, "raw", domain_stats_to_dict(py, &stats.raw)?)?;
+    d.set_item(py, "mem", domain_stats_to_dict(py, &stats.mem)?)?;
+    d.set_item(py, "obj", domain_stats_to_dict(py, &stats.obj)?)?;
+
+    Ok(d.into_object())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracking_allocator() {
+        let tracking = wrap_tracking_allocator(make_system_allocator(), &RAW_COUNTERS);
+        let allocator = &tracking.allocator;
+        let before = allocator_stats().unwrap().raw;
+
+        unsafe {
+            let ptr = allocator.malloc.unwrap()(allocator.ctx, 100);
+            assert!(!ptr.is_null());
+
+            let stats = allocator_stats().unwrap().raw;
+            assert_eq!(stats.allocated_bytes, before.allocated_bytes + 100);
+            assert_eq!(stats.live_allocations, before.live_allocations + 1);
+            assert_eq!(stats.total_allocations, before.total_allocations + 1);
+
+            let ptr = allocator.realloc.unwrap()(allocator.ctx, ptr, 300);
+            assert!(!ptr.is_null());
+
+            let stats = allocator_stats().unwrap().raw;
+            assert_eq!(stats.allocated_bytes, before.allocated_bytes + 300);
+            assert_eq!(stats.live_allocations, before.live_allocations + 1);
+            assert_eq!(stats.total_allocations, before.total_allocations + 2);
+
+            let zeroed = allocator.calloc.unwrap()(allocator.ctx, 4, 8);
+            assert!(!zeroed.is_null());
+            assert_eq!(
+                std::slice::from_raw_parts(zeroed as *const u8, 32),
+                &[0; 32][..]
+            );
+
+            let stats = allocator_stats().unwrap().raw;
+            assert_eq!(stats.allocated_bytes, before.allocated_bytes + 332);
+            assert_eq!(stats.live_allocations, before.live_allocations + 2);
+
+            allocator.free.unwrap()(allocator.ctx, ptr);
+            allocator.free.unwrap()(allocator.ctx, zeroed);
+
+            let stats = allocator_stats().unwrap().raw;
+            assert_eq!(stats.allocated_bytes, before.allocated_bytes);
+            assert_eq!(stats.live_allocations, before.live_allocations);
+            assert_eq!(stats.total_allocations, before.total_allocations + 3);
+        }
+    }
+
+    #[test]
+    fn test_tracking_allocator_overflow() {
+        let tracking = wrap_tracking_allocator(make_system_allocator(), &OBJ_COUNTERS);
+        let allocator = &tracking.allocator;
+        let before = allocator_stats().unwrap().obj;
+
+        unsafe {
+            let huge = std::usize::MAX - 1;
+
+            assert!(allocator.malloc.unwrap()(allocator.ctx, huge).is_null());
+            assert!(allocator.calloc.unwrap()(allocator.ctx, 1, huge).is_null());
+            assert!(allocator.calloc.unwrap()(allocator.ctx, 2, huge).is_null());
+
+            let ptr = allocator.malloc.unwrap()(allocator.ctx, 10);
+            assert!(!ptr.is_null());
+            assert!(allocator.realloc.unwrap()(allocator.ctx, ptr, huge).is_null());
+
+            // The original allocation survives a failed realloc.
+            let stats = allocator_stats().unwrap().obj;
+            assert_eq!(stats.allocated_bytes, before.allocated_bytes + 10);
+            assert_eq!(stats.total_allocations, before.total_allocations + 1);
+
+            allocator.free.unwrap()(allocator.ctx, ptr);
+        }
+    }
+}
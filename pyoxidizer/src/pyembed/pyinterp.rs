@@ -4,15 +4,17 @@
 
 //! Manage an embedded Python interpreter.
 
+use lazy_static::lazy_static;
 use libc::c_char;
 use python3_sys as pyffi;
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, HashMap};
 use std::env;
 use std::ffi::CString;
 use std::fs;
 use std::io::Write;
 use std::path::PathBuf;
 use std::ptr::null;
+use std::sync::{Arc, Mutex, RwLock};
 
 use cpython::exc::{SystemExit, ValueError};
 use cpython::{
@@ -21,12 +23,22 @@ use cpython::{
 };
 
 use super::config::{PythonConfig, PythonRawAllocator, PythonRunMode, TerminfoResolution};
-use super::importer::PyInit__pyoxidizer_importer;
-use super::osutils::resolve_terminfo_dirs;
+use super::crash::{enable_faulthandler, write_crash_report};
+use super::importer::{set_import_policy_enforced, ImportPolicy, PyInit__pyoxidizer_importer};
+#[cfg(not(Py_3_8))]
+use super::osutils::coerce_c_locale;
+use super::osutils::{
+    install_embedded_terminfo, install_ssl_ca_bundle, resolve_signal, resolve_terminfo_dirs,
+};
+use super::overlay::apply_config_overlay;
 #[cfg(feature = "jemalloc-sys")]
 use super::pyalloc::make_raw_jemalloc_allocator;
-use super::pyalloc::{make_raw_rust_memory_allocator, RawAllocator};
+use super::pyalloc::{
+    make_raw_rust_memory_allocator, make_system_allocator, make_tracking_allocator, DomainAllocator,
+};
+use super::pylogging::install_logging_bridge;
 use super::pystr::{osstring_to_bytes, osstring_to_str, OwnedPyStr};
+use super::selftest::run_self_test;
 
 pub const PYOXIDIZER_IMPORTER_NAME: &[u8] = b"_pyoxidizer_importer\0";
 
@@ -115,10 +127,127 @@ fn raw_jemallocator() -> pyffi::PyMemAllocatorEx {
     panic!("jemalloc is not available in this build configuration");
 }
 
-#[cfg(unix)]
+fn make_allocator(allocator: &PythonRawAllocator) -> DomainAllocator {
+    match allocator {
+        PythonRawAllocator::Jemalloc => DomainAllocator::Static(raw_jemallocator()),
+        PythonRawAllocator::Rust => DomainAllocator::Rust(make_raw_rust_memory_allocator()),
+        PythonRawAllocator::System => DomainAllocator::Static(make_system_allocator()),
+    }
+}
+
+const ALLOCATOR_DOMAINS: [pyffi::PyMemAllocatorDomain; 3] = [
+    pyffi::PyMemAllocatorDomain::PYMEM_DOMAIN_RAW,
+    pyffi::PyMemAllocatorDomain::PYMEM_DOMAIN_MEM,
+    pyffi::PyMemAllocatorDomain::PYMEM_DOMAIN_OBJ,
+];
+
+/// Memory allocator settings registered with Python.
+#[derive(Clone, Debug, PartialEq)]
+struct AllocatorSettings {
+    raw: PythonRawAllocator,
+    mem: Option<PythonRawAllocator>,
+    obj: Option<PythonRawAllocator>,
+    stats: bool,
+    debug_hooks: bool,
+}
+
+lazy_static! {
+    static ref REGISTERED_ALLOCATORS: Mutex<Option<AllocatorSettings>> = Mutex::new(None);
+}
+
+/// Packed data read from a packed data directory.
+#[derive(Clone, Copy)]
+struct PackedData {
+    py_modules: &'static [u8],
+    py_resources: &'static [u8],
+    zip_archives: &'static [u8],
+}
+
+lazy_static! {
+    /// Packed data loaded at run-time, keyed by directory.
+    ///
+    /// Data is leaked so it lives as long as the process, like data embedded
+    /// in the binary. So each directory is only read once.
+    static ref PACKED_DATA: Mutex<HashMap<PathBuf, PackedData>> = Mutex::new(HashMap::new());
+}
+
+/// Register memory allocators with Python.
+///
+/// Python holds on to some memory after finalization and frees it when an
+/// interpreter is initialized again. So allocators are registered once per
+/// process and are never freed. Later interpreters must use the same settings.
+fn register_allocators(settings: AllocatorSettings) -> Result<(), &'static str> {
+    let mut registered = REGISTERED_ALLOCATORS
+        .lock()
+        .or_else(|_| Err("unable to obtain allocator state"))?;
+
+    if let Some(existing) = registered.as_ref() {
+        return if *existing == settings {
+            Ok(())
+        } else {
+            Err("memory allocators cannot be changed after the first interpreter initialization")
+        };
+    }
+
+    // The system allocator is Python's default for the raw domain. So we
+    // don't need to register anything. No allocator for the mem and object
+    // domains means to keep using pymalloc.
+    let raw_allocator = match settings.raw {
+        PythonRawAllocator::System => None,
+        ref allocator => Some(make_allocator(allocator)),
+    };
+    let allocators = vec![
+        raw_allocator,
+        settings.mem.as_ref().map(make_allocator),
+        settings.obj.as_ref().map(make_allocator),
+    ];
+
+    for (domain, allocator) in ALLOCATOR_DOMAINS.iter().zip(allocators.iter()) {
+        if let Some(allocator) = allocator {
+            unsafe {
+                let ptr = allocator.allocator() as *const _;
+                pyffi::PyMem_SetAllocator(*domain, ptr as *mut _);
+            }
+        }
+    }
+
+    // Development mode detects common memory errors, as with
+    // PYTHONMALLOC=debug. The hooks wrap the allocators registered above.
+    if settings.debug_hooks {
+        unsafe {
+            pyffi::PyMem_SetupDebugHooks();
+        }
+    }
+
+    // Accounting wraps whatever allocator is registered for each domain.
+    // So it must be installed after custom allocators.
+    let mut tracking_allocators = Vec::new();
+
+    if settings.stats {
+        for domain in ALLOCATOR_DOMAINS.iter() {
+            let tracking = make_tracking_allocator(*domain);
+
+            unsafe {
+                let ptr = &tracking.allocator as *const _;
+                pyffi::PyMem_SetAllocator(*domain, ptr as *mut _);
+            }
+
+            tracking_allocators.push(tracking);
+        }
+    }
+
+    std::mem::forget(allocators);
+    std::mem::forget(tracking_allocators);
+
+    *registered = Some(settings);
+
+    Ok(())
+}
+
+#[cfg(all(unix, not(Py_3_8)))]
 fn set_windows_flags(_config: &PythonConfig) {}
 
-#[cfg(windows)]
+#[cfg(all(windows, not(Py_3_8)))]
 fn set_windows_flags(config: &PythonConfig) {
     unsafe {
         pyffi::Py_LegacyWindowsFSEncodingFlag = if config.legacy_windows_fs_encoding {
@@ -130,10 +259,501 @@ fn set_windows_flags(config: &PythonConfig) {
     }
 }
 
+/// Settings derived from a `PythonConfig` used to initialize the interpreter.
+struct InitSettings<'a> {
+    config: &'a PythonConfig,
+    program_name: &'a str,
+    home: Option<&'a str>,
+    #[cfg_attr(not(Py_3_8), allow(dead_code))]
+    sys_prefix: Option<&'a str>,
+    #[cfg_attr(not(Py_3_8), allow(dead_code))]
+    sys_exec_prefix: Option<&'a str>,
+    // Module search path for Python to use. If not defined, Python
+    // computes it.
+    search_paths: Option<&'a [String]>,
+    xoptions: &'a [String],
+    dev_mode: bool,
+}
+
+/// Prepare the process for interpreter initialization.
+#[cfg(not(Py_3_8))]
+fn pre_initialize(config: &PythonConfig) -> Result<(), &'static str> {
+    // Both influence how Py_DecodeLocale() decodes strings passed to
+    // Python before initialization. So they need to come first.
+    if config.coerce_c_locale {
+        coerce_c_locale();
+    }
+
+    unsafe {
+        pyffi::Py_UTF8Mode = if config.utf8_mode { 1 } else { 0 };
+    }
+
+    Ok(())
+}
+
+/// Prepare the process for interpreter initialization.
+///
+/// This pre-initializes Python, which configures the locale and UTF-8 mode.
+#[cfg(Py_3_8)]
+fn pre_initialize(config: &PythonConfig) -> Result<(), &'static str> {
+    let mut pre_config: pyffi::PyPreConfig = unsafe { std::mem::zeroed() };
+
+    unsafe {
+        pyffi::PyPreConfig_InitPythonConfig(&mut pre_config);
+    }
+
+    pre_config.parse_argv = 0;
+    pre_config.isolated = if config.isolated { 1 } else { 0 };
+    pre_config.use_environment = if config.ignore_python_env { 0 } else { 1 };
+    pre_config.utf8_mode = if config.utf8_mode { 1 } else { 0 };
+    pre_config.coerce_c_locale = if config.coerce_c_locale { 1 } else { 0 };
+    pre_config.coerce_c_locale_warn = 0;
+    #[cfg(windows)]
+    {
+        pre_config.legacy_windows_fs_encoding = if config.legacy_windows_fs_encoding {
+            1
+        } else {
+            0
+        };
+    }
+
+    // Development mode would install Python's debug allocators. We install
+    // debug hooks on top of our own allocators instead.
+    pre_config.dev_mode = 0;
+
+    check_status(unsafe { pyffi::Py_PreInitialize(&pre_config) })
+}
+
+/// Convert a `PyStatus` to a `Result`.
+#[cfg(Py_3_8)]
+fn check_status(status: pyffi::PyStatus) -> Result<(), &'static str> {
+    if unsafe { pyffi::PyStatus_Exception(status) } == 0 {
+        return Ok(());
+    }
+
+    if unsafe { pyffi::PyStatus_IsExit(status) } != 0 {
+        return Err("Python requested to exit during initialization");
+    }
+
+    if status.err_msg.is_null() {
+        return Err("error initializing Python");
+    }
+
+    // Error messages are string literals in libpython. So they live as long
+    // as the process.
+    let msg = unsafe { std::ffi::CStr::from_ptr(status.err_msg) };
+
+    Err(msg.to_str().unwrap_or("error initializing Python"))
+}
+
+/// Initialize the interpreter using legacy global configuration variables.
+///
+/// Returns the program name, which must outlive the interpreter.
+#[cfg(not(Py_3_8))]
+fn initialize_interpreter(settings: &InitSettings) -> Result<Option<OwnedPyStr>, &'static str> {
+    let config = settings.config;
+
+    if let Some(home) = settings.home {
+        let home = OwnedPyStr::from_str(home)?;
+
+        unsafe {
+            // Value is copied internally. So short lifetime is OK.
+            pyffi::Py_SetPythonHome(home.as_wchar_ptr());
+        }
+    }
+
+    let program_name = OwnedPyStr::from_str(settings.program_name)?;
+
+    unsafe {
+        pyffi::Py_SetProgramName(program_name.as_wchar_ptr());
+    }
+
+    // If we don't call Py_SetPath(), Python has its own logic for initializing it.
+    // Our importer adds sys_paths to sys.path after Py_Initialize(). So we set it
+    // to an empty string because we don't want any paths by default.
+    if let Some(paths) = settings.search_paths {
+        let delimiter = if cfg!(windows) { ";" } else { ":" };
+        let path = if config.use_custom_importlib {
+            String::new()
+        } else {
+            paths.join(delimiter)
+        };
+
+        unsafe {
+            // Value is copied internally. So short lifetime is OK.
+            let value = OwnedPyStr::from_str(&path)?;
+            pyffi::Py_SetPath(value.as_wchar_ptr());
+        }
+    }
+
+    if let (Some(ref encoding), Some(ref errors)) =
+        (&config.standard_io_encoding, &config.standard_io_errors)
+    {
+        let cencoding = CString::new(encoding.clone())
+            .or_else(|_| Err("unable to convert encoding to C string"))?;
+        let cerrors = CString::new(errors.clone())
+            .or_else(|_| Err("unable to convert encoding error mode to C string"))?;
+
+        let res = unsafe {
+            pyffi::Py_SetStandardStreamEncoding(
+                cencoding.as_ptr() as *const i8,
+                cerrors.as_ptr() as *const i8,
+            )
+        };
+
+        if res != 0 {
+            return Err("unable to set standard stream encoding");
+        }
+    }
+
+    unsafe {
+        pyffi::Py_BytesWarningFlag = config.bytes_warning;
+        pyffi::Py_DebugFlag = if config.parser_debug { 1 } else { 0 };
+        pyffi::Py_DontWriteBytecodeFlag = if config.dont_write_bytecode { 1 } else { 0 };
+        pyffi::Py_HashRandomizationFlag = if config.use_hash_seed { 1 } else { 0 };
+        pyffi::Py_IgnoreEnvironmentFlag = if config.ignore_python_env { 1 } else { 0 };
+        pyffi::Py_InteractiveFlag = if config.interactive { 1 } else { 0 };
+        pyffi::Py_InspectFlag = if config.inspect { 1 } else { 0 };
+        pyffi::Py_IsolatedFlag = if config.isolated { 1 } else { 0 };
+        pyffi::Py_NoSiteFlag = if config.import_site { 0 } else { 1 };
+        pyffi::Py_NoUserSiteDirectory = if config.import_user_site { 0 } else { 1 };
+        pyffi::Py_OptimizeFlag = config.opt_level;
+        pyffi::Py_QuietFlag = if config.quiet { 1 } else { 0 };
+        pyffi::Py_UnbufferedStdioFlag = if config.unbuffered_stdio { 1 } else { 0 };
+        pyffi::Py_VerboseFlag = config.verbose;
+    }
+
+    set_windows_flags(config);
+
+    // Development mode shows warnings that are ignored by default. Later
+    // filters take precedence, so explicit warning options come after.
+    let mut warnoptions = Vec::new();
+    if settings.dev_mode {
+        warnoptions.push(OwnedPyStr::from_str("default")?);
+    }
+    for option in &config.warnoptions {
+        warnoptions.push(OwnedPyStr::from_str(option)?);
+    }
+
+    let xoptions = settings
+        .xoptions
+        .iter()
+        .map(|option| OwnedPyStr::from_str(option))
+        .collect::<Result<Vec<OwnedPyStr>, &'static str>>()?;
+
+    // Values are copied internally. So short lifetimes are OK.
+    for value in &warnoptions {
+        unsafe {
+            pyffi::PySys_AddWarnOption(value.as_wchar_ptr());
+        }
+    }
+
+    for value in &xoptions {
+        unsafe {
+            pyffi::PySys_AddXOption(value.as_wchar_ptr());
+        }
+    }
+
+    /* Pre-initialization functions we could support:
+     *
+     * PyObject_SetArenaAllocator()
+     * PySys_ResetWarnOptions()
+     */
+
+    if config.hash_seed.is_some() && config.ignore_python_env {
+        return Err("hash_seed requires ignore_python_env to be false");
+    }
+
+    // Python 3.7 only reads the hash seed from the environment. Nothing may
+    // fail between setting it and restoring it below.
+    let original_hash_seed = env::var("PYTHONHASHSEED").ok();
+    if let Some(seed) = config.hash_seed {
+        env::set_var("PYTHONHASHSEED", seed.to_string());
+    }
+
+    unsafe {
+        pyffi::Py_InitializeEx(if config.install_signal_handlers { 1 } else { 0 });
+    }
+
+    // Restore the environment so the hash seed isn't inherited by child
+    // processes. os.environ is a copy made during initialization and updates
+    // the process environment when modified.
+    if config.hash_seed.is_some() {
+        let py = unsafe { Python::assume_gil_acquired() };
+        let res = py
+            .import("os")
+            .and_then(|os| os.get(py, "environ"))
+            .and_then(|environ| match &original_hash_seed {
+                Some(value) => environ.set_item(py, "PYTHONHASHSEED", value.as_str()),
+                None => environ
+                    .call_method(py, "pop", ("PYTHONHASHSEED", py.None()), None)
+                    .map(|_| ()),
+            });
+
+        if res.is_err() {
+            match &original_hash_seed {
+                Some(value) => env::set_var("PYTHONHASHSEED", value),
+                None => env::remove_var("PYTHONHASHSEED"),
+            }
+
+            return Err("unable to restore PYTHONHASHSEED");
+        }
+    }
+
+    Ok(Some(program_name))
+}
+
+/// Set a string field of a `PyConfig`.
+#[cfg(Py_3_8)]
+unsafe fn set_config_string(
+    py_config: &mut pyffi::PyConfig,
+    field: *mut *mut libc::wchar_t,
+    value: &str,
+) -> Result<(), &'static str> {
+    let value = CString::new(value).or_else(|_| Err("config string has NULL bytes"))?;
+
+    check_status(pyffi::PyConfig_SetBytesString(
+        py_config,
+        field,
+        value.as_ptr(),
+    ))
+}
+
+/// Append strings to a list field of a `PyConfig`.
+#[cfg(Py_3_8)]
+unsafe fn append_config_strings(
+    list: &mut pyffi::PyWideStringList,
+    values: &[String],
+) -> Result<(), &'static str> {
+    for value in values {
+        // Value is copied internally. So short lifetime is OK.
+        let value = OwnedPyStr::from_str(value)?;
+
+        check_status(pyffi::PyWideStringList_Append(list, value.as_wchar_ptr()))?;
+    }
+
+    Ok(())
+}
+
+/// Populate a `PyConfig` from our settings.
+#[cfg(Py_3_8)]
+unsafe fn populate_config(
+    py_config: &mut pyffi::PyConfig,
+    settings: &InitSettings,
+) -> Result<(), &'static str> {
+    let config = settings.config;
+
+    py_config.parse_argv = 0;
+    py_config.isolated = if config.isolated { 1 } else { 0 };
+    py_config.use_environment = if config.ignore_python_env { 0 } else { 1 };
+    py_config.dev_mode = if settings.dev_mode { 1 } else { 0 };
+    py_config.install_signal_handlers = if config.install_signal_handlers { 1 } else { 0 };
+    if let Some(seed) = config.hash_seed {
+        py_config.use_hash_seed = 1;
+        py_config.hash_seed = libc::c_ulong::from(seed);
+    }
+    py_config.site_import = if config.import_site { 1 } else { 0 };
+    py_config.user_site_directory = if config.import_user_site { 1 } else { 0 };
+    py_config.bytes_warning = config.bytes_warning;
+    py_config.inspect = if config.inspect { 1 } else { 0 };
+    py_config.interactive = if config.interactive { 1 } else { 0 };
+    py_config.optimization_level = config.opt_level;
+    py_config.parser_debug = if config.parser_debug { 1 } else { 0 };
+    py_config.write_bytecode = if config.dont_write_bytecode { 0 } else { 1 };
+    py_config.verbose = config.verbose;
+    py_config.quiet = if config.quiet { 1 } else { 0 };
+    py_config.buffered_stdio = if config.unbuffered_stdio { 0 } else { 1 };
+    #[cfg(windows)]
+    {
+        py_config.legacy_windows_stdio = if config.legacy_windows_stdio { 1 } else { 0 };
+    }
+
+    if let Some(paths) = settings.search_paths {
+        py_config.pathconfig_warnings = 0;
+        py_config.module_search_paths_set = 1;
+        append_config_strings(&mut py_config.module_search_paths, paths)?;
+    }
+
+    let field = &mut py_config.program_name as *mut _;
+    set_config_string(py_config, field, settings.program_name)?;
+    if let Some(home) = settings.home {
+        let field = &mut py_config.home as *mut _;
+        set_config_string(py_config, field, home)?;
+    }
+
+    // Python otherwise derives these from the computed path configuration.
+    if let Some(prefix) = settings.sys_prefix {
+        let field = &mut py_config.prefix as *mut _;
+        set_config_string(py_config, field, prefix)?;
+        let field = &mut py_config.base_prefix as *mut _;
+        set_config_string(py_config, field, prefix)?;
+    }
+
+    if let Some(exec_prefix) = settings.sys_exec_prefix {
+        let field = &mut py_config.exec_prefix as *mut _;
+        set_config_string(py_config, field, exec_prefix)?;
+        let field = &mut py_config.base_exec_prefix as *mut _;
+        set_config_string(py_config, field, exec_prefix)?;
+    }
+
+    if let (Some(ref encoding), Some(ref errors)) =
+        (&config.standard_io_encoding, &config.standard_io_errors)
+    {
+        let field = &mut py_config.stdio_encoding as *mut _;
+        set_config_string(py_config, field, encoding)?;
+        let field = &mut py_config.stdio_errors as *mut _;
+        set_config_string(py_config, field, errors)?;
+    }
+
+    append_config_strings(&mut py_config.warnoptions, &config.warnoptions)?;
+    append_config_strings(&mut py_config.xoptions, settings.xoptions)?;
+
+    Ok(())
+}
+
+/// Initialize the interpreter using the `PyConfig` API (PEP 587).
+///
+/// Errors are reported instead of exiting the process.
+#[cfg(Py_3_8)]
+fn initialize_interpreter(settings: &InitSettings) -> Result<Option<OwnedPyStr>, &'static str> {
+    let mut py_config: pyffi::PyConfig = unsafe { std::mem::zeroed() };
+
+    unsafe {
+        pyffi::PyConfig_InitPythonConfig(&mut py_config);
+    }
+
+    let res = unsafe { populate_config(&mut py_config, settings) }
+        .and_then(|_| check_status(unsafe { pyffi::Py_InitializeFromConfig(&py_config) }));
+
+    unsafe {
+        pyffi::PyConfig_Clear(&mut py_config);
+    }
+
+    res.map(|_| None)
+}
+
+/// Start tracemalloc if requested by X options.
+///
+/// Python 3.7 only starts tracemalloc for options passed on the command line.
+/// So we start it ourselves.
+#[cfg(not(Py_3_8))]
+fn start_tracemalloc(py: Python, xoptions: &[String]) -> Result<(), &'static str> {
+    for option in xoptions {
+        let frames = if option == "tracemalloc" {
+            1
+        } else if option.starts_with("tracemalloc=") {
+            option["tracemalloc=".len()..]
+                .parse::<u32>()
+                .or_else(|_| Err("invalid number of frames for -X tracemalloc"))?
+        } else {
+            continue;
+        };
+
+        py.import("tracemalloc")
+            .and_then(|m| m.call(py, "start", (frames,), None))
+            .or_else(|_| Err("unable to start tracemalloc"))?;
+    }
+
+    Ok(())
+}
+
+/// Start tracemalloc if requested by X options.
+///
+/// `PyConfig` handles this during initialization.
+#[cfg(Py_3_8)]
+fn start_tracemalloc(_py: Python, _xoptions: &[String]) -> Result<(), &'static str> {
+    Ok(())
+}
+
+/// Raise `KeyboardInterrupt` in the main thread of the Python interpreter.
+///
+/// This calls `PyErr_SetInterrupt()`, which simulates the arrival of `SIGINT`.
+/// The exception is raised the next time the interpreter checks for pending
+/// signals. This function can be called from any thread and does not require
+/// the GIL.
+///
+/// If Python code has replaced the `SIGINT` handler via `signal.signal()`,
+/// that handler is called instead.
+pub fn raise_keyboard_interrupt() {
+    unsafe {
+        pyffi::PyErr_SetInterrupt();
+    }
+}
+
+/// Obtain the current disposition of a signal.
+fn current_signal_handler(signal: libc::c_int) -> libc::sighandler_t {
+    unsafe {
+        let handler = libc::signal(signal, libc::SIG_DFL);
+        libc::signal(signal, handler);
+
+        handler
+    }
+}
+
+/// Signal handler forwarding signals to the interpreter as `KeyboardInterrupt`.
+extern "C" fn forward_signal_handler(_signal: libc::c_int) {
+    raise_keyboard_interrupt();
+}
+
+/// A handle on an initialized Python interpreter usable from any thread.
+///
+/// Instances are obtained from
+/// [`MainPythonInterpreter::handle()`](struct.MainPythonInterpreter.html#method.handle).
+/// Handles can be cloned and sent to other threads, which can then call into
+/// Python via [`with_gil()`](#method.with_gil).
+///
+/// Threads not created by Python have a Python thread state created for them
+/// when acquiring the GIL. It is destroyed when the GIL is released.
+///
+/// The thread owning the `MainPythonInterpreter` must release the GIL via
+/// `MainPythonInterpreter::release_gil()` for other threads to acquire it.
+/// When the `MainPythonInterpreter` is dropped, it waits for active
+/// `with_gil()` calls to finish. Subsequent calls return an error.
+#[derive(Clone)]
+pub struct InterpreterHandle {
+    alive: Arc<RwLock<bool>>,
+}
+
+impl InterpreterHandle {
+    /// Run a function with the GIL held.
+    ///
+    /// The GIL is acquired before calling the function and released when it
+    /// returns. Returns an error if the interpreter has been finalized.
+    pub fn with_gil<F, R>(&self, f: F) -> Result<R, &'static str>
+    where
+        F: FnOnce(Python) -> R,
+    {
+        let alive = self
+            .alive
+            .read()
+            .or_else(|_| Err("unable to obtain interpreter state"))?;
+
+        if !*alive {
+            return Err("interpreter has been finalized");
+        }
+
+        let gil = GILGuard::acquire();
+        let res = f(gil.python());
+        drop(gil);
+
+        Ok(res)
+    }
+
+    /// Whether the interpreter this handle refers to is still alive.
+    pub fn is_alive(&self) -> bool {
+        match self.alive.read() {
+            Ok(alive) => *alive,
+            Err(_) => false,
+        }
+    }
+}
+
 /// Manages an embedded Python interpreter.
 ///
 /// **Warning: Python interpreters have global state. There should only be a
-/// single instance of this type per process.**
+/// single instance of this type alive at a time.** Once an instance is
+/// dropped, the interpreter is finalized and another instance can be
+/// created. Memory allocator settings can't change between instances.
 ///
 /// Instances must only be constructed through [`MainPythonInterpreter::new()`](#method.new).
 ///
@@ -146,18 +766,38 @@ pub struct MainPythonInterpreter<'a> {
     pub config: PythonConfig,
     frozen_modules: [pyffi::_frozen; 3],
     init_run: bool,
-    raw_allocator: Option<pyffi::PyMemAllocatorEx>,
-    raw_rust_allocator: Option<RawAllocator>,
     gil: Option<GILGuard>,
     py: Option<Python<'a>>,
+    // Thread state of the main thread when it released the GIL it held
+    // since interpreter initialization.
+    thread_state: Option<*mut pyffi::PyThreadState>,
+    alive: Arc<RwLock<bool>>,
     program_name: Option<OwnedPyStr>,
+    // Directory embedded terminfo entries were extracted to.
+    terminfo_dir: Option<PathBuf>,
+    // File embedded CA certificates were written to.
+    ssl_ca_bundle_path: Option<PathBuf>,
+    // Built-in extension modules. Python reads the table while the
+    // interpreter is alive.
+    inittab: Vec<pyffi::_inittab>,
+    // Global tables we replaced, restored after finalization.
+    original_inittab: Option<*mut pyffi::_inittab>,
+    original_frozen_modules: Option<*const pyffi::_frozen>,
+    // Signal dispositions we changed, restored after finalization.
+    original_signal_handlers: Vec<(libc::c_int, libc::sighandler_t)>,
 }
 
 impl<'a> MainPythonInterpreter<'a> {
     /// Construct a Python interpreter from a configuration.
     ///
     /// The Python interpreter is initialized as a side-effect. The GIL is held.
-    pub fn new(config: PythonConfig) -> Result<MainPythonInterpreter<'a>, &'static str> {
+    ///
+    /// If the config allows fields to be overridden, a config overlay file is
+    /// applied to the config first.
+    pub fn new(mut config: PythonConfig) -> Result<MainPythonInterpreter<'a>, &'static str> {
+        apply_config_overlay(&mut config)?;
+        load_packed_data(&mut config)?;
+
         match config.terminfo_resolution {
             TerminfoResolution::Dynamic => {
                 if let Some(v) = resolve_terminfo_dirs() {
@@ -170,11 +810,22 @@ impl<'a> MainPythonInterpreter<'a> {
             TerminfoResolution::None => {}
         }
 
-        let (raw_allocator, raw_rust_allocator) = match config.raw_allocator {
-            PythonRawAllocator::Jemalloc => (Some(raw_jemallocator()), None),
-            PythonRawAllocator::Rust => (None, Some(make_raw_rust_memory_allocator())),
-            PythonRawAllocator::System => (None, None),
-        };
+        let terminfo_dir = install_embedded_terminfo(config.terminfo_data)?;
+
+        let ssl_ca_bundle_path = install_ssl_ca_bundle(config.ssl_ca_bundle_data)?;
+
+        // Tcl and Tk read these when tkinter creates an interpreter. Values
+        // from the environment take precedence.
+        for (key, value) in &[
+            ("TCL_LIBRARY", &config.tcl_library),
+            ("TK_LIBRARY", &config.tk_library),
+        ] {
+            if let Some(path) = value {
+                if env::var_os(key).is_none() {
+                    env::set_var(key, resolve_origin_path(path)?);
+                }
+            }
+        }
 
         let frozen_modules = make_custom_frozen_modules(&config);
 
@@ -182,11 +833,17 @@ impl<'a> MainPythonInterpreter<'a> {
             config,
             frozen_modules,
             init_run: false,
-            raw_allocator,
-            raw_rust_allocator,
             gil: None,
             py: None,
+            thread_state: None,
+            alive: Arc::new(RwLock::new(true)),
             program_name: None,
+            terminfo_dir,
+            ssl_ca_bundle_path,
+            inittab: Vec::new(),
+            original_inittab: None,
+            original_frozen_modules: None,
+            original_signal_handlers: Vec::new(),
         };
 
         res.init()?;
@@ -213,6 +870,10 @@ impl<'a> MainPythonInterpreter<'a> {
 
         let config = &self.config;
 
+        // This needs to happen before anything else touches Python. Custom
+        // memory allocators can only be registered afterwards.
+        pre_initialize(config)?;
+
         let exe = env::current_exe().or_else(|_| Err("could not obtain current exe"))?;
         let origin = exe
             .parent()
@@ -226,24 +887,15 @@ impl<'a> MainPythonInterpreter<'a> {
             .map(|path| path.replace("$ORIGIN", &origin))
             .collect();
 
-        // TODO should we call PyMem::SetupDebugHooks() if enabled?
-        if let Some(raw_allocator) = &self.raw_allocator {
-            unsafe {
-                let ptr = raw_allocator as *const _;
-                pyffi::PyMem_SetAllocator(
-                    pyffi::PyMemAllocatorDomain::PYMEM_DOMAIN_RAW,
-                    ptr as *mut _,
-                );
-            }
-        } else if let Some(raw_rust_allocator) = &self.raw_rust_allocator {
-            unsafe {
-                let ptr = &raw_rust_allocator.allocator as *const _;
-                pyffi::PyMem_SetAllocator(
-                    pyffi::PyMemAllocatorDomain::PYMEM_DOMAIN_RAW,
-                    ptr as *mut _,
-                );
-            }
-        }
+        let dev_mode = config.dev_mode || config.xoptions.iter().any(|o| o == "dev");
+
+        register_allocators(AllocatorSettings {
+            raw: config.raw_allocator.clone(),
+            mem: config.mem_allocator.clone(),
+            obj: config.obj_allocator.clone(),
+            stats: config.allocator_stats,
+            debug_hooks: dev_mode,
+        })?;
 
         // Module state is a bit wonky.
         //
@@ -272,138 +924,208 @@ impl<'a> MainPythonInterpreter<'a> {
         // TODO specify lifetimes so the compiler validates this for us.
         let module_state = super::importer::InitModuleState {
             register_filesystem_importer: self.config.filesystem_importer,
-            sys_paths,
+            sys_paths: sys_paths.clone(),
+            import_policy: ImportPolicy {
+                allow: config.import_allow.clone(),
+                deny: config.import_deny.clone(),
+            },
             py_modules_data: config.py_modules_data,
             py_resources_data: config.py_resources_data,
+            zip_archives_data: config.zip_archives_data,
+            zip_archives_path: origin.clone(),
         };
 
-        if config.use_custom_importlib {
-            // Replace the frozen modules in the interpreter with our custom set
-            // that knows how to import from memory.
-            unsafe {
-                pyffi::PyImport_FrozenModules = self.frozen_modules.as_ptr();
-            }
+        // We provide our own table of built-in modules instead of calling
+        // PyImport_ExtendInittab(), which replaces Python's table with one that
+        // is never restored. Name char* need to live as long as the interpreter
+        // is active. They are static or owned by the config, which we hold on to.
+        let original_inittab = unsafe { pyffi::PyImport_Inittab };
+        let mut inittab = Vec::new();
 
-            // Register our _pyoxidizer_importer extension which provides importing functionality.
-            unsafe {
-                // name char* needs to live as long as the interpreter is active.
-                pyffi::PyImport_AppendInittab(
-                    PYOXIDIZER_IMPORTER_NAME.as_ptr() as *const i8,
-                    Some(PyInit__pyoxidizer_importer),
-                );
+        for i in 0.. {
+            let record = unsafe { *original_inittab.offset(i) };
 
-                // Move pointer to our stack allocated instance. This pointer will be
-                // accessed when creating the Python module object, which should be
-                // done automatically as part of low-level interpreter initialization
-                // when calling Py_Initialize() below.
-                super::importer::NEXT_MODULE_STATE = &module_state;
+            if record.name.is_null() {
+                break;
             }
-        }
 
-        // TODO call PyImport_ExtendInitTab to avoid O(n) overhead.
-        for e in &config.extra_extension_modules {
-            let res = unsafe {
-                pyffi::PyImport_AppendInittab(e.name.as_ptr() as *const i8, Some(e.init_func))
-            };
+            inittab.push(record);
+        }
 
-            if res != 0 {
-                return Err("unable to register extension module");
-            }
+        if config.use_custom_importlib {
+            // Register our _pyoxidizer_importer extension which provides importing functionality.
+            inittab.push(pyffi::_inittab {
+                name: PYOXIDIZER_IMPORTER_NAME.as_ptr() as *mut _,
+                initfunc: Some(PyInit__pyoxidizer_importer),
+            });
         }
 
-        let exe_str = exe.to_str().ok_or_else(|| "unable to convert exe to str")?;
+        inittab.extend(
+            config
+                .extra_extension_modules
+                .iter()
+                .map(|e| pyffi::_inittab {
+                    name: e.name.as_ptr() as *mut _,
+                    initfunc: Some(e.init_func),
+                }),
+        );
+
+        inittab.push(pyffi::_inittab {
+            name: std::ptr::null_mut(),
+            initfunc: None,
+        });
 
-        let home = OwnedPyStr::from_str(exe_str)?;
+        self.inittab = inittab;
+        self.original_inittab = Some(original_inittab);
 
         unsafe {
-            // Pointer needs to live for lifetime of interpreter.
-            pyffi::Py_SetPythonHome(home.as_wchar_ptr());
+            pyffi::PyImport_Inittab = self.inittab.as_mut_ptr();
         }
 
-        let program_name = OwnedPyStr::from_str(exe_str)?;
+        if config.use_custom_importlib {
+            // Replace the frozen modules in the interpreter with our custom set
+            // that knows how to import from memory.
+            unsafe {
+                self.original_frozen_modules = Some(pyffi::PyImport_FrozenModules);
+                pyffi::PyImport_FrozenModules = self.frozen_modules.as_ptr();
+            }
 
-        unsafe {
-            pyffi::Py_SetProgramName(program_name.as_wchar_ptr());
+            // Move pointer to our stack allocated instance. This pointer will be
+            // accessed when creating the Python module object, which should be
+            // done automatically as part of low-level interpreter initialization
+            // when calling Py_Initialize() below.
+            unsafe {
+                super::importer::NEXT_MODULE_STATE = &module_state;
+            }
         }
 
-        // Value needs to live for lifetime of interpreter.
-        self.program_name = Some(program_name);
+        let exe_str = exe.to_str().ok_or_else(|| "unable to convert exe to str")?;
 
-        // If we don't call Py_SetPath(), Python has its own logic for initializing it.
-        // We set it to an empty string because we don't want any paths by default. If
-        // we do have defined paths, they will be set after Py_Initialize().
-        unsafe {
-            // Value is copied internally. So short lifetime is OK.
-            let value = OwnedPyStr::from_str("")?;
-            pyffi::Py_SetPath(value.as_wchar_ptr());
+        let sys_prefix = config
+            .sys_prefix
+            .as_ref()
+            .map(|path| path.replace("$ORIGIN", &origin));
+        let sys_exec_prefix = config
+            .sys_exec_prefix
+            .as_ref()
+            .map(|path| path.replace("$ORIGIN", &origin))
+            .or_else(|| sys_prefix.clone());
+
+        let mut xoptions = config.xoptions.clone();
+        if config.dev_mode && !xoptions.iter().any(|o| o == "dev") {
+            xoptions.push("dev".to_string());
         }
 
-        if let (Some(ref encoding), Some(ref errors)) =
-            (&config.standard_io_encoding, &config.standard_io_errors)
-        {
-            let cencoding = CString::new(encoding.clone())
-                .or_else(|_| Err("unable to convert encoding to C string"))?;
-            let cerrors = CString::new(errors.clone())
-                .or_else(|_| Err("unable to convert encoding error mode to C string"))?;
-
-            let res = unsafe {
-                pyffi::Py_SetStandardStreamEncoding(
-                    cencoding.as_ptr() as *const i8,
-                    cerrors.as_ptr() as *const i8,
-                )
-            };
+        let ignore_signals = config
+            .ignore_signals
+            .iter()
+            .map(|name| resolve_signal(name).ok_or_else(|| "unknown signal in ignore_signals"))
+            .collect::<Result<Vec<libc::c_int>, &'static str>>()?;
+        let forward_signals = config
+            .forward_signals
+            .iter()
+            .map(|name| resolve_signal(name).ok_or_else(|| "unknown signal in forward_signals"))
+            .collect::<Result<Vec<libc::c_int>, &'static str>>()?;
+
+        // Dispositions are process-wide. Record them before Python installs
+        // its own handlers so finalization leaves the process as we found it.
+        for &signal in ignore_signals.iter().chain(forward_signals.iter()) {
+            if !self
+                .original_signal_handlers
+                .iter()
+                .any(|&(s, _)| s == signal)
+            {
+                self.original_signal_handlers
+                    .push((signal, current_signal_handler(signal)));
+            }
+        }
 
-            if res != 0 {
-                return Err("unable to set standard stream encoding");
+        // Ignored signals need to be in place before Python initializes its
+        // signal handling, as Python leaves ignored signals alone.
+        for &signal in &ignore_signals {
+            unsafe {
+                libc::signal(signal, libc::SIG_IGN);
             }
         }
 
-        unsafe {
-            pyffi::Py_BytesWarningFlag = config.bytes_warning;
-            pyffi::Py_DebugFlag = if config.parser_debug { 1 } else { 0 };
-            pyffi::Py_DontWriteBytecodeFlag = if config.dont_write_bytecode { 1 } else { 0 };
-            // TODO we could potentially have the config be an Option<i32> so we can control
-            // the hash seed explicitly. But the APIs in Python 3.7 aren't great here, as we'd
-            // need to set an environment variable. Once we support the new initialization
-            // API in Python 3.8, things will be easier to implement.
-            pyffi::Py_HashRandomizationFlag = if config.use_hash_seed { 1 } else { 0 };
-            pyffi::Py_IgnoreEnvironmentFlag = if config.ignore_python_env { 1 } else { 0 };
-            pyffi::Py_InteractiveFlag = if config.interactive { 1 } else { 0 };
-            pyffi::Py_InspectFlag = if config.inspect { 1 } else { 0 };
-            pyffi::Py_IsolatedFlag = if config.isolated { 1 } else { 0 };
-            pyffi::Py_NoSiteFlag = if config.import_site { 0 } else { 1 };
-            pyffi::Py_NoUserSiteDirectory = if config.import_user_site { 0 } else { 1 };
-            pyffi::Py_OptimizeFlag = config.opt_level;
-            pyffi::Py_QuietFlag = if config.quiet { 1 } else { 0 };
-            pyffi::Py_UnbufferedStdioFlag = if config.unbuffered_stdio { 1 } else { 0 };
-            pyffi::Py_VerboseFlag = config.verbose;
-        }
-
-        set_windows_flags(config);
-
-        /* Pre-initialization functions we could support:
-         *
-         * PyObject_SetArenaAllocator()
-         * PySys_AddWarnOption()
-         * PySys_AddXOption()
-         * PySys_ResetWarnOptions()
-         */
+        // Python 3.8 receives sys.prefix and sys.exec_prefix during
+        // initialization. So modules imported during initialization, like
+        // site, see the configured values. With Python 3.7, Py_SetPath()
+        // empties them during initialization and they are only set afterwards.
+        let home = match &sys_prefix {
+            Some(prefix) => Some(prefix.as_str()),
+            None if config.use_custom_importlib => Some(exe_str),
+            None => None,
+        };
 
+        // Our importer defines sys.path. Without it, Python searches sys_paths
+        // or computes the path of the standard library as it normally would.
+        let search_paths = if config.use_custom_importlib || !sys_paths.is_empty() {
+            Some(sys_paths.as_slice())
+        } else {
+            None
+        };
+
+        // The path configuration outlives interpreters. Clear what was set or
+        // computed for a previous interpreter, as Python uses it for values
+        // we don't define.
         unsafe {
-            pyffi::Py_Initialize();
+            pyffi::Py_SetPath(std::ptr::null());
         }
 
+        let res = initialize_interpreter(&InitSettings {
+            config,
+            program_name: exe_str,
+            home,
+            sys_prefix: sys_prefix.as_ref().map(|prefix| prefix.as_str()),
+            sys_exec_prefix: sys_exec_prefix.as_ref().map(|prefix| prefix.as_str()),
+            search_paths,
+            xoptions: &xoptions,
+            dev_mode,
+        });
+
         // We shouldn't be accessing this pointer after Py_Initialize(). And the
         // memory is stack allocated and doesn't outlive this frame. We don't want
-        // to leave a stack pointer sitting around!
+        // to leave a stack pointer sitting around, even if initialization failed!
         unsafe {
             super::importer::NEXT_MODULE_STATE = std::ptr::null();
         }
 
+        // Value needs to live for lifetime of interpreter.
+        self.program_name = res?;
+
         let py = unsafe { Python::assume_gil_acquired() };
         self.py = Some(py);
         self.init_run = true;
 
+        // PyErr_SetInterrupt() calls the Python-level SIGINT handler registered
+        // by the _signal module. If Python didn't install signal handlers, that
+        // handler is only registered if SIGINT has the default disposition when
+        // _signal is initialized. So we temporarily reset SIGINT while importing
+        // _signal then restore the host's handler. This allows
+        // raise_keyboard_interrupt() to work without Python owning SIGINT.
+        if !config.install_signal_handlers {
+            let handler = unsafe { libc::signal(libc::SIGINT, libc::SIG_DFL) };
+            let res = py.import("_signal");
+
+            unsafe {
+                libc::signal(libc::SIGINT, handler);
+            }
+
+            if res.is_err() {
+                return Err("unable to import _signal");
+            }
+        }
+
+        for &signal in &forward_signals {
+            unsafe {
+                libc::signal(
+                    signal,
+                    forward_signal_handler as extern "C" fn(libc::c_int) as libc::sighandler_t,
+                );
+            }
+        }
+
         // env::args() panics if arguments aren't valid Unicode. But invalid
         // Unicode arguments are possible and some applications may want to
         // support them.
@@ -483,14 +1205,55 @@ impl<'a> MainPythonInterpreter<'a> {
             }
         }
 
+        if let Some(prefix) = &sys_prefix {
+            set_sys_str(py, "prefix", prefix)?;
+            set_sys_str(py, "base_prefix", prefix)?;
+        }
+
+        if let Some(exec_prefix) = &sys_exec_prefix {
+            set_sys_str(py, "exec_prefix", exec_prefix)?;
+            set_sys_str(py, "base_exec_prefix", exec_prefix)?;
+        }
+
+        if config.faulthandler {
+            let path = config
+                .faulthandler_path
+                .as_ref()
+                .map(|path| path.replace("$ORIGIN", &origin));
+
+            enable_faulthandler(py, path.as_ref().map(|p| p.as_str()))?;
+        } else if dev_mode {
+            enable_faulthandler(py, None)?;
+        }
+
+        if config.logging_bridge {
+            install_logging_bridge(py)?;
+        }
+
+        start_tracemalloc(py, &xoptions)?;
+
+        // Modules needed to initialize the interpreter were imported above. So
+        // the policy only applies to imports by application code.
+        set_import_policy_enforced(true);
+
         Ok(py)
     }
 
     /// Ensure the Python GIL is released.
+    ///
+    /// This allows other threads to acquire the GIL, such as those using an
+    /// [`InterpreterHandle`](struct.InterpreterHandle.html).
     pub fn release_gil(&mut self) {
         if self.py.is_some() {
             self.py = None;
-            self.gil = None;
+
+            // The GIL acquired by Py_Initialize() isn't held by a GILGuard.
+            // So we need to release it by saving the thread state.
+            if self.gil.is_some() {
+                self.gil = None;
+            } else {
+                self.thread_state = Some(unsafe { pyffi::PyEval_SaveThread() });
+            }
         }
     }
 
@@ -499,10 +1262,15 @@ impl<'a> MainPythonInterpreter<'a> {
         match self.py {
             Some(py) => py,
             None => {
-                let gil = GILGuard::acquire();
-                let py = unsafe { Python::assume_gil_acquired() };
+                if let Some(thread_state) = self.thread_state.take() {
+                    unsafe {
+                        pyffi::PyEval_RestoreThread(thread_state);
+                    }
+                } else {
+                    self.gil = Some(GILGuard::acquire());
+                }
 
-                self.gil = Some(gil);
+                let py = unsafe { Python::assume_gil_acquired() };
                 self.py = Some(py);
 
                 py
@@ -510,6 +1278,13 @@ impl<'a> MainPythonInterpreter<'a> {
         }
     }
 
+    /// Obtain a handle on the interpreter that can be used from other threads.
+    pub fn handle(&self) -> InterpreterHandle {
+        InterpreterHandle {
+            alive: self.alive.clone(),
+        }
+    }
+
     /// Runs the interpreter with the default code execution settings.
     ///
     /// The crate was built with settings that configure what should be
@@ -520,6 +1295,12 @@ impl<'a> MainPythonInterpreter<'a> {
 
         let py = self.acquire_gil();
 
+        if let Some(key) = &self.config.self_test_env {
+            if let Ok(spec) = env::var(key) {
+                return run_self_test(py, self.config.py_modules_data, &spec);
+            }
+        }
+
         match run {
             PythonRunMode::None => Ok(py.None()),
             PythonRunMode::Repl => self.run_repl(),
@@ -628,7 +1409,7 @@ impl<'a> MainPythonInterpreter<'a> {
                 err.restore(py);
                 let matches =
                     unsafe { pyffi::PyErr_ExceptionMatches(pyffi::PyExc_SystemExit) } != 0;
-                let err = cpython::PyErr::fetch(py);
+                let mut err = cpython::PyErr::fetch(py);
 
                 if matches {
                     return PythonRunResult::Exit {
@@ -642,6 +1423,20 @@ impl<'a> MainPythonInterpreter<'a> {
                     };
                 }
 
+                if let Some(directory) = &self.config.crash_report_directory {
+                    match resolve_origin_path(directory).and_then(|directory| {
+                        write_crash_report(
+                            py,
+                            &directory,
+                            self.config.app_version.as_ref().map(|v| v.as_str()),
+                            &mut err,
+                        )
+                    }) {
+                        Ok(path) => eprintln!("crash report written to {}", path.display()),
+                        Err(msg) => eprintln!("error writing crash report: {}", msg),
+                    }
+                }
+
                 self.print_err(err);
 
                 PythonRunResult::Err {}
@@ -698,8 +1493,6 @@ impl<'a> MainPythonInterpreter<'a> {
             let res = pyffi::PyEval_EvalCode(code.as_ptr(), globals, globals);
 
             if res.is_null() {
-                let err = PyErr::fetch(py);
-                err.print(py);
                 Err(PyErr::fetch(py))
             } else {
                 Ok(PyObject::from_owned_ptr(py, res))
@@ -731,7 +1524,11 @@ impl<'a> MainPythonInterpreter<'a> {
         let stdin_filename = "<stdin>";
         let filename = CString::new(stdin_filename)
             .or_else(|_| Err(PyErr::new::<ValueError, _>(py, "could not create CString")))?;
-        let mut cf = pyffi::PyCompilerFlags { cf_flags: 0 };
+        let mut cf = pyffi::PyCompilerFlags {
+            cf_flags: 0,
+            #[cfg(Py_3_8)]
+            cf_feature_version: 8,
+        };
 
         unsafe {
             let stdin = stdin_to_file();
@@ -796,6 +1593,71 @@ impl<'a> MainPythonInterpreter<'a> {
     }
 }
 
+/// Set a ``sys`` module attribute to a string value.
+fn set_sys_str(py: Python, name: &str, value: &str) -> Result<(), &'static str> {
+    let sys = py
+        .import("sys")
+        .or_else(|_| Err("could not obtain sys module"))?;
+
+    sys.add(py, name, value)
+        .or_else(|_| Err("unable to set sys attribute"))
+}
+
+/// Expand ``$ORIGIN`` in a path to the directory of the current executable.
+fn resolve_origin_path(path: &str) -> Result<PathBuf, &'static str> {
+    let exe = env::current_exe().or_else(|_| Err("could not obtain current exe"))?;
+    let origin = exe
+        .parent()
+        .ok_or_else(|| "unable to get exe parent")?
+        .display()
+        .to_string();
+
+    Ok(PathBuf::from(path.replace("$ORIGIN", &origin)))
+}
+
+/// Read a packed data file from a directory.
+fn read_packed_data(dir: &PathBuf, name: &str) -> Result<Vec<u8>, &'static str> {
+    fs::read(dir.join(name)).or_else(|_| Err("unable to read packed data file"))
+}
+
+/// Replace packed data in a config with data from its packed data directory.
+///
+/// This is a no-op if ``packed_data_directory`` isn't defined.
+pub(crate) fn load_packed_data(config: &mut PythonConfig) -> Result<(), &'static str> {
+    let dir = match &config.packed_data_directory {
+        Some(path) => resolve_origin_path(path)?,
+        None => return Ok(()),
+    };
+
+    let mut loaded = PACKED_DATA
+        .lock()
+        .or_else(|_| Err("unable to obtain packed data state"))?;
+
+    let data = match loaded.get(&dir) {
+        Some(data) => *data,
+        None => {
+            let py_modules = read_packed_data(&dir, "py-modules")?;
+            let py_resources = read_packed_data(&dir, "python-resources")?;
+            let zip_archives = read_packed_data(&dir, "python-zip-archives")?;
+
+            let data = PackedData {
+                py_modules: Box::leak(py_modules.into_boxed_slice()),
+                py_resources: Box::leak(py_resources.into_boxed_slice()),
+                zip_archives: Box::leak(zip_archives.into_boxed_slice()),
+            };
+            loaded.insert(dir, data);
+
+            data
+        }
+    };
+
+    config.py_modules_data = data.py_modules;
+    config.py_resources_data = data.py_resources;
+    config.zip_archives_data = data.zip_archives;
+
+    Ok(())
+}
+
 /// Write loaded Python modules to a directory.
 ///
 /// Given a Python interpreter and a path to a directory, this will create a
@@ -841,17 +1703,1011 @@ fn write_modules_to_directory(py: Python, path: &PathBuf) -> Result<(), &'static
 
 impl<'a> Drop for MainPythonInterpreter<'a> {
     fn drop(&mut self) {
-        if let Some(key) = &self.config.write_modules_directory_env {
-            if let Ok(path) = env::var(key) {
-                let path = PathBuf::from(path);
-                let py = self.acquire_gil();
+        // Wait for other threads using the interpreter to finish and prevent
+        // new uses. We can't hold the GIL while waiting or threads waiting to
+        // acquire it would never finish.
+        self.release_gil();
+
+        if let Ok(mut alive) = self.alive.write() {
+            *alive = false;
+        }
+
+        // Initialization may have failed before the interpreter was
+        // initialized. Acquiring the GIL would initialize it.
+        if self.init_run {
+            self.acquire_gil();
 
-                if let Err(msg) = write_modules_to_directory(py, &path) {
-                    eprintln!("error writing modules file: {}", msg);
+            if let Some(key) = &self.config.write_modules_directory_env {
+                if let Ok(path) = env::var(key) {
+                    let path = PathBuf::from(path);
+                    let py = self.acquire_gil();
+
+                    if let Err(msg) = write_modules_to_directory(py, &path) {
+                        eprintln!("error writing modules file: {}", msg);
+                    }
                 }
             }
+
+            let _ = unsafe { pyffi::Py_FinalizeEx() };
+
+            set_import_policy_enforced(false);
+            self.py = None;
+
+            // Releasing a GILGuard after finalization would crash.
+            if let Some(gil) = self.gil.take() {
+                std::mem::forget(gil);
+            }
+        }
+
+        // Forwarded signals must not reach the finalized interpreter. And
+        // the next interpreter may not want the same signals ignored.
+        for (signal, handler) in self.original_signal_handlers.drain(..) {
+            unsafe {
+                libc::signal(signal, handler);
+            }
+        }
+
+        // Restore global state so another interpreter can be initialized.
+        unsafe {
+            if let Some(inittab) = self.original_inittab.take() {
+                pyffi::PyImport_Inittab = inittab;
+            }
+
+            if let Some(frozen_modules) = self.original_frozen_modules.take() {
+                pyffi::PyImport_FrozenModules = frozen_modules;
+            }
         }
 
-        let _ = unsafe { pyffi::Py_FinalizeEx() };
+        if let Some(dir) = &self.terminfo_dir {
+            let _ = fs::remove_dir_all(dir);
+        }
+
+        // SSL_CERT_FILE was only set if it wasn't defined, so removing it
+        // restores the environment of the process and its future children.
+        if let Some(path) = self.ssl_ca_bundle_path.take() {
+            if env::var_os("SSL_CERT_FILE") == Some(path.clone().into_os_string()) {
+                env::remove_var("SSL_CERT_FILE");
+            }
+
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "build-mode-pyoxidizer"))]
+mod tests {
+    use super::*;
+    use crate::config::PythonConfigBuilder;
+    use crate::coroutine::future_into_py;
+    use crate::data::default_python_config;
+    use crate::extension::ExtensionModuleBuilder;
+    use crate::importer::PythonModulesData;
+    use crate::pylogging::set_python_log_level;
+    use byteorder::{LittleEndian, WriteBytesExt};
+    use cpython::py_fn;
+    use log::{Level, LevelFilter, Log, Metadata, Record};
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::task::{Context, Poll, Waker};
+
+    lazy_static! {
+        // Only a single interpreter can be alive at a time.
+        static ref INTERPRETER_LOCK: Mutex<()> = Mutex::new(());
+        static ref LOG_RECORDS: Mutex<Vec<(Level, String, String)>> = Mutex::new(Vec::new());
+        // Threads resolving futures. They hold Python objects until they exit.
+        static ref FUTURE_THREADS: Mutex<Vec<std::thread::JoinHandle<()>>> = Mutex::new(Vec::new());
+    }
+
+    struct CaptureLogger;
+
+    impl Log for CaptureLogger {
+        fn enabled(&self, _metadata: &Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &Record) {
+            LOG_RECORDS.lock().unwrap().push((
+                record.level(),
+                record.target().to_string(),
+                record.args().to_string(),
+            ));
+        }
+
+        fn flush(&self) {}
+    }
+
+    static CAPTURE_LOGGER: CaptureLogger = CaptureLogger;
+
+    fn get_sys_path(py: Python) -> Vec<String> {
+        let sys = py.import("sys").unwrap();
+
+        sys.get(py, "path").unwrap().extract(py).unwrap()
+    }
+
+    #[test]
+    fn test_reinitialize_sys_paths() {
+        let _lock = INTERPRETER_LOCK.lock().unwrap();
+
+        for i in 0..3 {
+            let mut config = default_python_config();
+            config.sys_paths = vec![format!("/reinitialize/{}", i)];
+
+            let mut interp = MainPythonInterpreter::new(config).unwrap();
+            let py = interp.acquire_gil();
+
+            assert_eq!(get_sys_path(py), vec![format!("/reinitialize/{}", i)]);
+            assert!(py.import("json").is_ok());
+        }
+    }
+
+    #[test]
+    fn test_reinitialize_run_modes() {
+        let _lock = INTERPRETER_LOCK.lock().unwrap();
+
+        let modes = vec![
+            PythonRunMode::Eval {
+                code: "value = 42".to_string(),
+            },
+            PythonRunMode::Eval {
+                code: "assert 'value' not in globals()".to_string(),
+            },
+            PythonRunMode::None,
+            PythonRunMode::Eval {
+                code: "import sys; assert sys.oxidized".to_string(),
+            },
+        ];
+
+        for mode in modes {
+            let mut config = default_python_config();
+            config.run = mode;
+
+            let mut interp = MainPythonInterpreter::new(config).unwrap();
+            assert!(interp.run().is_ok());
+        }
+    }
+
+    #[test]
+    fn test_reinitialize_import_policy() {
+        let _lock = INTERPRETER_LOCK.lock().unwrap();
+
+        let mut config = default_python_config();
+        config.import_deny = vec!["json".to_string()];
+
+        {
+            let mut interp = MainPythonInterpreter::new(config).unwrap();
+            let py = interp.acquire_gil();
+            assert!(py.import("json").is_err());
+        }
+
+        let mut interp = MainPythonInterpreter::new(default_python_config()).unwrap();
+        let py = interp.acquire_gil();
+        assert!(py.import("json").is_ok());
+    }
+
+    #[test]
+    fn test_reinitialize_allocator_change() {
+        let _lock = INTERPRETER_LOCK.lock().unwrap();
+
+        // Ensure allocators are registered.
+        drop(MainPythonInterpreter::new(default_python_config()).unwrap());
+
+        let mut config = default_python_config();
+        config.raw_allocator = match config.raw_allocator {
+            PythonRawAllocator::System => PythonRawAllocator::Rust,
+            _ => PythonRawAllocator::System,
+        };
+
+        assert!(MainPythonInterpreter::new(config).is_err());
+
+        // A failed initialization doesn't prevent using the original settings.
+        let mut interp = MainPythonInterpreter::new(default_python_config()).unwrap();
+        assert!(interp.run_code("import sys").is_ok());
+    }
+
+    #[test]
+    fn test_logging_bridge() {
+        let _lock = INTERPRETER_LOCK.lock().unwrap();
+
+        log::set_logger(&CAPTURE_LOGGER).unwrap();
+        log::set_max_level(LevelFilter::Trace);
+
+        let mut config = default_python_config();
+        config.logging_bridge = true;
+
+        let mut interp = MainPythonInterpreter::new(config).unwrap();
+        let py = interp.acquire_gil();
+        set_python_log_level(py, LevelFilter::Info).unwrap();
+
+        interp
+            .run_code(
+                "import logging\n\
+                 logging.getLogger('app').info('hello %s', 'world')\n\
+                 logging.getLogger('app').debug('ignored')\n\
+                 try:\n    \
+                     1 / 0\n\
+                 except ZeroDivisionError:\n    \
+                     logging.getLogger('app.db').exception('failed')\n",
+            )
+            .unwrap();
+
+        let records = LOG_RECORDS.lock().unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(
+            records[0],
+            (Level::Info, "app".to_string(), "hello world".to_string())
+        );
+        assert_eq!(records[1].0, Level::Error);
+        assert_eq!(records[1].1, "app.db");
+        assert!(records[1].2.starts_with("failed\nTraceback"));
+        assert!(records[1]
+            .2
+            .ends_with("ZeroDivisionError: division by zero"));
+    }
+
+    #[test]
+    fn test_extension_module_builder() {
+        let _lock = INTERPRETER_LOCK.lock().unwrap();
+
+        let mut config = default_python_config();
+        config.extra_extension_modules = vec![
+            ExtensionModuleBuilder::new("rustmod")
+                .doc("Defined in Rust.")
+                .init(|py, m| m.add(py, "VALUE", 42))
+                .init(|py, m| m.add(py, "NAME", "rust"))
+                .build()
+                .unwrap(),
+            ExtensionModuleBuilder::new("rustmod_error")
+                .init(|py, _| Err(PyErr::new::<ValueError, _>(py, "bad value")))
+                .build()
+                .unwrap(),
+            ExtensionModuleBuilder::new("rustmod_panic")
+                .init(|_, _| panic!("initializer panicked"))
+                .build()
+                .unwrap(),
+        ];
+
+        let mut interp = MainPythonInterpreter::new(config).unwrap();
+
+        interp
+            .run_code(
+                "import rustmod\n\
+                 assert rustmod.__name__ == 'rustmod'\n\
+                 assert rustmod.__doc__ == 'Defined in Rust.'\n\
+                 assert rustmod.VALUE == 42\n\
+                 assert rustmod.NAME == 'rust'\n\
+                 try:\n    \
+                     import rustmod_error\n    \
+                     raise AssertionError('import should fail')\n\
+                 except ImportError as e:\n    \
+                     assert isinstance(e.__cause__, ValueError)\n    \
+                     assert str(e.__cause__) == 'bad value'\n\
+                 try:\n    \
+                     import rustmod_panic\n    \
+                     raise AssertionError('import should fail')\n\
+                 except SystemError:\n    \
+                     pass\n",
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_handle_threads() {
+        let _lock = INTERPRETER_LOCK.lock().unwrap();
+
+        let mut interp = MainPythonInterpreter::new(default_python_config()).unwrap();
+        interp.run_code("import threading\nresults = []").unwrap();
+
+        let threads = (0..2)
+            .map(|_| {
+                let handle = interp.handle();
+
+                std::thread::spawn(move || {
+                    for i in 0..50 {
+                        handle
+                            .with_gil(|py| {
+                                let code =
+                                    format!("results.append((threading.get_ident(), {}))", i);
+                                let main = py.import("__main__").unwrap();
+                                let globals = main.dict(py);
+                                py.run(&code, Some(&globals), None).unwrap();
+                            })
+                            .unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        interp.release_gil();
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        interp
+            .run_code(
+                "assert len(results) == 100\n\
+                 idents = set(ident for ident, _ in results)\n\
+                 assert len(idents) == 2\n\
+                 assert threading.get_ident() not in idents\n",
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_handle_after_drop() {
+        let _lock = INTERPRETER_LOCK.lock().unwrap();
+
+        let interp = MainPythonInterpreter::new(default_python_config()).unwrap();
+        let handle = interp.handle();
+
+        assert!(handle.is_alive());
+        assert_eq!(handle.with_gil(|_| 42), Ok(42));
+
+        drop(interp);
+
+        assert!(!handle.is_alive());
+        assert_eq!(
+            handle.with_gil(|_| 42),
+            Err("interpreter has been finalized")
+        );
+    }
+
+    /// A future resolved by another thread.
+    struct ThreadValue {
+        state: Arc<Mutex<(Option<i32>, Option<Waker>)>>,
+    }
+
+    impl Future for ThreadValue {
+        type Output = PyResult<i32>;
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+            let mut state = self.state.lock().unwrap();
+
+            match state.0.take() {
+                Some(value) => Poll::Ready(Ok(value)),
+                None => {
+                    state.1 = Some(cx.waker().clone());
+                    Poll::Pending
+                }
+            }
+        }
+    }
+
+    fn ready_future(py: Python) -> PyResult<PyObject> {
+        future_into_py(py, async { Ok(42) })
+    }
+
+    fn failed_future(py: Python) -> PyResult<PyObject> {
+        let err = PyErr::new::<ValueError, _>(py, "bad value");
+
+        future_into_py(py, async { Err::<i32, _>(err) })
+    }
+
+    fn thread_future(py: Python) -> PyResult<PyObject> {
+        let state = Arc::new(Mutex::new((None, None)));
+        let future = ThreadValue {
+            state: state.clone(),
+        };
+
+        let thread = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+
+            let waker = {
+                let mut state = state.lock().unwrap();
+                state.0 = Some(7);
+                state.1.take()
+            };
+
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        });
+        FUTURE_THREADS.lock().unwrap().push(thread);
+
+        future_into_py(py, future)
+    }
+
+    #[test]
+    fn test_future_into_py() {
+        let _lock = INTERPRETER_LOCK.lock().unwrap();
+
+        let mut config = default_python_config();
+        config.extra_extension_modules = vec![ExtensionModuleBuilder::new("rustfutures")
+            .init(|py, m| m.add(py, "ready", py_fn!(py, ready_future())))
+            .init(|py, m| m.add(py, "failed", py_fn!(py, failed_future())))
+            .init(|py, m| m.add(py, "threaded", py_fn!(py, thread_future())))
+            .build()
+            .unwrap()];
+
+        let mut interp = MainPythonInterpreter::new(config).unwrap();
+
+        interp
+            .run_code(
+                "import asyncio\n\
+                 import rustfutures\n\
+                 async def main():\n    \
+                     assert await rustfutures.ready() == 42\n    \
+                     try:\n        \
+                         await rustfutures.failed()\n        \
+                         raise AssertionError('await should fail')\n    \
+                     except ValueError as e:\n        \
+                         assert str(e) == 'bad value'\n    \
+                     assert await rustfutures.threaded() == 7\n\
+                 asyncio.run(main())\n",
+            )
+            .unwrap();
+
+        // The waker may be dropped after the future resolved, which needs the
+        // GIL. The thread must finish before the interpreter is finalized.
+        interp.release_gil();
+        for thread in FUTURE_THREADS.lock().unwrap().drain(..) {
+            thread.join().unwrap();
+        }
+    }
+
+    /// Serialize archive files in the format of embedded zip archives data.
+    fn zip_archives_data(name: &str, files: &[(&str, &[u8])]) -> &'static [u8] {
+        let mut data = Vec::new();
+        data.write_u32::<LittleEndian>(1).unwrap();
+        data.write_u32::<LittleEndian>(name.len() as u32).unwrap();
+        data.write_u32::<LittleEndian>(files.len() as u32).unwrap();
+        for (path, content) in files {
+            data.write_u32::<LittleEndian>(path.len() as u32).unwrap();
+            data.write_u32::<LittleEndian>(content.len() as u32)
+                .unwrap();
+        }
+        data.extend_from_slice(name.as_bytes());
+        for (path, _) in files {
+            data.extend_from_slice(path.as_bytes());
+        }
+        for (_, content) in files {
+            data.extend_from_slice(content);
+        }
+
+        Box::leak(data.into_boxed_slice())
+    }
+
+    #[test]
+    fn test_zip_archive_import() {
+        let _lock = INTERPRETER_LOCK.lock().unwrap();
+
+        let mut config = default_python_config();
+        config.zip_archives_data = zip_archives_data(
+            "eggpkg-1.0.egg",
+            &[
+                ("EGG-INFO/PKG-INFO", b"Name: eggpkg\n"),
+                ("eggpkg/__init__.py", b"VALUE = 21\n"),
+                ("eggpkg/data.txt", b"hello"),
+                (
+                    "eggpkg/sub.py",
+                    b"from . import VALUE\nDOUBLE = VALUE * 2\n",
+                ),
+            ],
+        );
+
+        let mut interp = MainPythonInterpreter::new(config).unwrap();
+
+        interp
+            .run_code(
+                "import importlib.resources, os, pkgutil, sys\n\
+                 import eggpkg.sub\n\
+                 archive = sys.path[-1]\n\
+                 assert archive.endswith('eggpkg-1.0.egg'), archive\n\
+                 assert eggpkg.sub.DOUBLE == 42\n\
+                 assert eggpkg.__file__ == os.path.join(archive, 'eggpkg', '__init__.py')\n\
+                 assert eggpkg.__path__ == [os.path.join(archive, 'eggpkg')]\n\
+                 loader = eggpkg.__loader__\n\
+                 assert loader.get_data(os.path.join(archive, 'EGG-INFO', 'PKG-INFO')) == b'Name: eggpkg\\n'\n\
+                 assert loader.get_source('eggpkg') == 'VALUE = 21\\n'\n\
+                 assert loader.is_package('eggpkg') and not loader.is_package('eggpkg.sub')\n\
+                 assert importlib.resources.read_text('eggpkg', 'data.txt') == 'hello'\n\
+                 assert [m.name for m in pkgutil.iter_modules([archive])] == ['eggpkg']\n\
+                 try:\n    \
+                     import eggpkg.missing\n    \
+                     raise AssertionError('import should fail')\n\
+                 except ImportError:\n    \
+                     pass\n",
+            )
+            .unwrap();
+    }
+
+    /// Remove a package and its submodules from packed modules data.
+    fn remove_package(data: &'static [u8], package: &str) -> &'static [u8] {
+        let modules = PythonModulesData::from(data).unwrap();
+        let prefix = format!("{}.", package);
+        let names = modules
+            .module_names()
+            .filter(|name| *name != package && !name.starts_with(&prefix))
+            .collect::<Vec<_>>();
+
+        let mut res = Vec::new();
+        res.write_u32::<LittleEndian>(names.len() as u32).unwrap();
+        for name in &names {
+            res.write_u32::<LittleEndian>(name.len() as u32).unwrap();
+            res.write_u32::<LittleEndian>(modules.source(name).unwrap_or_default().len() as u32)
+                .unwrap();
+            res.write_u32::<LittleEndian>(modules.bytecode(name).unwrap_or_default().len() as u32)
+                .unwrap();
+            res.write_u32::<LittleEndian>(if modules.is_package(name) { 1 } else { 0 })
+                .unwrap();
+        }
+        for name in &names {
+            res.extend_from_slice(name.as_bytes());
+        }
+        for name in &names {
+            res.extend_from_slice(modules.source(name).unwrap_or_default());
+        }
+        for name in &names {
+            res.extend_from_slice(modules.bytecode(name).unwrap_or_default());
+        }
+
+        Box::leak(res.into_boxed_slice())
+    }
+
+    fn resources_data(packages: &[(&str, &[(&str, &[u8])])]) -> &'static [u8] {
+        let mut data = Vec::new();
+        data.write_u32::<LittleEndian>(packages.len() as u32)
+            .unwrap();
+        for (package, resources) in packages {
+            data.write_u32::<LittleEndian>(package.len() as u32)
+                .unwrap();
+            data.write_u32::<LittleEndian>(resources.len() as u32)
+                .unwrap();
+            for (name, content) in resources.iter() {
+                data.write_u32::<LittleEndian>(name.len() as u32).unwrap();
+                data.write_u32::<LittleEndian>(content.len() as u32)
+                    .unwrap();
+            }
+        }
+        for (package, resources) in packages {
+            data.extend_from_slice(package.as_bytes());
+            for (name, _) in resources.iter() {
+                data.extend_from_slice(name.as_bytes());
+            }
+        }
+        for (_, resources) in packages {
+            for (_, content) in resources.iter() {
+                data.extend_from_slice(content);
+            }
+        }
+
+        Box::leak(data.into_boxed_slice())
+    }
+
+    #[test]
+    fn test_pkg_resources_provider() {
+        let _lock = INTERPRETER_LOCK.lock().unwrap();
+
+        // A stand-in for pkg_resources imported from the filesystem.
+        let dir = env::temp_dir().join(format!("pyembed-pkg-resources-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("pkg_resources.py"),
+            "providers = {}\n\
+             def register_loader_type(loader_type, factory):\n    \
+                 providers[loader_type] = factory\n",
+        )
+        .unwrap();
+
+        let mut config = default_python_config();
+        config.py_modules_data = remove_package(config.py_modules_data, "pkg_resources");
+        config.filesystem_importer = true;
+        config.sys_paths = vec![dir.display().to_string()];
+        config.py_resources_data = resources_data(&[(
+            "json",
+            &[
+                ("data.txt", b"hello"),
+                ("templates/index.html", b"<html/>"),
+                ("templates/sub/page.html", b"<p/>"),
+            ],
+        )]);
+
+        let res = MainPythonInterpreter::new(config).and_then(|mut interp| {
+            interp
+                .run_code(
+                    "import importlib.machinery, json, os, pkg_resources\n\
+                     loader = pkg_resources.__loader__\n\
+                     assert isinstance(loader, importlib.machinery.SourceFileLoader)\n\
+                     assert pkg_resources.__spec__.loader is loader\n\
+                     provider = pkg_resources.providers[type(json.__loader__)](json)\n\
+                     assert provider.has_resource('data.txt')\n\
+                     assert provider.has_resource('templates')\n\
+                     assert not provider.has_resource('missing')\n\
+                     assert provider.resource_isdir('templates')\n\
+                     assert not provider.resource_isdir('data.txt')\n\
+                     assert provider.resource_listdir('') == ['data.txt', 'templates']\n\
+                     assert provider.resource_listdir('templates/') == ['index.html', 'sub']\n\
+                     cache = os.path.join(os.path.dirname(pkg_resources.__file__), 'cache')\n\
+                     class Manager:\n    \
+                         def get_cache_path(self, archive_name, names):\n        \
+                             path = os.path.join(cache, archive_name, *names)\n        \
+                             os.makedirs(os.path.dirname(path), exist_ok=True)\n        \
+                             return path\n\
+                     path = provider.get_resource_filename(Manager(), 'data.txt')\n\
+                     assert path.startswith(cache)\n\
+                     assert open(path, 'rb').read() == b'hello'\n\
+                     path = provider.get_resource_filename(Manager(), 'templates')\n\
+                     assert os.path.isdir(path)\n\
+                     assert open(os.path.join(path, 'sub', 'page.html'), 'rb').read() == b'<p/>'\n",
+                )
+                .map(|_| ())
+                .or_else(|_| Err("error running code"))
+        });
+
+        fs::remove_dir_all(&dir).unwrap();
+        res.unwrap();
+    }
+
+    #[test]
+    fn test_config_builder_filesystem() {
+        let _lock = INTERPRETER_LOCK.lock().unwrap();
+
+        // Path configuration must not leak between interpreters importing
+        // from memory and those computing the standard library path.
+        for _ in 0..2 {
+            let config = PythonConfigBuilder::new().build().unwrap();
+            let mut interp = MainPythonInterpreter::new(config).unwrap();
+            interp
+                .run_code(
+                    "import json, os, sys\n\
+                     assert json.dumps([1]) == '[1]'\n\
+                     assert os.path.isfile(json.__file__), json.__file__\n\
+                     assert sys.path\n",
+                )
+                .unwrap();
+            drop(interp);
+
+            let mut interp = MainPythonInterpreter::new(default_python_config()).unwrap();
+            interp
+                .run_code("import json\nassert not hasattr(json, '__file__')\n")
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_config_builder() {
+        let _lock = INTERPRETER_LOCK.lock().unwrap();
+
+        let defaults = default_python_config();
+
+        assert_eq!(
+            PythonConfigBuilder::new()
+                .py_modules_data(defaults.py_modules_data)
+                .build()
+                .err(),
+            Some("packed data requires frozen importlib data")
+        );
+
+        let mut builder = PythonConfigBuilder::new()
+            .frozen_importlib(
+                defaults.frozen_importlib_data,
+                defaults.frozen_importlib_external_data,
+            )
+            .py_modules_data(defaults.py_modules_data)
+            .py_resources_data(defaults.py_resources_data)
+            .run(PythonRunMode::Eval {
+                code: "import json".to_string(),
+            });
+        for path in &defaults.sys_paths {
+            builder = builder.sys_path(path);
+        }
+        let config = builder.build().unwrap();
+        assert!(config.use_custom_importlib);
+        assert!(config.filesystem_importer);
+
+        let mut interp = MainPythonInterpreter::new(config).unwrap();
+        assert!(interp.run().is_ok());
+        interp
+            .run_code("import sys\nassert 'json' in sys.modules\n")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_packed_data_directory() {
+        let _lock = INTERPRETER_LOCK.lock().unwrap();
+
+        let mut config = default_python_config();
+
+        let dir = env::temp_dir().join(format!("pyembed-packed-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("py-modules"), config.py_modules_data).unwrap();
+        fs::write(dir.join("python-resources"), config.py_resources_data).unwrap();
+        fs::write(
+            dir.join("python-zip-archives"),
+            zip_archives_data("devpkg.egg", &[("devpkg.py", b"VALUE = 42\n")]),
+        )
+        .unwrap();
+
+        config.py_modules_data = &[];
+        config.py_resources_data = &[];
+        config.packed_data_directory = Some(dir.display().to_string());
+
+        let res = MainPythonInterpreter::new(config).and_then(|mut interp| {
+            interp
+                .run_code("import devpkg\nassert devpkg.VALUE == 42\n")
+                .map(|_| ())
+                .or_else(|_| Err("error running code"))
+        });
+
+        fs::remove_dir_all(&dir).unwrap();
+        res.unwrap();
+
+        // Data is read once per process and shared by later interpreters.
+        let mut first = default_python_config();
+        first.packed_data_directory = Some(dir.display().to_string());
+        load_packed_data(&mut first).unwrap();
+        let mut second = first.clone();
+        load_packed_data(&mut second).unwrap();
+        assert_eq!(
+            first.py_modules_data.as_ptr(),
+            second.py_modules_data.as_ptr()
+        );
+
+        let mut interp = MainPythonInterpreter::new(first).unwrap();
+        interp
+            .run_code("import devpkg\nassert devpkg.VALUE == 42\n")
+            .unwrap();
+        drop(interp);
+
+        let mut config = default_python_config();
+        config.packed_data_directory = Some(dir.join("missing").display().to_string());
+        assert!(MainPythonInterpreter::new(config).is_err());
+    }
+
+    #[test]
+    fn test_sys_prefix() {
+        let _lock = INTERPRETER_LOCK.lock().unwrap();
+
+        let mut config = default_python_config();
+        config.import_site = true;
+        config.sys_prefix = Some("/opt/prefix".to_string());
+
+        let mut interp = MainPythonInterpreter::new(config).unwrap();
+        interp
+            .run_code(
+                "import sys\n\
+                 assert sys.prefix == sys.base_prefix == '/opt/prefix'\n\
+                 assert sys.exec_prefix == sys.base_exec_prefix == '/opt/prefix'\n",
+            )
+            .unwrap();
+
+        #[cfg(Py_3_8)]
+        interp
+            .run_code("import site\nassert site.PREFIXES == ['/opt/prefix', '/opt/prefix']\n")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let _lock = INTERPRETER_LOCK.lock().unwrap();
+
+        let mut config = default_python_config();
+        config.xoptions = vec!["tracemalloc=invalid".to_string()];
+
+        // Errors are reported instead of exiting the process.
+        let err = MainPythonInterpreter::new(config).err();
+        #[cfg(Py_3_8)]
+        assert_eq!(err, Some("-X tracemalloc=NFRAME: invalid number of frames"));
+        #[cfg(not(Py_3_8))]
+        assert_eq!(err, Some("invalid number of frames for -X tracemalloc"));
+
+        let mut interp = MainPythonInterpreter::new(default_python_config()).unwrap();
+        interp.run_code("import sys").unwrap();
+    }
+
+    /// Run a test in a new process, for settings that can only be applied to
+    /// the first interpreter initialized in a process.
+    ///
+    /// Returns whether the test ran. Otherwise, this is the new process.
+    fn run_in_new_process(name: &str) -> bool {
+        if env::var_os("PYEMBED_TEST_NEW_PROCESS").is_some() {
+            return false;
+        }
+
+        let status = std::process::Command::new(env::current_exe().unwrap())
+            .args(&["--exact", &format!("pyinterp::tests::{}", name)])
+            .env("PYEMBED_TEST_NEW_PROCESS", "1")
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        true
+    }
+
+    #[test]
+    fn test_interpreter_flags() {
+        // Development mode installs debug hooks on memory allocators.
+        if run_in_new_process("test_interpreter_flags") {
+            return;
+        }
+
+        let _lock = INTERPRETER_LOCK.lock().unwrap();
+
+        let mut config = default_python_config();
+        config.utf8_mode = true;
+        config.warnoptions = vec!["ignore::ImportWarning".to_string()];
+        config.xoptions = vec!["tracemalloc".to_string(), "custom=value".to_string()];
+        config.dev_mode = true;
+
+        let mut interp = MainPythonInterpreter::new(config).unwrap();
+        interp
+            .run_code(
+                "import sys, tracemalloc\n\
+                 assert sys.flags.utf8_mode == 1\n\
+                 assert sys.warnoptions[-1] == 'ignore::ImportWarning', sys.warnoptions\n\
+                 assert 'default' in sys.warnoptions, sys.warnoptions\n\
+                 assert sys._xoptions['tracemalloc'] is True\n\
+                 assert sys._xoptions['custom'] == 'value'\n\
+                 assert sys._xoptions['dev'] is True\n\
+                 assert tracemalloc.is_tracing()\n",
+            )
+            .unwrap();
+
+        #[cfg(Py_3_8)]
+        interp
+            .run_code("import sys\nassert sys.flags.dev_mode\n")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_hash_seed() {
+        // Python derives the hash secret when the first interpreter in a
+        // process is initialized.
+        if run_in_new_process("test_hash_seed") {
+            return;
+        }
+
+        let _lock = INTERPRETER_LOCK.lock().unwrap();
+
+        env::remove_var("PYTHONHASHSEED");
+
+        let mut config = default_python_config();
+        config.ignore_python_env = false;
+        config.hash_seed = Some(42);
+
+        let mut interp = MainPythonInterpreter::new(config).unwrap();
+        interp
+            .run_code(
+                "import os, sys\n\
+                 assert sys.flags.hash_randomization\n\
+                 assert sys.maxsize < 2 ** 32 or hash('x') == 6979806268542206823\n\
+                 assert 'PYTHONHASHSEED' not in os.environ\n",
+            )
+            .unwrap();
+
+        assert!(env::var_os("PYTHONHASHSEED").is_none());
+    }
+
+    #[test]
+    fn test_hash_seed_invalid_config() {
+        let _lock = INTERPRETER_LOCK.lock().unwrap();
+
+        env::remove_var("PYTHONHASHSEED");
+
+        let mut config = default_python_config();
+        config.ignore_python_env = false;
+        config.hash_seed = Some(42);
+        config.xoptions = vec!["invalid\0option".to_string()];
+
+        assert_eq!(
+            MainPythonInterpreter::new(config).err(),
+            Some("source string has NULL bytes")
+        );
+        assert!(env::var_os("PYTHONHASHSEED").is_none());
+
+        let mut interp = MainPythonInterpreter::new(default_python_config()).unwrap();
+        interp.run_code("import sys\n").unwrap();
+    }
+
+    #[test]
+    fn test_signal_handlers_restored() {
+        let _lock = INTERPRETER_LOCK.lock().unwrap();
+
+        let sigint = current_signal_handler(libc::SIGINT);
+        let sigterm = current_signal_handler(libc::SIGTERM);
+        let forward = forward_signal_handler as extern "C" fn(libc::c_int) as libc::sighandler_t;
+
+        for _ in 0..2 {
+            let mut config = default_python_config();
+            config.ignore_signals = vec!["SIGTERM".to_string()];
+            config.forward_signals = vec!["SIGINT".to_string()];
+
+            let interp = MainPythonInterpreter::new(config).unwrap();
+            assert_eq!(current_signal_handler(libc::SIGTERM), libc::SIG_IGN);
+            assert_eq!(current_signal_handler(libc::SIGINT), forward);
+            drop(interp);
+
+            assert_eq!(current_signal_handler(libc::SIGINT), sigint);
+            assert_eq!(current_signal_handler(libc::SIGTERM), sigterm);
+        }
+
+        let mut config = default_python_config();
+        config.ignore_signals = vec!["SIGTERM".to_string()];
+        config.forward_signals = vec!["SIGINVALID".to_string()];
+        assert_eq!(
+            MainPythonInterpreter::new(config).err(),
+            Some("unknown signal in forward_signals")
+        );
+        assert_eq!(current_signal_handler(libc::SIGTERM), sigterm);
+
+        let mut interp = MainPythonInterpreter::new(default_python_config()).unwrap();
+        interp.run_code("import sys\n").unwrap();
+    }
+
+    #[test]
+    fn test_ssl_ca_bundle() {
+        let _lock = INTERPRETER_LOCK.lock().unwrap();
+
+        env::remove_var("SSL_CERT_FILE");
+        env::remove_var("SSL_CERT_DIR");
+
+        for _ in 0..2 {
+            let mut config = default_python_config();
+            config.ssl_ca_bundle_data = b"# no certificates\n";
+
+            let path = {
+                let mut interp = MainPythonInterpreter::new(config).unwrap();
+                interp
+                    .run_code(
+                        "import os, ssl\n\
+                         cafile = ssl.get_default_verify_paths().cafile\n\
+                         assert cafile and os.path.isfile(cafile), cafile\n",
+                    )
+                    .unwrap();
+
+                PathBuf::from(env::var_os("SSL_CERT_FILE").unwrap())
+            };
+
+            assert!(!path.exists());
+            assert!(env::var_os("SSL_CERT_FILE").is_none());
+        }
+    }
+
+    #[test]
+    fn test_crash_report_run_module() {
+        let _lock = INTERPRETER_LOCK.lock().unwrap();
+
+        let dir = env::temp_dir().join(format!("pyembed-crash-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("crashmod.py"),
+            "def fail():\n    raise ValueError('boom')\n\nfail()\n",
+        )
+        .unwrap();
+
+        let mut config = default_python_config();
+        config.filesystem_importer = true;
+        config.sys_paths = vec![dir.display().to_string()];
+        config.run = PythonRunMode::Module {
+            module: "crashmod".to_string(),
+        };
+        config.crash_report_directory = Some(dir.join("crashes").display().to_string());
+        config.app_version = Some("1.2.3".to_string());
+
+        let res = MainPythonInterpreter::new(config)
+            .map(|mut interp| interp.run_and_handle_error())
+            .unwrap();
+
+        let reports = fs::read_dir(dir.join("crashes"))
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect::<Vec<_>>();
+        let report = crate::json::parse(&fs::read(&reports[0]).unwrap()).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        match res {
+            PythonRunResult::Err {} => {}
+            _ => panic!("expected error result"),
+        }
+        assert_eq!(reports.len(), 1);
+
+        let exception = report.get("exception").unwrap();
+        let traceback = exception.get("traceback").unwrap().as_str().unwrap();
+        assert_eq!(exception.get("type").unwrap().as_str(), Some("ValueError"));
+        assert_eq!(exception.get("message").unwrap().as_str(), Some("boom"));
+        assert!(traceback.contains("crashmod.py\", line 4, in <module>"));
+        assert!(traceback.contains("in fail\n    raise ValueError('boom')"));
+        assert!(traceback.ends_with("ValueError: boom\n"));
+        assert_eq!(report.get("app_version").unwrap().as_str(), Some("1.2.3"));
     }
 }
@@ -0,0 +1,145 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Bridge Python's `logging` module to the `log` crate.
+
+use cpython::{
+    py_fn, NoArgs, ObjectProtocol, PyDict, PyObject, PyResult, PyString, PyTuple, Python,
+};
+use log::{log, log_enabled, Level, LevelFilter};
+
+/// Name of the `logging.Handler` subclass forwarding records to Rust.
+const HANDLER_CLASS_NAME: &str = "PyOxidizerLogHandler";
+
+/// Python logging level corresponding to `LevelFilter::Off`.
+///
+/// This is above `logging.CRITICAL`, so no records are emitted.
+const PYTHON_LEVEL_OFF: i32 = 100;
+
+/// Convert a Python logging level number to a `log` level.
+fn level_from_python(levelno: i32) -> Level {
+    if levelno >= 40 {
+        Level::Error
+    } else if levelno >= 30 {
+        Level::Warn
+    } else if levelno >= 20 {
+        Level::Info
+    } else if levelno >= 10 {
+        Level::Debug
+    } else {
+        Level::Trace
+    }
+}
+
+/// Convert a `log` level filter to a Python logging level number.
+fn level_to_python(level: LevelFilter) -> i32 {
+    match level {
+        LevelFilter::Off => PYTHON_LEVEL_OFF,
+        LevelFilter::Error => 40,
+        LevelFilter::Warn => 30,
+        LevelFilter::Info => 20,
+        LevelFilter::Debug => 10,
+        LevelFilter::Trace => 5,
+    }
+}
+
+/// Forward a `logging.LogRecord` to the `log` crate.
+///
+/// The logger name becomes the record target. Exception text is appended to
+/// the message.
+fn emit_record(py: Python, record: PyObject) -> PyResult<PyObject> {
+    let levelno: i32 = record.getattr(py, "levelno")?.extract(py)?;
+    let level = level_from_python(levelno);
+    let name: String = record.getattr(py, "name")?.extract(py)?;
+
+    if !log_enabled!(target: name.as_str(), level) {
+        return Ok(py.None());
+    }
+
+    let mut message = record
+        .call_method(py, "getMessage", NoArgs, None)?
+        .str(py)?
+        .to_string_lossy(py)
+        .to_string();
+
+    let exc_info = record.getattr(py, "exc_info")?;
+
+    if exc_info.is_true(py)? {
+        let logging = py.import("logging")?;
+        let formatter = logging.call(py, "Formatter", NoArgs, None)?;
+        let text = formatter.call_method(py, "formatException", (exc_info,), None)?;
+
+        message.push('\n');
+        message.push_str(&text.cast_as::<PyString>(py)?.to_string_lossy(py));
+    }
+
+    log!(target: name.as_str(), level, "{}", message);
+
+    Ok(py.None())
+}
+
+/// Install a `logging.Handler` on the root logger forwarding records to the
+/// `log` crate.
+pub fn install_logging_bridge(py: Python) -> Result<(), &'static str> {
+    let logging = py
+        .import("logging")
+        .or_else(|_| Err("unable to import logging"))?;
+    let builtins = py
+        .import("builtins")
+        .or_else(|_| Err("unable to import builtins"))?;
+
+    let handler_base = logging
+        .get(py, "Handler")
+        .or_else(|_| Err("unable to obtain logging.Handler"))?;
+
+    // Handler.handle() calls self.emit(record). A static method receives just
+    // the record.
+    let emit = builtins
+        .call(
+            py,
+            "staticmethod",
+            (py_fn!(py, emit_record(record: PyObject)),),
+            None,
+        )
+        .or_else(|_| Err("unable to create logging handler"))?;
+
+    let attrs = PyDict::new(py);
+    attrs
+        .set_item(py, "emit", emit)
+        .or_else(|_| Err("unable to create logging handler"))?;
+
+    let class = builtins
+        .call(
+            py,
+            "type",
+            (HANDLER_CLASS_NAME, PyTuple::new(py, &[handler_base]), attrs),
+            None,
+        )
+        .or_else(|_| Err("unable to create logging handler"))?;
+
+    let handler = class
+        .call(py, NoArgs, None)
+        .or_else(|_| Err("unable to create logging handler"))?;
+
+    logging
+        .call(py, "getLogger", NoArgs, None)
+        .and_then(|root| root.call_method(py, "addHandler", (handler,), None))
+        .or_else(|_| Err("unable to install logging handler"))?;
+
+    Ok(())
+}
+
+/// Set the level of the Python root logger.
+///
+/// Records below this level aren't emitted by Python, including to the
+/// handler forwarding records to the `log` crate. `LevelFilter::Off` disables
+/// all records.
+pub fn set_python_log_level(py: Python, level: LevelFilter) -> PyResult<()> {
+    let logging = py.import("logging")?;
+    let root = logging.call(py, "getLogger", NoArgs, None)?;
+
+    root.call_method(py, "setLevel", (level_to_python(level),), None)?;
+
+    Ok(())
+}
@@ -0,0 +1,215 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Import every module embedded in memory and report the results.
+//!
+//! Results are printed to stdout as JSON objects, one per line.
+
+use std::io::Write;
+
+use cpython::exc::{SystemExit, ValueError};
+use cpython::{NoArgs, ObjectProtocol, PyErr, PyObject, PyResult, Python};
+
+use super::importer::PythonModulesData;
+use super::json::JsonValue;
+
+/// Whether a module name matches a pattern where `*` matches any characters.
+pub(crate) fn pattern_matches(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.as_bytes();
+    let name = name.as_bytes();
+
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        if p < pattern.len() && pattern[p] == b'*' {
+            star = Some((p, n));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == name[n] {
+            p += 1;
+            n += 1;
+        } else if let Some((star_p, star_n)) = star {
+            // Let the last `*` consume one more character and retry.
+            p = star_p + 1;
+            n = star_n + 1;
+            star = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == b'*')
+}
+
+/// Module name patterns controlling which modules are imported.
+struct ModuleFilter {
+    include: Vec<String>,
+    exclude: Vec<String>,
+}
+
+impl ModuleFilter {
+    /// Parse a comma delimited list of patterns.
+    ///
+    /// Patterns prefixed with `-` exclude modules.
+    fn from_spec(spec: &str) -> ModuleFilter {
+        let mut include = Vec::new();
+        let mut exclude = Vec::new();
+
+        for pattern in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            if pattern.starts_with('-') {
+                exclude.push(pattern[1..].to_string());
+            } else {
+                include.push(pattern.to_string());
+            }
+        }
+
+        ModuleFilter { include, exclude }
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        let included =
+            self.include.is_empty() || self.include.iter().any(|p| pattern_matches(p, name));
+
+        included && !self.exclude.iter().any(|p| pattern_matches(p, name))
+    }
+}
+
+fn describe_error(py: Python, mut err: PyErr) -> String {
+    let instance = err.instance(py);
+
+    let type_name = instance.get_type(py).name(py).to_string();
+
+    match instance.str(py) {
+        Ok(message) => format!("{}: {}", type_name, message.to_string_lossy(py)),
+        Err(_) => type_name,
+    }
+}
+
+/// Flush Python's stdout so output from imported modules isn't interleaved
+/// with the report.
+fn flush_python_stdout(py: Python) {
+    if let Ok(sys) = py.import("sys") {
+        if let Ok(stdout) = sys.get(py, "stdout") {
+            let _ = stdout.call_method(py, "flush", NoArgs, None);
+        }
+    }
+}
+
+/// Import every in-memory module matching a pattern specification.
+///
+/// Raises `SystemExit` with a non-zero code if any module failed to import.
+pub fn run_self_test(py: Python, modules_data: &'static [u8], spec: &str) -> PyResult<PyObject> {
+    let modules_data = match PythonModulesData::from(modules_data) {
+        Ok(v) => v,
+        Err(msg) => return Err(PyErr::new::<ValueError, _>(py, msg)),
+    };
+
+    let filter = ModuleFilter::from_spec(spec);
+
+    let mut names = modules_data
+        .data
+        .keys()
+        .filter(|name| filter.matches(name))
+        .cloned()
+        .collect::<Vec<_>>();
+    names.sort();
+
+    let mut failed = 0;
+    let stdout = std::io::stdout();
+
+    for name in &names {
+        let res = py.import(name);
+        flush_python_stdout(py);
+
+        let record = match res {
+            Ok(_) => JsonValue::object(vec![("module", (*name).into()), ("status", "pass".into())]),
+            Err(err) => {
+                failed += 1;
+                JsonValue::object(vec![
+                    ("module", (*name).into()),
+                    ("status", "fail".into()),
+                    ("error", describe_error(py, err).into()),
+                ])
+            }
+        };
+
+        let _ = writeln!(stdout.lock(), "{}", record);
+    }
+
+    let summary = JsonValue::object(vec![
+        ("total", names.len().into()),
+        ("passed", (names.len() - failed).into()),
+        ("failed", failed.into()),
+    ]);
+    let _ = writeln!(stdout.lock(), "{}", summary);
+
+    if failed > 0 {
+        Err(PyErr::new::<SystemExit, _>(py, 1))
+    } else {
+        Ok(py.None())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pattern_matches() {
+        let cases = [
+            ("*", "", true),
+            ("*", "foo", true),
+            ("*", "foo.bar", true),
+            ("foo", "foo", true),
+            ("foo", "foobar", false),
+            ("foo", "fo", false),
+            ("foo*", "foo", true),
+            ("foo*", "foo.bar", true),
+            ("foo*", "fo", false),
+            ("foo.*", "foo.bar", true),
+            ("foo.*", "foo", false),
+            ("*.bar", "foo.bar", true),
+            ("*.bar", "foo.baz", false),
+            ("a*b*c", "abc", true),
+            ("a*b*c", "aXbYc", true),
+            ("a*b*c", "abcbc", true),
+            ("a*b*c", "aXbYcZ", false),
+            ("a*b*c", "acb", false),
+            ("**", "foo", true),
+            ("", "", true),
+            ("", "foo", false),
+        ];
+
+        for (pattern, name, expected) in cases.iter() {
+            assert_eq!(
+                pattern_matches(pattern, name),
+                *expected,
+                "pattern {:?} matching {:?}",
+                pattern,
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_module_filter() {
+        let filter = ModuleFilter::from_spec("*, -test, -test.*");
+        assert!(filter.matches("json"));
+        assert!(filter.matches("testing"));
+        assert!(!filter.matches("test"));
+        assert!(!filter.matches("test.support"));
+
+        let filter = ModuleFilter::from_spec("-test.*");
+        assert!(filter.matches("json"));
+        assert!(filter.matches("test"));
+        assert!(!filter.matches("test.support"));
+
+        let filter = ModuleFilter::from_spec("json*,");
+        assert!(filter.matches("json.decoder"));
+        assert!(!filter.matches("email"));
+
+        let filter = ModuleFilter::from_spec("");
+        assert!(filter.matches("json"));
+    }
+}
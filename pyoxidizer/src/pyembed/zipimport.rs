@@ -0,0 +1,430 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Importing Python modules from embedded zip archives.
+
+Archives, such as eggs, are embedded with their files already extracted.
+Each archive is served by an importer mimicking `zipimport.zipimporter`,
+which is registered for a `sys.path` entry named after the archive.
+*/
+
+use std::collections::{BTreeSet, HashMap};
+use std::path::MAIN_SEPARATOR;
+use std::sync::Arc;
+
+use cpython::exc::{FileNotFoundError, ImportError};
+use cpython::{
+    py_class, py_class_impl, py_coerce_item, ObjectProtocol, PyBytes, PyClone, PyDict, PyErr,
+    PyObject, PyResult, PyString, Python, PythonObject, ToPyObject,
+};
+
+use super::importer::{create_resource_reader, get_memory_view};
+
+/// Files in an archive, keyed by their `/` delimited path in the archive.
+pub type ArchiveFiles = Arc<Box<HashMap<&'static str, &'static [u8]>>>;
+
+/// Length of the header of `.pyc` files.
+const BYTECODE_HEADER_LEN: usize = 16;
+
+/// Describes where a module is in an archive.
+#[derive(Debug, PartialEq)]
+struct ArchiveModule {
+    /// Path of the module's files in the archive, without extension.
+    path: String,
+
+    /// Whether the module is a package.
+    is_package: bool,
+}
+
+impl ArchiveModule {
+    fn source_path(&self) -> String {
+        format!("{}.py", self.path)
+    }
+
+    fn bytecode_path(&self) -> String {
+        format!("{}.pyc", self.path)
+    }
+
+    /// Path of the directory holding a package's modules.
+    fn package_directory(&self) -> &str {
+        self.path.trim_end_matches("/__init__")
+    }
+}
+
+/// Find a module in an archive.
+///
+/// Like `zipimport`, packages take precedence over modules and both `.py`
+/// and `.pyc` files are recognized.
+fn find_archive_module(
+    files: &HashMap<&'static str, &'static [u8]>,
+    fullname: &str,
+) -> Option<ArchiveModule> {
+    let path = fullname.replace('.', "/");
+
+    let candidates = vec![
+        ArchiveModule {
+            path: format!("{}/__init__", path),
+            is_package: true,
+        },
+        ArchiveModule {
+            path,
+            is_package: false,
+        },
+    ];
+
+    candidates.into_iter().find(|module| {
+        files.contains_key(module.source_path().as_str())
+            || files.contains_key(module.bytecode_path().as_str())
+    })
+}
+
+/// Obtain the top-level modules in an archive and whether they are packages.
+fn archive_top_level_modules(
+    files: &HashMap<&'static str, &'static [u8]>,
+) -> BTreeSet<(String, bool)> {
+    files
+        .keys()
+        .filter_map(|path| {
+            let parts = path.split('/').collect::<Vec<_>>();
+
+            match parts.as_slice() {
+                [name, init] if *init == "__init__.py" || *init == "__init__.pyc" => {
+                    Some((name.to_string(), true))
+                }
+                [filename] if filename.ends_with(".py") => {
+                    Some((filename.trim_end_matches(".py").to_string(), false))
+                }
+                [filename] if filename.ends_with(".pyc") => {
+                    Some((filename.trim_end_matches(".pyc").to_string(), false))
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Obtain the files directly in a directory of an archive, keyed by file name.
+fn directory_files(
+    files: &HashMap<&'static str, &'static [u8]>,
+    directory: &str,
+) -> HashMap<&'static str, &'static [u8]> {
+    let prefix = format!("{}/", directory);
+
+    files
+        .iter()
+        .filter_map(|(path, data)| {
+            if path.starts_with(&prefix) && !path[prefix.len()..].contains('/') {
+                Some((&path[prefix.len()..], *data))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Python functions and values used by archive importers.
+///
+/// These are resolved once, when the importing mechanism is set up.
+pub struct ZipImporterFunctions {
+    pub marshal_loads: PyObject,
+    pub magic_number: Vec<u8>,
+    pub compile_fn: PyObject,
+    pub exec_fn: PyObject,
+    pub call_with_frames_removed: PyObject,
+    pub decode_source: PyObject,
+    pub spec_from_file_location: PyObject,
+    pub load_module_shim: PyObject,
+}
+
+/// Create an importer for an embedded archive.
+///
+/// `archive` is the path of the archive, which is its `sys.path` entry.
+pub fn create_zip_importer(
+    py: Python,
+    archive: String,
+    files: ArchiveFiles,
+    functions: &ZipImporterFunctions,
+) -> PyResult<PyOxidizerZipImporter> {
+    PyOxidizerZipImporter::create_instance(
+        py,
+        archive,
+        files,
+        functions.marshal_loads.clone_ref(py),
+        functions.magic_number.clone(),
+        functions.compile_fn.clone_ref(py),
+        functions.exec_fn.clone_ref(py),
+        functions.call_with_frames_removed.clone_ref(py),
+        functions.decode_source.clone_ref(py),
+        functions.spec_from_file_location.clone_ref(py),
+        functions.load_module_shim.clone_ref(py),
+    )
+}
+
+#[allow(unused_doc_comments)]
+/// Python type to import modules from an embedded zip archive.
+///
+/// This type implements the interface of `zipimport.zipimporter`, except
+/// for the `archive` and `prefix` attributes. It also implements
+/// importlib.abc.MetaPathFinder and importlib.abc.Loader.
+py_class!(pub class PyOxidizerZipImporter |py| {
+    data archive: String;
+    data files: ArchiveFiles;
+    data marshal_loads: PyObject;
+    data magic_number: Vec<u8>;
+    data compile_fn: PyObject;
+    data exec_fn: PyObject;
+    data call_with_frames_removed: PyObject;
+    data decode_source: PyObject;
+    data spec_from_file_location: PyObject;
+    data load_module_shim: PyObject;
+
+    def __repr__(&self) -> PyResult<String> {
+        Ok(format!("<PyOxidizerZipImporter object \"{}\">", self.archive(py)))
+    }
+
+    // Start of importlib.abc.MetaPathFinder interface.
+
+    def find_spec(&self, fullname: &PyString, _path: Option<PyObject> = None, _target: Option<PyObject> = None) -> PyResult<PyObject> {
+        let key = fullname.to_string(py)?;
+
+        match find_archive_module(self.files(py), &key) {
+            Some(module) => {
+                let kwargs = PyDict::new(py);
+                kwargs.set_item(py, "loader", self.as_object())?;
+
+                if module.is_package {
+                    let location = self.archive_path(py, module.package_directory());
+                    kwargs.set_item(py, "submodule_search_locations", vec![location])?;
+                } else {
+                    kwargs.set_item(py, "submodule_search_locations", py.None())?;
+                }
+
+                let filename = self.module_filename(py, &module);
+
+                self.spec_from_file_location(py).call(py, (fullname, filename), Some(&kwargs))
+            }
+            None => Ok(py.None()),
+        }
+    }
+
+    def invalidate_caches(&self) -> PyResult<PyObject> {
+        Ok(py.None())
+    }
+
+    // End of importlib.abc.MetaPathFinder interface.
+
+    // Start of zipimport.zipimporter interface.
+
+    def find_module(&self, fullname: &PyString, _path: Option<PyObject> = None) -> PyResult<PyObject> {
+        let key = fullname.to_string(py)?;
+
+        match find_archive_module(self.files(py), &key) {
+            Some(_) => Ok(self.as_object().clone_ref(py)),
+            None => Ok(py.None()),
+        }
+    }
+
+    def load_module(&self, fullname: &PyString) -> PyResult<PyObject> {
+        self.load_module_shim(py).call(py, (self.as_object(), fullname), None)
+    }
+
+    def get_code(&self, fullname: &PyString) -> PyResult<PyObject> {
+        let key = fullname.to_string(py)?;
+        let module = self.find_module_or_raise(py, &key)?;
+        let files = self.files(py);
+
+        // Source is preferred because we can't tell whether bytecode is
+        // stale. Bytecode is used for archives lacking source.
+        if let Some(source) = files.get(module.source_path().as_str()) {
+            let kwargs = PyDict::new(py);
+            kwargs.set_item(py, "dont_inherit", true)?;
+
+            let filename = self.archive_path(py, &module.source_path());
+
+            return self.compile_fn(py).call(
+                py,
+                (PyBytes::new(py, source), filename, "exec"),
+                Some(&kwargs),
+            );
+        }
+
+        match files.get(module.bytecode_path().as_str()) {
+            Some(data) if data.len() >= BYTECODE_HEADER_LEN && data[0..4] == self.magic_number(py)[..] => {
+                match get_memory_view(py, &data[BYTECODE_HEADER_LEN..]) {
+                    Some(mv) => self.marshal_loads(py).call(py, (mv,), None),
+                    None => Err(PyErr::fetch(py)),
+                }
+            }
+            _ => Err(PyErr::new::<ImportError, _>(py, (format!("bad magic number in {}", key), key))),
+        }
+    }
+
+    def get_source(&self, fullname: &PyString) -> PyResult<PyObject> {
+        let key = fullname.to_string(py)?;
+        let module = self.find_module_or_raise(py, &key)?;
+
+        match self.files(py).get(module.source_path().as_str()) {
+            Some(source) => self.decode_source(py).call(py, (PyBytes::new(py, source),), None),
+            None => Ok(py.None()),
+        }
+    }
+
+    def get_filename(&self, fullname: &PyString) -> PyResult<String> {
+        let key = fullname.to_string(py)?;
+        let module = self.find_module_or_raise(py, &key)?;
+
+        Ok(self.module_filename(py, &module))
+    }
+
+    def is_package(&self, fullname: &PyString) -> PyResult<bool> {
+        let key = fullname.to_string(py)?;
+
+        Ok(self.find_module_or_raise(py, &key)?.is_package)
+    }
+
+    def get_data(&self, pathname: &PyString) -> PyResult<PyBytes> {
+        let pathname = pathname.to_string(py)?;
+        let prefix = format!("{}{}", self.archive(py), MAIN_SEPARATOR);
+
+        let key = if pathname.starts_with(&prefix) {
+            &pathname[prefix.len()..]
+        } else {
+            &pathname
+        };
+        let key = key.replace(MAIN_SEPARATOR, "/");
+
+        match self.files(py).get(key.as_str()) {
+            Some(data) => Ok(PyBytes::new(py, data)),
+            None => Err(PyErr::new::<FileNotFoundError, _>(py, format!("{} not found in archive", pathname))),
+        }
+    }
+
+    def get_resource_reader(&self, fullname: &PyString) -> PyResult<PyObject> {
+        let key = fullname.to_string(py)?;
+
+        match find_archive_module(self.files(py), &key) {
+            Some(ref module) if module.is_package => {
+                let resources = Arc::new(Box::new(directory_files(self.files(py), module.package_directory())));
+
+                create_resource_reader(py, resources)
+            }
+            _ => Ok(py.None()),
+        }
+    }
+
+    // End of zipimport.zipimporter interface.
+
+    // Start of importlib.abc.Loader interface.
+
+    def create_module(&self, _spec: &PyObject) -> PyResult<PyObject> {
+        Ok(py.None())
+    }
+
+    def exec_module(&self, module: &PyObject) -> PyResult<PyObject> {
+        let name = module.getattr(py, "__name__")?;
+        let name = name.cast_as::<PyString>(py)?;
+        let code = self.get_code(py, name)?;
+        let dict = module.getattr(py, "__dict__")?;
+
+        self.call_with_frames_removed(py).call(py, (self.exec_fn(py), code, dict), None)
+    }
+
+    // End of importlib.abc.Loader interface.
+
+    // Support for pkgutil.iter_modules().
+    def iter_modules(&self, prefix: &str = "") -> PyResult<PyObject> {
+        let modules = archive_top_level_modules(self.files(py))
+            .into_iter()
+            .map(|(name, is_package)| (format!("{}{}", prefix, name), is_package))
+            .collect::<Vec<_>>();
+
+        Ok(modules.to_py_object(py).into_object())
+    }
+});
+
+impl PyOxidizerZipImporter {
+    /// Obtain the filesystem-like path of a file in the archive.
+    fn archive_path(&self, py: Python, path: &str) -> String {
+        format!(
+            "{}{}{}",
+            self.archive(py),
+            MAIN_SEPARATOR,
+            path.replace('/', &MAIN_SEPARATOR.to_string())
+        )
+    }
+
+    /// Obtain the value of `__file__` for a module.
+    fn module_filename(&self, py: Python, module: &ArchiveModule) -> String {
+        if self.files(py).contains_key(module.source_path().as_str()) {
+            self.archive_path(py, &module.source_path())
+        } else {
+            self.archive_path(py, &module.bytecode_path())
+        }
+    }
+
+    fn find_module_or_raise(&self, py: Python, fullname: &str) -> PyResult<ArchiveModule> {
+        find_archive_module(self.files(py), fullname).ok_or_else(|| {
+            PyErr::new::<ImportError, _>(py, (format!("can't find module {}", fullname), fullname))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn archive_files() -> HashMap<&'static str, &'static [u8]> {
+        let mut files = HashMap::new();
+        files.insert("EGG-INFO/PKG-INFO", &b""[..]);
+        files.insert("foo/__init__.py", &b""[..]);
+        files.insert("foo/bar.pyc", &b""[..]);
+        files.insert("foo/data.txt", &b"data"[..]);
+        files.insert("foo/templates/index.html", &b"html"[..]);
+        files.insert("single.py", &b""[..]);
+
+        files
+    }
+
+    #[test]
+    fn test_find_archive_module() {
+        let files = archive_files();
+
+        let module = find_archive_module(&files, "foo").unwrap();
+        assert_eq!(module.path, "foo/__init__");
+        assert!(module.is_package);
+        assert_eq!(module.package_directory(), "foo");
+
+        let module = find_archive_module(&files, "foo.bar").unwrap();
+        assert_eq!(module.path, "foo/bar");
+        assert!(!module.is_package);
+        assert_eq!(module.bytecode_path(), "foo/bar.pyc");
+
+        assert!(find_archive_module(&files, "single").is_some());
+        assert!(find_archive_module(&files, "foo.data").is_none());
+        assert!(find_archive_module(&files, "EGG-INFO").is_none());
+    }
+
+    #[test]
+    fn test_archive_top_level_modules() {
+        let modules = archive_top_level_modules(&archive_files())
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            modules,
+            vec![("foo".to_string(), true), ("single".to_string(), false)]
+        );
+    }
+
+    #[test]
+    fn test_directory_files() {
+        let files = directory_files(&archive_files(), "foo");
+
+        let mut names = files.keys().cloned().collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, vec!["__init__.py", "bar.pyc", "data.txt"]);
+        assert_eq!(files.get("data.txt"), Some(&&b"data"[..]));
+    }
+}
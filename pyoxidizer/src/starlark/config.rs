@@ -21,8 +21,9 @@ use super::embedded_python_config::EmbeddedPythonConfig;
 use super::env::{required_str_arg, required_type_arg};
 use super::python_distribution::PythonDistribution;
 use super::python_packaging::{
-    FilterInclude, Stdlib, StdlibExtensionVariant, StdlibExtensionsExplicitExcludes,
-    StdlibExtensionsExplicitIncludes, StdlibExtensionsPolicy, WriteLicenseFiles,
+    FileDependentPackages, FilterInclude, Stdlib, StdlibExtensionVariant,
+    StdlibExtensionsExplicitExcludes, StdlibExtensionsExplicitIncludes, StdlibExtensionsPolicy,
+    TclLibraryFiles, WriteLicenseFiles,
 };
 use super::python_run_mode::PythonRunMode;
 use crate::app_packaging::config::{
@@ -104,6 +105,9 @@ starlark_module! { config_env =>
                 "FilterInclude" => Ok(x.downcast_apply(|x: &FilterInclude| -> PythonPackaging {
                     PythonPackaging::FilterInclude(x.rule.clone())
                 })),
+                "FileDependentPackages" => Ok(x.downcast_apply(|x: &FileDependentPackages| -> PythonPackaging {
+                    PythonPackaging::FileDependentPackages(x.rule.clone())
+                })),
                 "Stdlib" => Ok(x.downcast_apply(|x: &Stdlib| -> PythonPackaging {
                     PythonPackaging::Stdlib(x.rule.clone())
                 })),
@@ -122,6 +126,9 @@ starlark_module! { config_env =>
                 "WriteLicenseFiles" => Ok(x.downcast_apply(|x: &WriteLicenseFiles| -> PythonPackaging {
                     PythonPackaging::WriteLicenseFiles(x.rule.clone())
                 })),
+                "TclLibraryFiles" => Ok(x.downcast_apply(|x: &TclLibraryFiles| -> PythonPackaging {
+                    PythonPackaging::TclLibraryFiles(x.rule.clone())
+                })),
                 t => Err(RuntimeError {
                     code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
                     message: format!("invalid packaging rule type: {}", t),
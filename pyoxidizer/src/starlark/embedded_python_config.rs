@@ -15,9 +15,13 @@ use std::any::Any;
 use std::cmp::Ordering;
 use std::collections::HashMap;
 
-use super::env::{optional_list_arg, optional_str_arg, required_bool_arg, required_type_arg};
+use super::env::{
+    optional_list_arg, optional_str_arg, optional_type_arg, required_bool_arg, required_type_arg,
+};
 use crate::app_packaging::config::default_raw_allocator;
-use crate::py_packaging::config::{RawAllocator, TerminfoResolution};
+use crate::py_packaging::config::{
+    is_known_signal, RawAllocator, SslCaBundle, TerminfoResolution, OVERLAY_FIELDS,
+};
 
 #[derive(Debug, Clone)]
 pub struct EmbeddedPythonConfig {
@@ -54,6 +58,27 @@ impl TypedValue for EmbeddedPythonConfig {
     }
 }
 
+/// Resolve the allocator to use for the mem or object domain.
+///
+/// `None` and `pymalloc` keep Python's default allocator.
+fn parse_domain_allocator(
+    name: &str,
+    value: Option<String>,
+) -> Result<Option<RawAllocator>, ValueError> {
+    match value.as_ref().map(|x| x.as_ref()) {
+        None | Some("pymalloc") => Ok(None),
+        Some("jemalloc") => Ok(Some(RawAllocator::Jemalloc)),
+        Some("rust") => Ok(Some(RawAllocator::Rust)),
+        Some("system") => Ok(Some(RawAllocator::System)),
+        _ => Err(RuntimeError {
+            code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+            message: format!("invalid value for {}", name),
+            label: format!("invalid value for {}", name),
+        }
+        .into()),
+    }
+}
+
 starlark_module! { embedded_python_config_module =>
     #[allow(non_snake_case, clippy::ptr_arg)]
     EmbeddedPythonConfig(
@@ -73,16 +98,46 @@ starlark_module! { embedded_python_config_module =>
         stdio_encoding=None,
         unbuffered_stdio=false,
         filesystem_importer=false,
+        import_allow=None,
+        import_deny=None,
         quiet=false,
         sys_frozen=false,
         sys_meipass=false,
         sys_paths=None,
         raw_allocator=None,
+        mem_allocator=None,
+        obj_allocator=None,
+        allocator_stats=false,
         terminfo_resolution="dynamic",
         terminfo_dirs=None,
+        terminfo_embed=None,
+        terminfo_embed_dir=None,
+        install_signal_handlers=true,
+        ignore_signals=None,
+        forward_signals=None,
+        overlay_path=None,
+        overlay_env=None,
+        overlay_fields=None,
         use_hash_seed=false,
         verbose=0,
-        write_modules_directory_env=None
+        utf8_mode=false,
+        coerce_c_locale=false,
+        warnoptions=None,
+        xoptions=None,
+        dev_mode=false,
+        hash_seed=None,
+        write_modules_directory_env=None,
+        self_test_env=None,
+        faulthandler=false,
+        faulthandler_path=None,
+        logging_bridge=false,
+        crash_report_directory=None,
+        app_version=None,
+        sys_prefix=None,
+        sys_exec_prefix=None,
+        sysconfig_data=false,
+        ssl_ca_bundle=None,
+        packed_data_at_runtime=false
     ) {
         required_type_arg("bytes_warning", "int", &bytes_warning)?;
         let dont_write_bytecode = required_bool_arg("dont_write_bytecode", &dont_write_bytecode)?;
@@ -99,16 +154,45 @@ starlark_module! { embedded_python_config_module =>
         let stdio_encoding = optional_str_arg("stdio_encoding", &stdio_encoding)?;
         let unbuffered_stdio = required_bool_arg("unbuffered_stdio", &unbuffered_stdio)?;
         let filesystem_importer = required_bool_arg("filesystem_importer", &filesystem_importer)?;
+        optional_list_arg("import_allow", "string", &import_allow)?;
+        optional_list_arg("import_deny", "string", &import_deny)?;
         let quiet = required_bool_arg("quiet", &quiet)?;
         let sys_frozen = required_bool_arg("sys_frozen", &sys_frozen)?;
         let sys_meipass = required_bool_arg("sys_meipass", &sys_meipass)?;
         optional_list_arg("sys_paths", "string", &sys_paths)?;
         let raw_allocator = optional_str_arg("raw_allocator", &raw_allocator)?;
+        let mem_allocator = optional_str_arg("mem_allocator", &mem_allocator)?;
+        let obj_allocator = optional_str_arg("obj_allocator", &obj_allocator)?;
+        let allocator_stats = required_bool_arg("allocator_stats", &allocator_stats)?;
         let terminfo_resolution = optional_str_arg("terminfo_resolution", &terminfo_resolution)?;
         let terminfo_dirs = optional_str_arg("terminfo_dirs", &terminfo_dirs)?;
+        optional_list_arg("terminfo_embed", "string", &terminfo_embed)?;
+        let terminfo_embed_dir = optional_str_arg("terminfo_embed_dir", &terminfo_embed_dir)?;
+        let install_signal_handlers = required_bool_arg("install_signal_handlers", &install_signal_handlers)?;
+        optional_list_arg("ignore_signals", "string", &ignore_signals)?;
+        optional_list_arg("forward_signals", "string", &forward_signals)?;
+        let overlay_path = optional_str_arg("overlay_path", &overlay_path)?;
+        let overlay_env = optional_str_arg("overlay_env", &overlay_env)?;
+        optional_list_arg("overlay_fields", "string", &overlay_fields)?;
         let use_hash_seed = required_bool_arg("use_hash_seed", &use_hash_seed)?;
         required_type_arg("verbose", "int", &verbose)?;
+        let utf8_mode = required_bool_arg("utf8_mode", &utf8_mode)?;
+        let coerce_c_locale = required_bool_arg("coerce_c_locale", &coerce_c_locale)?;
+        optional_list_arg("warnoptions", "string", &warnoptions)?;
+        optional_list_arg("xoptions", "string", &xoptions)?;
+        let dev_mode = required_bool_arg("dev_mode", &dev_mode)?;
+        optional_type_arg("hash_seed", "int", &hash_seed)?;
         let write_modules_directory_env = optional_str_arg("write_modules_directory_env", &write_modules_directory_env)?;
+        let self_test_env = optional_str_arg("self_test_env", &self_test_env)?;
+        let faulthandler = required_bool_arg("faulthandler", &faulthandler)?;
+        let faulthandler_path = optional_str_arg("faulthandler_path", &faulthandler_path)?;
+        let logging_bridge = required_bool_arg("logging_bridge", &logging_bridge)?;
+        let crash_report_directory = optional_str_arg("crash_report_directory", &crash_report_directory)?;
+        let app_version = optional_str_arg("app_version", &app_version)?;
+        let sys_prefix = optional_str_arg("sys_prefix", &sys_prefix)?;
+        let sys_exec_prefix = optional_str_arg("sys_exec_prefix", &sys_exec_prefix)?;
+        let sysconfig_data = required_bool_arg("sysconfig_data", &sysconfig_data)?;
+        let packed_data_at_runtime = required_bool_arg("packed_data_at_runtime", &packed_data_at_runtime)?;
 
         let build_target = env.get("BUILD_TARGET").unwrap().to_str();
 
@@ -135,6 +219,9 @@ starlark_module! { embedded_python_config_module =>
             None => default_raw_allocator(&build_target),
         };
 
+        let mem_allocator = parse_domain_allocator("mem_allocator", mem_allocator)?;
+        let obj_allocator = parse_domain_allocator("obj_allocator", obj_allocator)?;
+
         let terminfo_resolution = match terminfo_resolution {
             Some(x) => match x.as_ref() {
                 "dynamic" => TerminfoResolution::Dynamic,
@@ -160,11 +247,95 @@ starlark_module! { embedded_python_config_module =>
             None => TerminfoResolution::None,
         };
 
+        let ssl_ca_bundle = match optional_str_arg("ssl_ca_bundle", &ssl_ca_bundle)? {
+            Some(ref v) if v == "certifi" => Some(SslCaBundle::Certifi),
+            Some(path) => Some(SslCaBundle::File(path)),
+            None => None,
+        };
+
+        let warnoptions = match warnoptions.get_type() {
+            "list" => warnoptions.into_iter().unwrap().map(|x| x.to_string()).collect(),
+            _ => Vec::new(),
+        };
+
+        let xoptions = match xoptions.get_type() {
+            "list" => xoptions.into_iter().unwrap().map(|x| x.to_string()).collect(),
+            _ => Vec::new(),
+        };
+
+        let import_allow = match import_allow.get_type() {
+            "list" => import_allow.into_iter().unwrap().map(|x| x.to_string()).collect(),
+            _ => Vec::new(),
+        };
+
+        let import_deny = match import_deny.get_type() {
+            "list" => import_deny.into_iter().unwrap().map(|x| x.to_string()).collect(),
+            _ => Vec::new(),
+        };
+
+        let hash_seed = match hash_seed.get_type() {
+            "int" => {
+                let seed = hash_seed.to_int().unwrap();
+
+                // Python accepts seeds in the range [0; 4294967295].
+                if seed < 0 || seed > i64::from(std::u32::MAX) {
+                    return Err(RuntimeError {
+                        code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                        message: "hash_seed must be between 0 and 4294967295".to_string(),
+                        label: "invalid value for hash_seed".to_string(),
+                    }.into());
+                }
+
+                Some(seed as u32)
+            }
+            _ => None,
+        };
+
+        let terminfo_embed = match terminfo_embed.get_type() {
+            "list" => terminfo_embed.into_iter().unwrap().map(|x| x.to_string()).collect(),
+            _ => Vec::new(),
+        };
+
         let sys_paths = match sys_paths.get_type() {
             "list" => sys_paths.into_iter().unwrap().map(|x| x.to_string()).collect(),
             _ => Vec::new(),
         };
 
+        let ignore_signals: Vec<String> = match ignore_signals.get_type() {
+            "list" => ignore_signals.into_iter().unwrap().map(|x| x.to_string()).collect(),
+            _ => Vec::new(),
+        };
+
+        let forward_signals: Vec<String> = match forward_signals.get_type() {
+            "list" => forward_signals.into_iter().unwrap().map(|x| x.to_string()).collect(),
+            _ => Vec::new(),
+        };
+
+        for signal in ignore_signals.iter().chain(forward_signals.iter()) {
+            if !is_known_signal(signal, &build_target) {
+                return Err(RuntimeError {
+                    code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                    message: format!("unknown signal: {}", signal),
+                    label: "invalid signal name".to_string(),
+                }.into());
+            }
+        }
+
+        let overlay_fields: Vec<String> = match overlay_fields.get_type() {
+            "list" => overlay_fields.into_iter().unwrap().map(|x| x.to_string()).collect(),
+            _ => Vec::new(),
+        };
+
+        for field in &overlay_fields {
+            if !OVERLAY_FIELDS.contains(&field.as_str()) {
+                return Err(RuntimeError {
+                    code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                    message: format!("{} cannot be overridden by a config overlay", field),
+                    label: "invalid value for overlay_fields".to_string(),
+                }.into());
+            }
+        }
+
         let config = crate::py_packaging::config::EmbeddedPythonConfig {
             bytes_warning: bytes_warning.to_int().unwrap() as i32,
             dont_write_bytecode,
@@ -183,14 +354,44 @@ starlark_module! { embedded_python_config_module =>
             stdio_encoding_errors,
             unbuffered_stdio,
             filesystem_importer,
+            import_allow,
+            import_deny,
             sys_frozen,
             sys_meipass,
             sys_paths,
             raw_allocator,
+            mem_allocator,
+            obj_allocator,
+            allocator_stats,
             terminfo_resolution,
+            terminfo_embed,
+            terminfo_embed_dir,
+            install_signal_handlers,
+            ignore_signals,
+            forward_signals,
+            overlay_path,
+            overlay_env,
+            overlay_fields,
             use_hash_seed,
             verbose: verbose.to_int().unwrap() as i32,
+            utf8_mode,
+            coerce_c_locale,
+            warnoptions,
+            xoptions,
+            dev_mode,
+            hash_seed,
             write_modules_directory_env,
+            self_test_env,
+            faulthandler,
+            faulthandler_path,
+            logging_bridge,
+            crash_report_directory,
+            app_version,
+            sys_prefix,
+            sys_exec_prefix,
+            sysconfig_data,
+            ssl_ca_bundle,
+            packed_data_at_runtime,
         };
 
         Ok(Value::new(EmbeddedPythonConfig { config }))
@@ -223,16 +424,46 @@ mod tests {
             quiet: false,
             use_hash_seed: false,
             verbose: 0,
+            utf8_mode: false,
+            coerce_c_locale: false,
+            warnoptions: Vec::new(),
+            xoptions: Vec::new(),
+            dev_mode: false,
+            hash_seed: None,
             stdio_encoding_name: None,
             stdio_encoding_errors: None,
             unbuffered_stdio: false,
             filesystem_importer: false,
+            import_allow: Vec::new(),
+            import_deny: Vec::new(),
             sys_frozen: false,
             sys_meipass: false,
             sys_paths: Vec::new(),
             raw_allocator: default_raw_allocator(crate::app_packaging::repackage::HOST),
+            mem_allocator: None,
+            obj_allocator: None,
+            allocator_stats: false,
             terminfo_resolution: TerminfoResolution::Dynamic,
+            terminfo_embed: Vec::new(),
+            terminfo_embed_dir: None,
+            install_signal_handlers: true,
+            ignore_signals: Vec::new(),
+            forward_signals: Vec::new(),
+            overlay_path: None,
+            overlay_env: None,
+            overlay_fields: Vec::new(),
             write_modules_directory_env: None,
+            self_test_env: None,
+            faulthandler: false,
+            faulthandler_path: None,
+            logging_bridge: false,
+            crash_report_directory: None,
+            app_version: None,
+            sys_prefix: None,
+            sys_exec_prefix: None,
+            sysconfig_data: false,
+            ssl_ca_bundle: None,
+            packed_data_at_runtime: false,
         };
 
         c.downcast_apply(|x: &EmbeddedPythonConfig| assert_eq!(x.config, wanted));
@@ -299,4 +530,199 @@ mod tests {
             );
         });
     }
+
+    #[test]
+    fn test_signals() {
+        let c = starlark_ok("EmbeddedPythonConfig(install_signal_handlers=False, ignore_signals=['SIGPIPE'], forward_signals=['SIGTERM', 'SIGHUP'])");
+        c.downcast_apply(|x: &EmbeddedPythonConfig| {
+            assert!(!x.config.install_signal_handlers);
+            assert_eq!(x.config.ignore_signals, ["SIGPIPE"]);
+            assert_eq!(x.config.forward_signals, ["SIGTERM", "SIGHUP"]);
+        });
+
+        let err = starlark_nok("EmbeddedPythonConfig(ignore_signals=['SIGFOO'])");
+        assert_eq!(err.message, "unknown signal: SIGFOO");
+
+        let err = starlark_nok("EmbeddedPythonConfig(forward_signals=['sigterm'])");
+        assert_eq!(err.message, "unknown signal: sigterm");
+    }
+
+    #[test]
+    fn test_overlay() {
+        let c = starlark_ok("EmbeddedPythonConfig(overlay_path='$ORIGIN/app.json', overlay_env='APP_CONFIG', overlay_fields=['sys_paths', 'run'])");
+        c.downcast_apply(|x: &EmbeddedPythonConfig| {
+            assert_eq!(x.config.overlay_path, Some("$ORIGIN/app.json".to_string()));
+            assert_eq!(x.config.overlay_env, Some("APP_CONFIG".to_string()));
+            assert_eq!(x.config.overlay_fields, ["sys_paths", "run"]);
+        });
+
+        let err = starlark_nok("EmbeddedPythonConfig(overlay_fields=['py_modules_data'])");
+        assert_eq!(
+            err.message,
+            "py_modules_data cannot be overridden by a config overlay"
+        );
+    }
+
+    #[test]
+    fn test_self_test_env() {
+        let c = starlark_ok("EmbeddedPythonConfig(self_test_env='PYOXIDIZER_SELF_TEST')");
+        c.downcast_apply(|x: &EmbeddedPythonConfig| {
+            assert_eq!(
+                x.config.self_test_env,
+                Some("PYOXIDIZER_SELF_TEST".to_string())
+            );
+        });
+    }
+
+    #[test]
+    fn test_crash_reporting() {
+        let c = starlark_ok("EmbeddedPythonConfig(faulthandler=True, faulthandler_path='$ORIGIN/faults.log', crash_report_directory='$ORIGIN/crashes', app_version='1.2.3')");
+        c.downcast_apply(|x: &EmbeddedPythonConfig| {
+            assert!(x.config.faulthandler);
+            assert_eq!(
+                x.config.faulthandler_path,
+                Some("$ORIGIN/faults.log".to_string())
+            );
+            assert_eq!(
+                x.config.crash_report_directory,
+                Some("$ORIGIN/crashes".to_string())
+            );
+            assert_eq!(x.config.app_version, Some("1.2.3".to_string()));
+        });
+    }
+
+    #[test]
+    fn test_sys_prefix() {
+        let c = starlark_ok("EmbeddedPythonConfig(sys_prefix='$ORIGIN', sys_exec_prefix='$ORIGIN/exec', sysconfig_data=True)");
+        c.downcast_apply(|x: &EmbeddedPythonConfig| {
+            assert_eq!(x.config.sys_prefix, Some("$ORIGIN".to_string()));
+            assert_eq!(x.config.sys_exec_prefix, Some("$ORIGIN/exec".to_string()));
+            assert!(x.config.sysconfig_data);
+        });
+    }
+
+    #[test]
+    fn test_terminfo_embed() {
+        let c = starlark_ok(
+            "EmbeddedPythonConfig(terminfo_embed=['xterm', 'xterm-256color'], terminfo_embed_dir='/usr/share/terminfo')",
+        );
+        c.downcast_apply(|x: &EmbeddedPythonConfig| {
+            assert_eq!(x.config.terminfo_embed, ["xterm", "xterm-256color"]);
+            assert_eq!(
+                x.config.terminfo_embed_dir,
+                Some("/usr/share/terminfo".to_string())
+            );
+        });
+    }
+
+    #[test]
+    fn test_ssl_ca_bundle() {
+        let c = starlark_ok("EmbeddedPythonConfig(ssl_ca_bundle='certifi')");
+        c.downcast_apply(|x: &EmbeddedPythonConfig| {
+            assert_eq!(x.config.ssl_ca_bundle, Some(SslCaBundle::Certifi));
+        });
+
+        let c = starlark_ok("EmbeddedPythonConfig(ssl_ca_bundle='certs/cacert.pem')");
+        c.downcast_apply(|x: &EmbeddedPythonConfig| {
+            assert_eq!(
+                x.config.ssl_ca_bundle,
+                Some(SslCaBundle::File("certs/cacert.pem".to_string()))
+            );
+        });
+    }
+
+    #[test]
+    fn test_packed_data_at_runtime() {
+        let c = starlark_ok("EmbeddedPythonConfig(packed_data_at_runtime=True)");
+        c.downcast_apply(|x: &EmbeddedPythonConfig| {
+            assert!(x.config.packed_data_at_runtime);
+        });
+    }
+
+    #[test]
+    fn test_utf8_mode() {
+        let c = starlark_ok("EmbeddedPythonConfig(utf8_mode=True, coerce_c_locale=True)");
+        c.downcast_apply(|x: &EmbeddedPythonConfig| {
+            assert!(x.config.utf8_mode);
+            assert!(x.config.coerce_c_locale);
+        });
+    }
+
+    #[test]
+    fn test_warn_and_x_options() {
+        let c = starlark_ok(
+            "EmbeddedPythonConfig(warnoptions=['error::DeprecationWarning'], xoptions=['importtime', 'tracemalloc=5'], dev_mode=True)",
+        );
+        c.downcast_apply(|x: &EmbeddedPythonConfig| {
+            assert_eq!(x.config.warnoptions, ["error::DeprecationWarning"]);
+            assert_eq!(x.config.xoptions, ["importtime", "tracemalloc=5"]);
+            assert!(x.config.dev_mode);
+        });
+
+        let err = starlark_nok("EmbeddedPythonConfig(warnoptions='error')");
+        assert!(err
+            .message
+            .starts_with("function expects a list for warnoptions"));
+    }
+
+    #[test]
+    fn test_hash_seed() {
+        let c = starlark_ok("EmbeddedPythonConfig(hash_seed=42)");
+        c.downcast_apply(|x: &EmbeddedPythonConfig| {
+            assert_eq!(x.config.hash_seed, Some(42));
+        });
+
+        let c = starlark_ok("EmbeddedPythonConfig(hash_seed=0)");
+        c.downcast_apply(|x: &EmbeddedPythonConfig| {
+            assert_eq!(x.config.hash_seed, Some(0));
+        });
+
+        let err = starlark_nok("EmbeddedPythonConfig(hash_seed=-1)");
+        assert_eq!(err.message, "hash_seed must be between 0 and 4294967295");
+
+        let err = starlark_nok("EmbeddedPythonConfig(hash_seed='42')");
+        assert_eq!(
+            err.message,
+            "function expects a int for hash_seed; got type string"
+        );
+    }
+
+    #[test]
+    fn test_import_policy() {
+        let c = starlark_ok(
+            "EmbeddedPythonConfig(import_allow=['myapp', 'encodings.*'], import_deny=['ctypes', 'subprocess'])",
+        );
+        c.downcast_apply(|x: &EmbeddedPythonConfig| {
+            assert_eq!(x.config.import_allow, ["myapp", "encodings.*"]);
+            assert_eq!(x.config.import_deny, ["ctypes", "subprocess"]);
+        });
+
+        let err = starlark_nok("EmbeddedPythonConfig(import_deny='ctypes')");
+        assert!(err
+            .message
+            .starts_with("function expects a list for import_deny"));
+    }
+
+    #[test]
+    fn test_logging_bridge() {
+        let c = starlark_ok("EmbeddedPythonConfig(logging_bridge=True)");
+        c.downcast_apply(|x: &EmbeddedPythonConfig| {
+            assert!(x.config.logging_bridge);
+        });
+    }
+
+    #[test]
+    fn test_domain_allocators() {
+        let c = starlark_ok(
+            "EmbeddedPythonConfig(mem_allocator='jemalloc', obj_allocator='pymalloc', allocator_stats=True)",
+        );
+        c.downcast_apply(|x: &EmbeddedPythonConfig| {
+            assert_eq!(x.config.mem_allocator, Some(RawAllocator::Jemalloc));
+            assert_eq!(x.config.obj_allocator, None);
+            assert!(x.config.allocator_stats);
+        });
+
+        let err = starlark_nok("EmbeddedPythonConfig(obj_allocator='foo')");
+        assert_eq!(err.message, "invalid value for obj_allocator");
+    }
 }
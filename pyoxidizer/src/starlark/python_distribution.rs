@@ -32,7 +32,7 @@ use crate::py_packaging::distribution::{
 use crate::py_packaging::distutils::{prepare_hacked_distutils, read_built_extensions};
 use crate::py_packaging::fsscan::{find_python_resources, PythonFileResource};
 use crate::py_packaging::pip::pip_install as raw_pip_install;
-use crate::py_packaging::resource::PythonResource;
+use crate::py_packaging::resource::{PythonResource, ZipArchive};
 use crate::python_distributions::CPYTHON_BY_TRIPLE;
 
 #[derive(Debug, Clone)]
@@ -108,6 +108,11 @@ fn find_resources(path: &Path, state_dir: Option<&Path>) -> Result<Vec<PythonRes
                 res.push(PythonResource::try_from(&r)?);
             }
 
+            // Zipped eggs are exploded into the resources they contain.
+            PythonFileResource::EggFile { path } => {
+                res.extend(ZipArchive::from_path(&path)?.python_resources()?);
+            }
+
             _ => {}
         }
     }
@@ -19,11 +19,12 @@ use super::env::{
     optional_list_arg, required_bool_arg, required_list_arg, required_str_arg, required_type_arg,
 };
 use crate::app_packaging::config::{
-    resolve_install_location, PackagingFilterInclude, PackagingStdlib,
-    PackagingStdlibExtensionVariant, PackagingStdlibExtensionsExplicitExcludes,
-    PackagingStdlibExtensionsExplicitIncludes, PackagingStdlibExtensionsPolicy,
-    PackagingWriteLicenseFiles,
+    resolve_install_location, InstallLocation, PackagingFileDependentPackages,
+    PackagingFilterInclude, PackagingStdlib, PackagingStdlibExtensionVariant,
+    PackagingStdlibExtensionsExplicitExcludes, PackagingStdlibExtensionsExplicitIncludes,
+    PackagingStdlibExtensionsPolicy, PackagingTclLibraryFiles, PackagingWriteLicenseFiles,
 };
+use crate::py_packaging::audit::{resolve_source_usage, SourceUsage};
 use crate::py_packaging::distribution::ExtensionModuleFilter;
 
 #[derive(Debug, Clone)]
@@ -61,6 +62,41 @@ impl TypedValue for FilterInclude {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct FileDependentPackages {
+    pub rule: PackagingFileDependentPackages,
+}
+
+impl TypedValue for FileDependentPackages {
+    immutable!();
+    any!();
+    not_supported!(binop);
+    not_supported!(container);
+    not_supported!(function);
+    not_supported!(get_hash);
+    not_supported!(to_int);
+
+    fn to_str(&self) -> String {
+        format!("FileDependentPackages<{:#?}>", self.rule)
+    }
+
+    fn to_repr(&self) -> String {
+        self.to_str()
+    }
+
+    fn get_type(&self) -> &'static str {
+        "FileDependentPackages"
+    }
+
+    fn to_bool(&self) -> bool {
+        true
+    }
+
+    fn compare(&self, other: &dyn TypedValue, _recursion: u32) -> Result<Ordering, ValueError> {
+        default_compare(self, other)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct StdlibExtensionsPolicy {
     pub rule: PackagingStdlibExtensionsPolicy,
@@ -271,6 +307,41 @@ impl TypedValue for WriteLicenseFiles {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct TclLibraryFiles {
+    pub rule: PackagingTclLibraryFiles,
+}
+
+impl TypedValue for TclLibraryFiles {
+    immutable!();
+    any!();
+    not_supported!(binop);
+    not_supported!(container);
+    not_supported!(function);
+    not_supported!(get_hash);
+    not_supported!(to_int);
+
+    fn to_str(&self) -> String {
+        format!("TclLibraryFiles<{:#?}>", self.rule)
+    }
+
+    fn to_repr(&self) -> String {
+        self.to_str()
+    }
+
+    fn get_type(&self) -> &'static str {
+        "TclLibraryFiles"
+    }
+
+    fn to_bool(&self) -> bool {
+        true
+    }
+
+    fn compare(&self, other: &dyn TypedValue, _recursion: u32) -> Result<Ordering, ValueError> {
+        default_compare(self, other)
+    }
+}
+
 starlark_module! { python_packaging_env =>
     #[allow(non_snake_case, clippy::ptr_arg)]
     FilterInclude(files=None, glob_files=None) {
@@ -296,6 +367,54 @@ starlark_module! { python_packaging_env =>
         Ok(Value::new(FilterInclude { rule }))
     }
 
+    #[allow(non_snake_case, clippy::ptr_arg)]
+    FileDependentPackages(
+        install_location="app-relative:lib",
+        usages=None,
+        excludes=None
+    ) {
+        let install_location = required_str_arg("install_location", &install_location)?;
+        optional_list_arg("usages", "string", &usages)?;
+        optional_list_arg("excludes", "string", &excludes)?;
+
+        let install_location = match resolve_install_location(&install_location) {
+            Ok(InstallLocation::AppRelative { path }) => Ok(InstallLocation::AppRelative { path }),
+            Ok(InstallLocation::Embedded) => Err("install_location must be app-relative".to_string()),
+            Err(e) => Err(e),
+        }.or_else(|e| {
+            Err(RuntimeError {
+                code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                message: e.to_string(),
+                label: e.to_string(),
+            }.into())
+        })?;
+
+        let usages = match usages.get_type() {
+            "list" => usages.into_iter()?.map(|x| resolve_source_usage(&x.to_string())).collect::<Result<Vec<SourceUsage>, String>>().or_else(|e| {
+                Err(RuntimeError {
+                    code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                    message: e.to_string(),
+                    label: e.to_string(),
+                }.into())
+            })?,
+            "NoneType" => vec![SourceUsage::File, SourceUsage::CtypesLibrary],
+            _ => panic!("should have validated type above"),
+        };
+        let excludes = match excludes.get_type() {
+            "list" => excludes.into_iter()?.map(|x| x.to_string()).collect(),
+            "NoneType" => Vec::new(),
+            _ => panic!("should have validated type above"),
+        };
+
+        let rule = PackagingFileDependentPackages {
+            usages,
+            excludes,
+            install_location,
+        };
+
+        Ok(Value::new(FileDependentPackages { rule }))
+    }
+
     #[allow(non_snake_case, clippy::ptr_arg)]
     StdlibExtensionsPolicy(policy) {
         let policy = required_str_arg("policy", &policy)?;
@@ -409,13 +528,23 @@ starlark_module! { python_packaging_env =>
 
         Ok(Value::new(WriteLicenseFiles { rule }))
     }
+
+    #[allow(non_snake_case, clippy::ptr_arg)]
+    TclLibraryFiles(path) {
+        let path = required_str_arg("path", &path)?;
+
+        let rule = PackagingTclLibraryFiles {
+            path,
+        };
+
+        Ok(Value::new(TclLibraryFiles { rule }))
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::super::testutil::*;
     use super::*;
-    use crate::app_packaging::config::InstallLocation;
 
     #[test]
     fn test_filter_include_default() {
@@ -428,6 +557,43 @@ mod tests {
         v.downcast_apply(|x: &FilterInclude| assert_eq!(x.rule, wanted));
     }
 
+    #[test]
+    fn test_file_dependent_packages_default() {
+        let v = starlark_ok("FileDependentPackages()");
+        let wanted = PackagingFileDependentPackages {
+            usages: vec![SourceUsage::File, SourceUsage::CtypesLibrary],
+            excludes: Vec::new(),
+            install_location: InstallLocation::AppRelative {
+                path: "lib".to_string(),
+            },
+        };
+        v.downcast_apply(|x: &FileDependentPackages| assert_eq!(x.rule, wanted));
+    }
+
+    #[test]
+    fn test_file_dependent_packages_basic() {
+        let v = starlark_ok(
+            "FileDependentPackages('app-relative:packages', usages=['__file__'], excludes=['foo'])",
+        );
+        let wanted = PackagingFileDependentPackages {
+            usages: vec![SourceUsage::File],
+            excludes: vec!["foo".to_string()],
+            install_location: InstallLocation::AppRelative {
+                path: "packages".to_string(),
+            },
+        };
+        v.downcast_apply(|x: &FileDependentPackages| assert_eq!(x.rule, wanted));
+    }
+
+    #[test]
+    fn test_file_dependent_packages_invalid() {
+        let err = starlark_nok("FileDependentPackages('embedded')");
+        assert_eq!(err.message, "install_location must be app-relative");
+
+        let err = starlark_nok("FileDependentPackages(usages=['sys.argv'])");
+        assert_eq!(err.message, "invalid source usage: sys.argv");
+    }
+
     #[test]
     fn test_stdlib_extensions_policy_default() {
         let err = starlark_nok("StdlibExtensionsPolicy()");
@@ -523,4 +689,19 @@ mod tests {
         };
         v.downcast_apply(|x: &WriteLicenseFiles| assert_eq!(x.rule, wanted));
     }
+
+    #[test]
+    fn test_tcl_library_files_default() {
+        let err = starlark_nok("TclLibraryFiles()");
+        assert!(err.message.starts_with("Missing parameter path"));
+    }
+
+    #[test]
+    fn test_tcl_library_files_basic() {
+        let v = starlark_ok("TclLibraryFiles('tcl')");
+        let wanted = PackagingTclLibraryFiles {
+            path: "tcl".to_string(),
+        };
+        v.downcast_apply(|x: &TclLibraryFiles| assert_eq!(x.rule, wanted));
+    }
 }
@@ -14,12 +14,14 @@ use starlark::{
 use std::any::Any;
 use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::path::PathBuf;
 
-use super::env::required_type_arg;
+use super::env::{required_str_arg, required_type_arg};
 use crate::py_packaging::distribution::ExtensionModule;
 use crate::py_packaging::embedded_resource::EmbeddedPythonResourcesPrePackaged;
 use crate::py_packaging::resource::{
     BytecodeModule, BytecodeOptimizationLevel, PythonResource, ResourceData, SourceModule,
+    ZipArchive,
 };
 
 #[derive(Debug, Clone)]
@@ -270,6 +272,61 @@ impl TypedValue for PythonExtensionModule {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct PythonZipArchive {
+    pub archive: ZipArchive,
+}
+
+impl TypedValue for PythonZipArchive {
+    immutable!();
+    any!();
+    not_supported!(
+        binop, dir_attr, function, get_hash, indexable, iterable, sequence, set_attr, to_int
+    );
+
+    fn to_str(&self) -> String {
+        format!("PythonZipArchive<name={}>", self.archive.name)
+    }
+
+    fn to_repr(&self) -> String {
+        self.to_str()
+    }
+
+    fn get_type(&self) -> &'static str {
+        "PythonZipArchive"
+    }
+
+    fn to_bool(&self) -> bool {
+        true
+    }
+
+    fn compare(&self, other: &dyn TypedValue, _recursion: u32) -> Result<Ordering, ValueError> {
+        default_compare(self, other)
+    }
+
+    fn get_attr(&self, attribute: &str) -> ValueResult {
+        let v = match attribute {
+            "name" => Value::new(self.archive.name.clone()),
+            attr => {
+                return Err(ValueError::OperationNotSupported {
+                    op: format!(".{}", attr),
+                    left: "PythonZipArchive".to_string(),
+                    right: None,
+                })
+            }
+        };
+
+        Ok(v)
+    }
+
+    fn has_attr(&self, attribute: &str) -> Result<bool, ValueError> {
+        Ok(match attribute {
+            "name" => true,
+            _ => false,
+        })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct PythonEmbeddedResources {
     pub embedded: EmbeddedPythonResourcesPrePackaged,
@@ -356,6 +413,10 @@ impl<'a> From<&'a PythonResource> for Value {
             PythonResource::BuiltExtensionModule(_em) => {
                 panic!("not yet implemented");
             }
+
+            PythonResource::ZipArchive(archive) => Value::new(PythonZipArchive {
+                archive: archive.clone(),
+            }),
         }
     }
 }
@@ -438,4 +499,119 @@ starlark_module! { python_resource_env =>
 
         Ok(Value::new(None))
     }
+
+    #[allow(clippy::ptr_arg)]
+    PythonEmbeddedResources.add_zip_archive(this, archive) {
+        required_type_arg("archive", "PythonZipArchive", &archive)?;
+
+        this.downcast_apply_mut(|embedded: &mut PythonEmbeddedResources| {
+            let a = archive.downcast_apply(|a: &PythonZipArchive| a.archive.clone());
+            embedded.embedded.add_zip_archive(&a);
+        });
+
+        Ok(Value::new(None))
+    }
+
+    #[allow(non_snake_case, clippy::ptr_arg)]
+    PythonZipArchive(env env, path) {
+        let path = PathBuf::from(required_str_arg("path", &path)?);
+
+        let path = if path.is_absolute() {
+            path
+        } else {
+            let cwd = env.get("CWD").expect("CWD not defined").to_string();
+            PathBuf::from(cwd).join(path)
+        };
+
+        let archive = ZipArchive::from_path(&path).or_else(|e| Err(
+            RuntimeError {
+                code: "ZIP_ARCHIVE_ERROR",
+                message: e,
+                label: "PythonZipArchive()".to_string(),
+            }.into()
+        ))?;
+
+        Ok(Value::new(PythonZipArchive { archive }))
+    }
+
+    #[allow(clippy::ptr_arg)]
+    PythonZipArchive.python_resources(this) {
+        let resources = this.downcast_apply(|a: &PythonZipArchive| a.archive.python_resources()).or_else(|e| Err(
+            RuntimeError {
+                code: "ZIP_ARCHIVE_ERROR",
+                message: format!("could not find resources: {}", e),
+                label: "python_resources()".to_string(),
+            }.into()
+        ))?;
+
+        Ok(Value::from(resources.iter().map(Value::from).collect::<Vec<Value>>()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testutil::*;
+    use super::*;
+    use std::io::Write;
+
+    fn write_zip(path: &std::path::Path) {
+        let mut writer = zip::ZipWriter::new(std::fs::File::create(path).unwrap());
+
+        for (name, data) in &[
+            ("foo/__init__.py", &b""[..]),
+            ("foo/data.txt", &b"data"[..]),
+        ] {
+            writer
+                .start_file(*name, zip::write::FileOptions::default())
+                .unwrap();
+            writer.write_all(data).unwrap();
+        }
+
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn test_zip_archive_missing() {
+        let err = starlark_nok("PythonZipArchive('/does/not/exist.egg')");
+        assert_eq!(err.message, "unable to read /does/not/exist.egg");
+    }
+
+    #[test]
+    fn test_zip_archive() {
+        let td = tempdir::TempDir::new("pyoxidizer-test").unwrap();
+        let path = td.path().join("foo-1.0-py3.7.egg");
+        write_zip(&path);
+
+        let mut env = starlark_env();
+        starlark_eval_in_env(
+            &mut env,
+            &format!("archive = PythonZipArchive('{}')", path.display()),
+        )
+        .unwrap();
+
+        let archive = starlark_eval_in_env(&mut env, "archive").unwrap();
+        assert_eq!(archive.get_type(), "PythonZipArchive");
+        assert_eq!(
+            starlark_eval_in_env(&mut env, "archive.name")
+                .unwrap()
+                .to_str(),
+            "foo-1.0-py3.7.egg"
+        );
+
+        let types =
+            starlark_eval_in_env(&mut env, "[str(r) for r in archive.python_resources()]").unwrap();
+        assert_eq!(
+            types.to_str(),
+            "[\"PythonSourceModule<name=foo>\", \"PythonResourceData<package=foo, name=data.txt>\"]"
+        );
+
+        starlark_eval_in_env(&mut env, "resources = PythonEmbeddedResources()").unwrap();
+        starlark_eval_in_env(&mut env, "resources.add_zip_archive(archive)").unwrap();
+
+        let resources = starlark_eval_in_env(&mut env, "resources").unwrap();
+        resources.downcast_apply(|r: &PythonEmbeddedResources| {
+            let archive = &r.embedded.zip_archives["foo-1.0-py3.7.egg"];
+            assert_eq!(archive.files["foo/data.txt"], b"data");
+        });
+    }
 }
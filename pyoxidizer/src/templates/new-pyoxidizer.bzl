@@ -20,15 +20,45 @@ embedded_python_config = EmbeddedPythonConfig(
 #     stdio_encoding=None,
 #     unbuffered_stdio=False,
 #     filesystem_importer=False,
+#     import_allow=None,
+#     import_deny=None,
 #     sys_frozen=False,
 #     sys_meipass=False,
 #     sys_paths=None,
 #     raw_allocator=None,
+#     mem_allocator=None,
+#     obj_allocator=None,
+#     allocator_stats=False,
 #     terminfo_resolution="dynamic",
 #     terminfo_dirs=None,
+#     terminfo_embed=None,
+#     terminfo_embed_dir=None,
+#     install_signal_handlers=True,
+#     ignore_signals=None,
+#     forward_signals=None,
+#     overlay_path=None,
+#     overlay_env=None,
+#     overlay_fields=None,
 #     use_hash_seed=False,
 #     verbose=0,
+#     utf8_mode=False,
+#     coerce_c_locale=False,
+#     warnoptions=None,
+#     xoptions=None,
+#     dev_mode=False,
+#     hash_seed=None,
 #     write_modules_directory_env=None,
+#     self_test_env=None,
+#     faulthandler=False,
+#     faulthandler_path=None,
+#     logging_bridge=False,
+#     crash_report_directory=None,
+#     app_version=None,
+#     sys_prefix=None,
+#     sys_exec_prefix=None,
+#     sysconfig_data=False,
+#     ssl_ca_bundle=None,
+#     packed_data_at_runtime=False,
 )
 
 # This variable captures all packaging rules. Append to it to perform
@@ -63,6 +93,9 @@ packaging_rules.append(Stdlib(include_source=False))
 # Write out license files next to the produced binary.
 packaging_rules.append(WriteLicenseFiles(""))
 
+# Install Tcl/Tk library files needed by tkinter.
+#packaging_rules.append(TclLibraryFiles("lib"))
+
 {{#each pip_install_simple}}
 packaging_rules.append(PipInstallSimple("{{{ this }}}"))
 {{/each}}
@@ -85,6 +118,10 @@ packaging_rules.append(PipInstallSimple("{{{ this }}}"))
 # in a file.
 #packaging_rules.append(FilterInclude(files=["/path/to/filter-file"]))
 
+# Install packages relying on __file__ or ctypes library loading into a
+# "lib" directory next to the binary instead of embedding them. Requires sys_paths=["$ORIGIN/lib"] in the embedded Python config.
+#packaging_rules.append(FileDependentPackages("app-relative:lib"))
+
 # How Python should run by default. This is only needed if you
 # call ``run()``. For applications customizing how the embedded
 # Python interpreter is invoked, this section is not relevant.
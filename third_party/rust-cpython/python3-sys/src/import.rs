@@ -75,7 +75,7 @@ pub unsafe fn PyImport_ImportModuleEx(name: *const c_char,
 #[cfg(not(Py_LIMITED_API))]
 pub struct _inittab {
     pub name: *mut c_char,
-    pub initfunc: Option<unsafe extern "C" fn()>,
+    pub initfunc: Option<unsafe extern "C" fn() -> *mut PyObject>,
 }
 
 #[repr(C)]
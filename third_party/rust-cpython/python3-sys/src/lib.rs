@@ -57,6 +57,7 @@ pub use pystate::*;
 pub use pyarena::*;
 pub use modsupport::*;
 pub use pythonrun::*;
+#[cfg(all(Py_3_8, not(Py_LIMITED_API)))] pub use initconfig::*;
 pub use ceval::*;
 pub use sysmodule::*;
 #[cfg(Py_3_6)] pub use osmodule::*;
@@ -137,6 +138,7 @@ mod pystate; // TODO supports PEP-384 only; needs adjustment for Python 3.3 and
 #[cfg(not(Py_LIMITED_API))] mod pyarena; // TODO: incomplete
 mod modsupport; // TODO supports PEP-384 only; needs adjustment for Python 3.3 and 3.5
 mod pythonrun; // TODO some functions need to be moved to pylifecycle
+#[cfg(all(Py_3_8, not(Py_LIMITED_API)))] mod initconfig;
 //mod pylifecycle; // TODO new in 3.5
 mod ceval; // TODO supports PEP-384 only; needs adjustment for Python 3.3 and 3.5
 mod sysmodule; // TODO supports PEP-384 only; needs adjustment for Python 3.3 and 3.5